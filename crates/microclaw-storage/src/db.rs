@@ -1,5 +1,6 @@
 use rusqlite::OptionalExtension;
 use rusqlite::{params, Connection};
+use std::borrow::Cow;
 use std::path::Path;
 #[cfg(feature = "sqlite-vec")]
 use std::sync::Once;
@@ -11,6 +12,73 @@ pub struct Database {
     conn: Mutex<Connection>,
 }
 
+/// Disagreement between `memories` and the sqlite-vec `memories_vec` index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VectorIndexDesync {
+    /// Memories with `embedding_model` set but no row in `memories_vec`.
+    pub missing_vectors: usize,
+    /// Rows in `memories_vec` whose memory no longer exists.
+    pub orphan_vectors: usize,
+}
+
+impl VectorIndexDesync {
+    pub fn total(&self) -> usize {
+        self.missing_vectors + self.orphan_vectors
+    }
+}
+
+/// Reported by [`Database::prepare_vector_index`] when the configured
+/// embedding dimension or distance metric differs from the one the index was
+/// built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectorDimensionChange {
+    pub previous: usize,
+    pub current: usize,
+    pub previous_metric: VectorMetric,
+    pub current_metric: VectorMetric,
+    /// Memories whose stored embedding was discarded and must be re-embedded.
+    pub cleared_embeddings: usize,
+}
+
+/// Distance function the sqlite-vec `memories_vec` index is built with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VectorMetric {
+    #[default]
+    Cosine,
+    /// Euclidean distance, for models whose vectors are not normalized.
+    L2,
+}
+
+impl VectorMetric {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VectorMetric::Cosine => "cosine",
+            VectorMetric::L2 => "l2",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "cosine" => Some(VectorMetric::Cosine),
+            "l2" | "euclidean" => Some(VectorMetric::L2),
+            _ => None,
+        }
+    }
+
+    /// Map a KNN distance to a similarity where higher means closer. Cosine
+    /// gives `1 - distance`; L2 distance is unbounded, so it is inverted to
+    /// `1 / (1 + distance)`.
+    pub fn similarity(self, distance: f32) -> f32 {
+        match self {
+            VectorMetric::Cosine => 1.0 - distance,
+            VectorMetric::L2 => 1.0 / (1.0 + distance.max(0.0)),
+        }
+    }
+}
+
+/// Sentinel `data_dir` for [`Database::new`] that opens an in-memory SQLite database.
+pub const IN_MEMORY_DATA_DIR: &str = ":memory:";
+
 #[cfg(feature = "sqlite-vec")]
 static SQLITE_VEC_AUTOEXT_INIT: Once = Once::new();
 
@@ -21,6 +89,42 @@ type SqliteAutoExtensionFn = unsafe extern "C" fn(
     *const rusqlite::ffi::sqlite3_api_routines,
 ) -> i32;
 
+#[cfg(feature = "sqlite-vec")]
+fn vector_index_desync_locked(conn: &Connection) -> Result<VectorIndexDesync, MicroClawError> {
+    let missing: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM memories m
+         WHERE m.embedding_model IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM memories_vec v WHERE v.rowid = m.id)",
+        [],
+        |row| row.get(0),
+    )?;
+    let orphan: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM memories_vec v
+         WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = v.rowid)",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(VectorIndexDesync {
+        missing_vectors: missing as usize,
+        orphan_vectors: orphan as usize,
+    })
+}
+
+#[cfg(feature = "sqlite-vec")]
+fn vector_metric_locked(conn: &Connection) -> Result<VectorMetric, MicroClawError> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT value FROM db_meta WHERE key = 'embedding_metric'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(stored
+        .as_deref()
+        .and_then(VectorMetric::parse)
+        .unwrap_or_default())
+}
+
 pub async fn call_blocking<T, F>(db: std::sync::Arc<Database>, f: F) -> Result<T, MicroClawError>
 where
     T: Send + 'static,
@@ -95,6 +199,152 @@ pub struct Memory {
     pub last_seen_at: String,
     pub is_archived: bool,
     pub archived_at: Option<String>,
+    /// Pinned memories are never archived, merged, or superseded by
+    /// maintenance and always surface in recall.
+    pub pinned: bool,
+    /// Times the memory was injected into a prompt by recall.
+    pub access_count: i64,
+    /// File the memory refers to (image, PDF, ...). Only the retrieval
+    /// text is embedded and searched; this is a pointer returned alongside it.
+    pub attachment_path: Option<String>,
+    /// Normalized form searched and embedded in place of `content`, which
+    /// is still what recall shows. Cleared whenever `content` changes.
+    pub search_text: Option<String>,
+    /// Short human-facing heading shown before `content`. Keyword search
+    /// ranks title hits above body hits, and embedding weights it the same way.
+    pub title: Option<String>,
+}
+
+/// How many ranked matches to read so that grouping can fill
+/// `limit_per_category` slots in each category.
+pub fn grouped_search_limit(limit_per_category: usize) -> usize {
+    limit_per_category
+        .saturating_mul(crate::memory_quality::MemoryCategory::ALL.len())
+        .saturating_mul(GROUPED_SEARCH_HEADROOM)
+}
+
+/// Bucket already-ranked `memories` by category, keeping the first
+/// `limit_per_category` of each. Rows with an unrecognised category are
+/// dropped, and categories left empty are absent.
+pub fn group_memories_by_category(
+    memories: Vec<Memory>,
+    limit_per_category: usize,
+) -> std::collections::HashMap<crate::memory_quality::MemoryCategory, Vec<Memory>> {
+    let mut groups: std::collections::HashMap<_, Vec<Memory>> = std::collections::HashMap::new();
+    for memory in memories {
+        let Some(category) = crate::memory_quality::MemoryCategory::parse(&memory.category) else {
+            continue;
+        };
+        let group = groups.entry(category).or_default();
+        if group.len() < limit_per_category {
+            group.push(memory);
+        }
+    }
+    groups.retain(|_, group| !group.is_empty());
+    groups
+}
+
+/// Columns selected for a [`Memory`], in the order [`memory_from_row`] reads
+/// them.
+const MEMORY_SELECT_COLUMNS: &str = "id, chat_id, content, category, created_at, updated_at, \
+     embedding_model, confidence, source, last_seen_at, is_archived, archived_at, pinned, \
+     access_count, attachment_path, search_text, title";
+
+fn memory_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Memory> {
+    Ok(Memory {
+        id: row.get(0)?,
+        chat_id: row.get(1)?,
+        content: row.get(2)?,
+        category: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        embedding_model: row.get(6)?,
+        confidence: row.get(7)?,
+        source: row.get(8)?,
+        last_seen_at: row.get(9)?,
+        is_archived: row.get::<_, i64>(10)? != 0,
+        archived_at: row.get(11)?,
+        pinned: row.get::<_, i64>(12)? != 0,
+        access_count: row.get(13)?,
+        attachment_path: row.get(14)?,
+        search_text: row.get(15)?,
+        title: row.get(16)?,
+    })
+}
+
+fn parse_utc(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+}
+
+impl Memory {
+    /// `created_at` parsed and normalized to UTC, whatever offset it was
+    /// written with. `None` if the stored string is not RFC 3339.
+    pub fn created_at_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        parse_utc(&self.created_at)
+    }
+
+    /// `updated_at` parsed and normalized to UTC.
+    pub fn updated_at_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        parse_utc(&self.updated_at)
+    }
+
+    /// Text to match and embed: `search_text` when set, else `content`,
+    /// led by the title when there is one.
+    pub fn retrieval_text(&self) -> Cow<'_, str> {
+        compose_retrieval_text(
+            self.title.as_deref(),
+            self.search_text.as_deref().unwrap_or(&self.content),
+        )
+    }
+
+    /// `content` as recall shows it, prefixed with the title when set.
+    pub fn display_text(&self) -> Cow<'_, str> {
+        match self.title.as_deref() {
+            Some(title) => Cow::Owned(format!("{title}: {}", self.content)),
+            None => Cow::Borrowed(&self.content),
+        }
+    }
+}
+
+/// Join a memory's title and body into the text that is embedded. The title
+/// is written twice so a few heading words are not drowned out by a long body.
+fn compose_retrieval_text<'a>(title: Option<&str>, body: &'a str) -> Cow<'a, str> {
+    match title {
+        Some(title) => Cow::Owned(format!("{title}\n{title}\n\n{body}")),
+        None => Cow::Borrowed(body),
+    }
+}
+
+/// How [`Database::search_memories_with_options`] matches a multi-word query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryMatchMode {
+    /// The whole query must appear as one substring.
+    #[default]
+    Phrase,
+    /// Every whitespace-separated term must appear somewhere.
+    All,
+    /// Any single term is enough.
+    Any,
+}
+
+impl MemoryMatchMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "phrase" => Some(Self::Phrase),
+            "all" => Some(Self::All),
+            "any" => Some(Self::Any),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemorySnapshot {
+    pub label: String,
+    pub created_at: String,
+    pub row_count: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -180,7 +430,22 @@ pub struct AuditLogRecord {
 pub type SessionMetaRow = (String, String, Option<String>, Option<i64>);
 pub type SessionTreeRow = (i64, Option<String>, Option<i64>, String);
 
-const SCHEMA_VERSION_CURRENT: i64 = 8;
+const SCHEMA_VERSION_CURRENT: i64 = 16;
+
+/// Largest `k` sqlite-vec accepts in a KNN query; larger values fail the
+/// whole query rather than returning fewer rows.
+pub const MAX_KNN_CANDIDATES: usize = 4096;
+
+/// Only the first this-many ids of a recall are paired by
+/// [`Database::record_co_recall`], bounding it to 45 pair writes.
+pub const MAX_CO_RECALL_IDS: usize = 10;
+
+/// Pass as a `limit` to return every match (SQLite's largest `LIMIT`).
+pub const UNLIMITED: usize = i64::MAX as usize;
+
+/// Grouped search reads this many ranked matches per category slot, leaving
+/// room for one crowded category without scanning every match.
+const GROUPED_SEARCH_HEADROOM: usize = 4;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -207,6 +472,18 @@ fn table_has_column(conn: &Connection, table: &str, column: &str) -> Result<bool
     Ok(false)
 }
 
+#[cfg(feature = "sqlite-vec")]
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, MicroClawError> {
+    let found: Option<i64> = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE name = ?1",
+            params![table],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(found.is_some())
+}
+
 fn ensure_memory_schema(conn: &Connection) -> Result<(), MicroClawError> {
     if !table_has_column(conn, "memories", "embedding_model")? {
         conn.execute("ALTER TABLE memories ADD COLUMN embedding_model TEXT", [])?;
@@ -530,6 +807,89 @@ fn apply_schema_migrations(conn: &Connection) -> Result<(), MicroClawError> {
         set_schema_version(conn, 8)?;
         version = 8;
     }
+    if version < 9 {
+        if !table_has_column(conn, "memories", "pinned")? {
+            conn.execute(
+                "ALTER TABLE memories ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+        set_schema_version(conn, 9)?;
+        version = 9;
+    }
+    if version < 10 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS memory_snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                label TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL,
+                row_count INTEGER NOT NULL DEFAULT 0
+            );",
+        )?;
+        set_schema_version(conn, 10)?;
+        version = 10;
+    }
+    if version < 11 {
+        if !table_has_column(conn, "memories", "access_count")? {
+            conn.execute(
+                "ALTER TABLE memories ADD COLUMN access_count INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+        set_schema_version(conn, 11)?;
+        version = 11;
+    }
+    if version < 12 {
+        if !table_has_column(conn, "memories", "attachment_path")? {
+            conn.execute("ALTER TABLE memories ADD COLUMN attachment_path TEXT", [])?;
+        }
+        set_schema_version(conn, 12)?;
+        version = 12;
+    }
+    if version < 13 {
+        if !table_has_column(conn, "memories", "search_text")? {
+            conn.execute("ALTER TABLE memories ADD COLUMN search_text TEXT", [])?;
+        }
+        set_schema_version(conn, 13)?;
+        version = 13;
+    }
+    if version < 14 {
+        // Pairs are stored once with a_id < b_id.
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS memory_links (
+                a_id INTEGER NOT NULL,
+                b_id INTEGER NOT NULL,
+                count INTEGER NOT NULL DEFAULT 0,
+                last_seen_at TEXT NOT NULL,
+                PRIMARY KEY (a_id, b_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_memory_links_b ON memory_links(b_id);
+            CREATE TRIGGER IF NOT EXISTS memory_links_on_delete
+            AFTER DELETE ON memories
+            BEGIN
+                DELETE FROM memory_links WHERE a_id = OLD.id OR b_id = OLD.id;
+            END;",
+        )?;
+        set_schema_version(conn, 14)?;
+        version = 14;
+    }
+    if version < 15 {
+        if !table_has_column(conn, "memories", "title")? {
+            conn.execute("ALTER TABLE memories ADD COLUMN title TEXT", [])?;
+        }
+        set_schema_version(conn, 15)?;
+        version = 15;
+    }
+    if version < 16 {
+        if !table_has_column(conn, "memories", "embedding_attempts")? {
+            conn.execute(
+                "ALTER TABLE memories ADD COLUMN embedding_attempts INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+        set_schema_version(conn, 16)?;
+        version = 16;
+    }
     if version != SCHEMA_VERSION_CURRENT {
         set_schema_version(conn, SCHEMA_VERSION_CURRENT)?;
     }
@@ -544,10 +904,33 @@ impl Database {
         }
     }
 
+    /// Open (or create) `<data_dir>/microclaw.db`. Passing [`IN_MEMORY_DATA_DIR`]
+    /// opens a private in-memory database instead, with no files on disk.
     pub fn new(data_dir: &str) -> Result<Self, MicroClawError> {
-        let db_path = Path::new(data_dir).join("microclaw.db");
+        if data_dir == IN_MEMORY_DATA_DIR {
+            return Self::open_at(None);
+        }
         std::fs::create_dir_all(data_dir)?;
+        Self::open_at(Some(&Path::new(data_dir).join("microclaw.db")))
+    }
+
+    /// Open (or create) the database at an explicit file path, creating its
+    /// parent directory, e.g. to keep it on a faster volume than `data_dir`.
+    pub fn open_file(path: &Path) -> Result<Self, MicroClawError> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::open_at(Some(path))
+    }
+
+    /// How long a call waits for a lock held by another connection (another
+    /// process, or a backup) before failing with `database is locked`.
+    pub fn set_busy_timeout(&self, timeout: std::time::Duration) -> Result<(), MicroClawError> {
+        self.lock_conn().busy_timeout(timeout)?;
+        Ok(())
+    }
 
+    fn open_at(path: Option<&Path>) -> Result<Self, MicroClawError> {
         #[cfg(feature = "sqlite-vec")]
         SQLITE_VEC_AUTOEXT_INIT.call_once(|| unsafe {
             let init_fn_ptr = sqlite_vec::sqlite3_vec_init as *const ();
@@ -555,8 +938,14 @@ impl Database {
             rusqlite::ffi::sqlite3_auto_extension(Some(init_fn));
         });
 
-        let conn = Connection::open(db_path)?;
-        conn.execute_batch("PRAGMA journal_mode=WAL;")?;
+        let conn = match path {
+            None => Connection::open_in_memory()?,
+            Some(path) => {
+                let conn = Connection::open(path)?;
+                conn.execute_batch("PRAGMA journal_mode=WAL;")?;
+                conn
+            }
+        };
 
         conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS chats (
@@ -774,6 +1163,75 @@ impl Database {
         })
     }
 
+    /// Prove the database still accepts writes: insert and delete a scratch
+    /// `db_meta` row in one committed transaction. The commit flushes the
+    /// touched pages, so a read-only or full filesystem fails here instead
+    /// of on the next real store, and no row is left behind.
+    pub fn check_writable(&self) -> Result<(), MicroClawError> {
+        let conn = self.lock_conn();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO db_meta(key, value) VALUES('health_check', ?1)",
+            params![chrono::Utc::now().to_rfc3339()],
+        )?;
+        tx.execute("DELETE FROM db_meta WHERE key = 'health_check'", [])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Write a consistent standalone copy of the database to `dest` with
+    /// SQLite's online backup API; copying a live WAL-mode file is not safe.
+    /// File databases are read through a separate read-only connection, so
+    /// other queries keep running while pages are copied.
+    pub fn backup_to(&self, dest: &Path) -> Result<(), MicroClawError> {
+        if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let source_path = self
+            .lock_conn()
+            .path()
+            .filter(|p| !p.is_empty())
+            .map(std::path::PathBuf::from);
+        // Copy under a temporary name and rename once complete, so a failed
+        // or interrupted run never leaves a partial file that looks like a
+        // backup.
+        let mut partial_name = dest.file_name().unwrap_or_default().to_os_string();
+        partial_name.push(".partial");
+        let partial = dest.with_file_name(partial_name);
+        let _ = std::fs::remove_file(&partial);
+        let result =
+            (|| {
+                let mut target = Connection::open(&partial)?;
+                // All pages in one step: the copy is a consistent snapshot even
+                // while other connections write.
+                let copy = |source: &Connection, target: &mut Connection| {
+                    match rusqlite::backup::Backup::new(source, target)?.step(-1)? {
+                        rusqlite::backup::StepResult::Done => Ok(()),
+                        other => Err(MicroClawError::Io(std::io::Error::other(format!(
+                            "database backup did not finish: {other:?}"
+                        )))),
+                    }
+                };
+                match &source_path {
+                    Some(path) => {
+                        let source = Connection::open_with_flags(
+                            path,
+                            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+                        )?;
+                        copy(&source, &mut target)?;
+                    }
+                    None => copy(&self.lock_conn(), &mut target)?,
+                }
+                drop(target);
+                std::fs::rename(&partial, dest)?;
+                Ok(())
+            })();
+        if result.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        result
+    }
+
     pub fn upsert_chat(
         &self,
         chat_id: i64,
@@ -2153,33 +2611,18 @@ impl Database {
         limit: usize,
     ) -> Result<Vec<Memory>, MicroClawError> {
         let conn = self.lock_conn();
-        let mut stmt = conn.prepare(
-            "SELECT id, chat_id, content, category, created_at, updated_at, embedding_model,
-                    confidence, source, last_seen_at, is_archived, archived_at
+        // Runs on every recall; reuse the compiled statement.
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {MEMORY_SELECT_COLUMNS}
              FROM memories
              WHERE (chat_id = ?1 OR chat_id IS NULL)
                AND is_archived = 0
-               AND confidence >= 0.45
-             ORDER BY updated_at DESC
-             LIMIT ?2",
-        )?;
+               AND (confidence >= 0.45 OR pinned = 1)
+             ORDER BY pinned DESC, updated_at DESC
+             LIMIT ?2"
+        ))?;
         let memories = stmt
-            .query_map(params![chat_id, limit as i64], |row| {
-                Ok(Memory {
-                    id: row.get(0)?,
-                    chat_id: row.get(1)?,
-                    content: row.get(2)?,
-                    category: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    embedding_model: row.get(6)?,
-                    confidence: row.get(7)?,
-                    source: row.get(8)?,
-                    last_seen_at: row.get(9)?,
-                    is_archived: row.get::<_, i64>(10)? != 0,
-                    archived_at: row.get(11)?,
-                })
-            })?
+            .query_map(params![chat_id, limit as i64], memory_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
         Ok(memories)
     }
@@ -2189,29 +2632,101 @@ impl Database {
         chat_id: Option<i64>,
     ) -> Result<Vec<Memory>, MicroClawError> {
         let conn = self.lock_conn();
-        let mut stmt = conn.prepare(
-            "SELECT id, chat_id, content, category, created_at, updated_at, embedding_model,
-                    confidence, source, last_seen_at, is_archived, archived_at
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {MEMORY_SELECT_COLUMNS}
              FROM memories
-             WHERE (chat_id = ?1 OR (?1 IS NULL AND chat_id IS NULL))",
+             WHERE (chat_id = ?1 OR (?1 IS NULL AND chat_id IS NULL))"
+        ))?;
+        let memories = stmt
+            .query_map(params![chat_id], memory_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(memories)
+    }
+
+    /// Bump `access_count` for every id in one statement. Returns rows updated.
+    pub fn record_memory_access(&self, ids: &[i64]) -> Result<usize, MicroClawError> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let conn = self.lock_conn();
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let updated = conn.execute(
+            &format!(
+                "UPDATE memories SET access_count = access_count + 1 WHERE id IN ({placeholders})"
+            ),
+            rusqlite::params_from_iter(ids),
+        )?;
+        Ok(updated)
+    }
+
+    /// Count every pair among `ids` as recalled together once more. Returns
+    /// how many pairs were written.
+    pub fn record_co_recall(&self, ids: &[i64]) -> Result<usize, MicroClawError> {
+        let mut ids: Vec<i64> = ids.iter().copied().take(MAX_CO_RECALL_IDS).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        if ids.len() < 2 {
+            return Ok(0);
+        }
+        let conn = self.lock_conn();
+        let tx = conn.unchecked_transaction()?;
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut pairs = 0;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO memory_links (a_id, b_id, count, last_seen_at)
+                 VALUES (?1, ?2, 1, ?3)
+                 ON CONFLICT(a_id, b_id) DO UPDATE
+                 SET count = count + 1, last_seen_at = excluded.last_seen_at",
+            )?;
+            for (i, a) in ids.iter().enumerate() {
+                for b in &ids[i + 1..] {
+                    pairs += stmt.execute(params![a, b, now])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(pairs)
+    }
+
+    /// Active memories most often recalled together with `id`, as
+    /// `(id, times_recalled_together)`, highest count first.
+    pub fn related_memories(
+        &self,
+        id: i64,
+        limit: usize,
+    ) -> Result<Vec<(i64, i64)>, MicroClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT l.other, l.count
+             FROM (
+                SELECT b_id AS other, count FROM memory_links WHERE a_id = ?1
+                UNION ALL
+                SELECT a_id AS other, count FROM memory_links WHERE b_id = ?1
+             ) l
+             JOIN memories m ON m.id = l.other
+             WHERE m.is_archived = 0
+             ORDER BY l.count DESC, l.other ASC
+             LIMIT ?2",
         )?;
+        let rows = stmt.query_map(params![id, limit as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Active memories that recall has surfaced most often.
+    pub fn get_most_accessed_memories(&self, limit: usize) -> Result<Vec<Memory>, MicroClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {MEMORY_SELECT_COLUMNS}
+             FROM memories
+             WHERE is_archived = 0 AND access_count > 0
+             ORDER BY access_count DESC, updated_at DESC
+             LIMIT ?1"
+        ))?;
         let memories = stmt
-            .query_map(params![chat_id], |row| {
-                Ok(Memory {
-                    id: row.get(0)?,
-                    chat_id: row.get(1)?,
-                    content: row.get(2)?,
-                    category: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    embedding_model: row.get(6)?,
-                    confidence: row.get(7)?,
-                    source: row.get(8)?,
-                    last_seen_at: row.get(9)?,
-                    is_archived: row.get::<_, i64>(10)? != 0,
-                    archived_at: row.get(11)?,
-                })
-            })?
+            .query_map(params![limit as i64], memory_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
         Ok(memories)
     }
@@ -2234,25 +2749,104 @@ impl Database {
         query: &str,
         limit: usize,
     ) -> Result<Vec<Memory>, MicroClawError> {
-        self.search_memories_with_options(chat_id, query, limit, false, true)
+        self.search_memories_with_options(
+            chat_id,
+            query,
+            MemoryMatchMode::Phrase,
+            limit,
+            false,
+            true,
+            None,
+        )
+    }
+
+    /// Like [`Database::search_memories`], but only memories created at or
+    /// after `since`. A hard cutoff, unlike recency weighting in ranking.
+    pub fn search_memories_since(
+        &self,
+        chat_id: i64,
+        query: &str,
+        limit: usize,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Memory>, MicroClawError> {
+        self.search_memories_with_options(
+            chat_id,
+            query,
+            MemoryMatchMode::Phrase,
+            limit,
+            false,
+            true,
+            Some(since),
+        )
+    }
+
+    /// Run one keyword search and bucket the matches by category, keeping up
+    /// to `limit_per_category` of the best-ranked in each. Categories with no
+    /// match are absent; rows with an unrecognised category are dropped.
+    pub fn search_memories_grouped(
+        &self,
+        chat_id: i64,
+        query: &str,
+        match_mode: MemoryMatchMode,
+        limit_per_category: usize,
+    ) -> Result<
+        std::collections::HashMap<crate::memory_quality::MemoryCategory, Vec<Memory>>,
+        MicroClawError,
+    > {
+        let matches = self.search_memories_with_options(
+            chat_id,
+            query,
+            match_mode,
+            grouped_search_limit(limit_per_category),
+            false,
+            true,
+            None,
+        )?;
+        Ok(group_memories_by_category(matches, limit_per_category))
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn search_memories_with_options(
         &self,
         chat_id: i64,
         query: &str,
+        match_mode: MemoryMatchMode,
         limit: usize,
         include_archived: bool,
         broad_recall: bool,
+        since: Option<chrono::DateTime<chrono::Utc>>,
     ) -> Result<Vec<Memory>, MicroClawError> {
         let conn = self.lock_conn();
-        let pattern = format!("%{}%", query.to_lowercase());
-        let mut sql = String::from(
-            "SELECT id, chat_id, content, category, created_at, updated_at, embedding_model,
-                    confidence, source, last_seen_at, is_archived, archived_at
+        let query = query.to_lowercase();
+        let patterns: Vec<String> = match match_mode {
+            MemoryMatchMode::Phrase => vec![format!("%{query}%")],
+            MemoryMatchMode::All | MemoryMatchMode::Any => query
+                .split_whitespace()
+                .map(|term| format!("%{term}%"))
+                .collect(),
+        };
+        if patterns.is_empty() {
+            return Ok(Vec::new());
+        }
+        let joiner = if match_mode == MemoryMatchMode::Any {
+            " OR "
+        } else {
+            " AND "
+        };
+        let term_clause = (0..patterns.len())
+            .map(|i| {
+                format!(
+                    "(LOWER(COALESCE(search_text, content)) LIKE ?{n} OR LOWER(COALESCE(title, '')) LIKE ?{n})",
+                    n = i + 3
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(joiner);
+        let mut sql = format!(
+            "SELECT {MEMORY_SELECT_COLUMNS}
              FROM memories
              WHERE (chat_id = ?1 OR chat_id IS NULL)
-               AND LOWER(content) LIKE ?2",
+               AND ({term_clause})"
         );
         if !include_archived {
             sql.push_str(" AND is_archived = 0");
@@ -2260,37 +2854,72 @@ impl Database {
         if !broad_recall {
             sql.push_str(" AND confidence >= 0.45");
         }
-        sql.push_str(" ORDER BY confidence DESC, updated_at DESC LIMIT ?3");
+        // Timestamps may carry any offset; julianday compares instants.
+        if since.is_some() {
+            sql.push_str(&format!(
+                " AND julianday(created_at) >= julianday(?{})",
+                patterns.len() + 3
+            ));
+        }
+        // In `Any` mode a row can match some terms and not others; rank by
+        // how many terms hit, a title hit counting double, before falling
+        // back to confidence and recency.
+        let match_count = (0..patterns.len())
+            .map(|i| {
+                format!(
+                    "(LOWER(COALESCE(search_text, content)) LIKE ?{n}) + 2 * (LOWER(COALESCE(title, '')) LIKE ?{n})",
+                    n = i + 3
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" + ");
+        sql.push_str(&format!(
+            " ORDER BY ({match_count}) DESC, confidence DESC, updated_at DESC LIMIT ?2"
+        ));
+        let mut values: Vec<rusqlite::types::Value> = vec![chat_id.into(), (limit as i64).into()];
+        values.extend(patterns.into_iter().map(Into::into));
+        if let Some(since) = since {
+            values.push(since.to_rfc3339().into());
+        }
         let mut stmt = conn.prepare(&sql)?;
         let memories = stmt
-            .query_map(params![chat_id, pattern, limit as i64], |row| {
-                Ok(Memory {
-                    id: row.get(0)?,
-                    chat_id: row.get(1)?,
-                    content: row.get(2)?,
-                    category: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    embedding_model: row.get(6)?,
-                    confidence: row.get(7)?,
-                    source: row.get(8)?,
-                    last_seen_at: row.get(9)?,
-                    is_archived: row.get::<_, i64>(10)? != 0,
-                    archived_at: row.get(11)?,
-                })
-            })?
+            .query_map(rusqlite::params_from_iter(values), memory_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
         Ok(memories)
     }
 
     /// Delete a memory row by id. Returns true if a row was deleted.
     pub fn delete_memory(&self, id: i64) -> Result<bool, MicroClawError> {
-        let conn = self.lock_conn();
-        let rows = conn.execute("DELETE FROM memories WHERE id = ?1", params![id])?;
-        Ok(rows > 0)
+        Ok(self.delete_memories(&[id])? > 0)
+    }
+
+    /// Delete several memory rows, and their vectors, in one transaction.
+    /// Unknown ids are ignored; returns how many rows were deleted.
+    pub fn delete_memories(&self, ids: &[i64]) -> Result<usize, MicroClawError> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = self.lock_conn();
+        let tx = conn.transaction()?;
+        #[cfg(feature = "sqlite-vec")]
+        if table_exists(&tx, "memories_vec")? {
+            for id in ids {
+                tx.execute("DELETE FROM memories_vec WHERE rowid = ?1", params![id])?;
+            }
+        }
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let deleted = tx.execute(
+            &format!("DELETE FROM memories WHERE id IN ({placeholders})"),
+            rusqlite::params_from_iter(ids),
+        )?;
+        tx.commit()?;
+        Ok(deleted)
     }
 
-    /// Update content and category of an existing memory. Returns true if found.
+    /// Update the content and category of an existing memory in place; never
+    /// inserts. Returns false when `id` does not exist. `created_at` is left
+    /// untouched and the embedding is cleared so the next backfill re-embeds
+    /// the new content.
     pub fn update_memory_content(
         &self,
         id: i64,
@@ -2305,6 +2934,7 @@ impl Database {
                  category = ?2,
                  updated_at = ?3,
                  embedding_model = NULL,
+                 search_text = NULL,
                  last_seen_at = ?3,
                  is_archived = 0,
                  archived_at = NULL
@@ -2331,6 +2961,7 @@ impl Database {
                  category = ?2,
                  updated_at = ?3,
                  embedding_model = NULL,
+                 search_text = NULL,
                  confidence = ?4,
                  source = ?5,
                  last_seen_at = ?3,
@@ -2362,42 +2993,100 @@ impl Database {
         Ok(rows > 0)
     }
 
-    pub fn get_memories_without_embedding(
+    /// Store the embedding of `content` for a memory, but only if the memory
+    /// is still retrieved by exactly that text (see
+    /// [`Memory::retrieval_text`]). Embedding runs outside the DB lock, so an
+    /// older write can finish after a newer update; checking under the lock
+    /// keeps the latest text's vector instead of whichever embed call
+    /// returned last. Returns false when the write was skipped.
+    pub fn store_memory_embedding_if_current(
         &self,
-        chat_id: Option<i64>,
-        limit: usize,
-    ) -> Result<Vec<Memory>, MicroClawError> {
+        memory_id: i64,
+        content: &str,
+        embedding: &[f32],
+        model: &str,
+    ) -> Result<bool, MicroClawError> {
         let conn = self.lock_conn();
-        let mut query = String::from(
-            "SELECT id, chat_id, content, category, created_at, updated_at, embedding_model
-             , confidence, source, last_seen_at, is_archived, archived_at
-             FROM memories
-             WHERE embedding_model IS NULL
-               AND is_archived = 0",
-        );
-        if chat_id.is_some() {
-            query.push_str(" AND chat_id = ?1");
+        let tx = conn.unchecked_transaction()?;
+        let current: Option<(Option<String>, String)> = tx
+            .query_row(
+                "SELECT title, COALESCE(search_text, content) FROM memories WHERE id = ?1",
+                params![memory_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let current = current
+            .as_ref()
+            .map(|(title, body)| compose_retrieval_text(title.as_deref(), body));
+        if current.as_deref() != Some(content) {
+            return Ok(false);
         }
-        query.push_str(" ORDER BY updated_at DESC LIMIT ");
-        query.push_str(&limit.to_string());
-
+        if !crate::vector::is_usable_embedding(embedding) {
+            tracing::warn!(
+                "Not storing an empty or zero embedding for memory {memory_id}; left unembedded for backfill to retry"
+            );
+            #[cfg(feature = "sqlite-vec")]
+            tx.execute(
+                "DELETE FROM memories_vec WHERE rowid = ?1",
+                params![memory_id],
+            )?;
+            tx.execute(
+                "UPDATE memories SET embedding_model = NULL WHERE id = ?1",
+                params![memory_id],
+            )?;
+            tx.commit()?;
+            return Ok(false);
+        }
+        #[cfg(feature = "sqlite-vec")]
+        {
+            let vector_json = serde_json::to_string(embedding)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO memories_vec(rowid, embedding) VALUES(?1, vec_f32(?2))",
+                params![memory_id, vector_json],
+            )?;
+        }
+        #[cfg(not(feature = "sqlite-vec"))]
+        let _ = embedding;
+        tx.execute(
+            "UPDATE memories SET embedding_model = ?1, embedding_attempts = 0 WHERE id = ?2",
+            params![model, memory_id],
+        )?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Count a failed embedding attempt so backfill tries other rows first.
+    pub fn record_embedding_failure(&self, memory_id: i64) -> Result<(), MicroClawError> {
+        let conn = self.lock_conn();
+        conn.execute(
+            "UPDATE memories SET embedding_attempts = embedding_attempts + 1 WHERE id = ?1",
+            params![memory_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_memories_without_embedding(
+        &self,
+        chat_id: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<Memory>, MicroClawError> {
+        let conn = self.lock_conn();
+        let mut query = format!(
+            "SELECT {MEMORY_SELECT_COLUMNS}
+             FROM memories
+             WHERE embedding_model IS NULL
+               AND is_archived = 0"
+        );
+        if chat_id.is_some() {
+            query.push_str(" AND chat_id = ?1");
+        }
+        // Rows that keep failing sink behind fresh ones instead of filling
+        // every batch.
+        query.push_str(" ORDER BY embedding_attempts ASC, updated_at DESC LIMIT ");
+        query.push_str(&limit.to_string());
+
         let mut stmt = conn.prepare(&query)?;
-        let mapper = |row: &rusqlite::Row<'_>| {
-            Ok(Memory {
-                id: row.get(0)?,
-                chat_id: row.get(1)?,
-                content: row.get(2)?,
-                category: row.get(3)?,
-                created_at: row.get(4)?,
-                updated_at: row.get(5)?,
-                embedding_model: row.get(6)?,
-                confidence: row.get(7)?,
-                source: row.get(8)?,
-                last_seen_at: row.get(9)?,
-                is_archived: row.get::<_, i64>(10)? != 0,
-                archived_at: row.get(11)?,
-            })
-        };
+        let mapper = memory_from_row;
 
         let rows = if let Some(cid) = chat_id {
             stmt.query_map(params![cid], mapper)?
@@ -2408,7 +3097,26 @@ impl Database {
     }
 
     #[cfg(feature = "sqlite-vec")]
-    pub fn prepare_vector_index(&self, dimension: usize) -> Result<(), MicroClawError> {
+    /// Create the `memories_vec` index for `dimension`. Vectors of another
+    /// size can't be compared, so on a dimension change the old index is
+    /// dropped, every memory is marked for re-embedding, and the change is
+    /// returned so callers can surface it.
+    pub fn prepare_vector_index(
+        &self,
+        dimension: usize,
+    ) -> Result<Option<VectorDimensionChange>, MicroClawError> {
+        self.prepare_vector_index_with_metric(dimension, VectorMetric::Cosine)
+    }
+
+    /// Like [`Database::prepare_vector_index`], but builds the index with
+    /// `metric`. Switching metrics rebuilds the index the same way a
+    /// dimension change does.
+    #[cfg(feature = "sqlite-vec")]
+    pub fn prepare_vector_index_with_metric(
+        &self,
+        dimension: usize,
+        metric: VectorMetric,
+    ) -> Result<Option<VectorDimensionChange>, MicroClawError> {
         let conn = self.lock_conn();
         let dimension = dimension.max(1);
         conn.execute(
@@ -2423,18 +3131,32 @@ impl Database {
                 |row| row.get(0),
             )
             .optional()?;
+        // Indexes built before the metric was configurable are cosine.
+        let current_metric = vector_metric_locked(&conn)?;
+        let mut change = None;
         if let Some(existing) = current_dim {
-            if existing != dimension.to_string() {
+            if existing != dimension.to_string() || current_metric != metric {
                 conn.execute("DROP TABLE IF EXISTS memories_vec", [])?;
-                conn.execute("UPDATE memories SET embedding_model = NULL", [])?;
+                let cleared_embeddings = conn.execute(
+                    "UPDATE memories SET embedding_model = NULL WHERE embedding_model IS NOT NULL",
+                    [],
+                )?;
+                change = Some(VectorDimensionChange {
+                    previous: existing.parse().unwrap_or(0),
+                    current: dimension,
+                    previous_metric: current_metric,
+                    current_metric: metric,
+                    cleared_embeddings,
+                });
             }
         }
 
         conn.execute(
             &format!(
                 "CREATE VIRTUAL TABLE IF NOT EXISTS memories_vec USING vec0(
-                    embedding float[{dimension}] distance_metric=cosine
-                )"
+                    embedding float[{dimension}] distance_metric={}
+                )",
+                metric.as_str()
             ),
             [],
         )?;
@@ -2443,44 +3165,193 @@ impl Database {
              ON CONFLICT(key) DO UPDATE SET value = excluded.value",
             params![dimension.to_string()],
         )?;
-        Ok(())
+        conn.execute(
+            "INSERT INTO db_meta(key, value) VALUES('embedding_metric', ?1)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![metric.as_str()],
+        )?;
+        Ok(change)
     }
 
+    /// Write a memory's vector. An empty or zero vector is not stored: any
+    /// previous vector is removed and false is returned.
     #[cfg(feature = "sqlite-vec")]
     pub fn upsert_memory_vec(
         &self,
         memory_id: i64,
         embedding: &[f32],
-    ) -> Result<(), MicroClawError> {
+    ) -> Result<bool, MicroClawError> {
         let conn = self.lock_conn();
+        if !crate::vector::is_usable_embedding(embedding) {
+            tracing::warn!("Not storing an empty or zero embedding for memory {memory_id}");
+            conn.execute(
+                "DELETE FROM memories_vec WHERE rowid = ?1",
+                params![memory_id],
+            )?;
+            return Ok(false);
+        }
         let vector_json = serde_json::to_string(embedding)?;
         conn.execute(
             "INSERT OR REPLACE INTO memories_vec(rowid, embedding) VALUES(?1, vec_f32(?2))",
             params![memory_id, vector_json],
         )?;
-        Ok(())
+        Ok(true)
+    }
+
+    /// Read back the stored vector for a memory, or `None` if it has not been
+    /// embedded. Kept separate from the `Memory` readers so normal recall does
+    /// not pay for decoding vectors it never looks at.
+    #[cfg(feature = "sqlite-vec")]
+    pub fn get_memory_embedding(&self, memory_id: i64) -> Result<Option<Vec<f32>>, MicroClawError> {
+        let conn = self.lock_conn();
+        let vector_json: Option<String> = conn
+            .query_row(
+                "SELECT vec_to_json(embedding) FROM memories_vec WHERE rowid = ?1",
+                params![memory_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match vector_json {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// [`Database::get_memory_embedding`] for several memories in one query,
+    /// in the order of `memory_ids`.
+    #[cfg(feature = "sqlite-vec")]
+    pub fn get_memory_embeddings(
+        &self,
+        memory_ids: &[i64],
+    ) -> Result<Vec<Option<Vec<f32>>>, MicroClawError> {
+        if memory_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.lock_conn();
+        let placeholders = vec!["?"; memory_ids.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT rowid, vec_to_json(embedding) FROM memories_vec WHERE rowid IN ({placeholders})"
+        ))?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(memory_ids), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut by_id = std::collections::HashMap::new();
+        for row in rows {
+            let (id, json) = row?;
+            by_id.insert(id, serde_json::from_str::<Vec<f32>>(&json)?);
+        }
+        Ok(memory_ids.iter().map(|id| by_id.get(id).cloned()).collect())
+    }
+
+    /// Count rows where `memories_vec` disagrees with `memories`: memories
+    /// marked as embedded with no vector row, and vector rows whose memory is
+    /// gone. Cheap enough to run before deciding on a rebuild.
+    #[cfg(feature = "sqlite-vec")]
+    pub fn vector_index_desync(&self) -> Result<VectorIndexDesync, MicroClawError> {
+        let conn = self.lock_conn();
+        vector_index_desync_locked(&conn)
     }
 
+    /// Repair a desynced vector index. Orphan vector rows are deleted and
+    /// memories missing a vector get `embedding_model` cleared so the next
+    /// embedding backfill re-embeds them. Returns the desync that was found,
+    /// or an error if the tables still disagree afterwards.
+    #[cfg(feature = "sqlite-vec")]
+    pub fn rebuild_vector_index(&self) -> Result<VectorIndexDesync, MicroClawError> {
+        let mut conn = self.lock_conn();
+        let before = vector_index_desync_locked(&conn)?;
+        if before.total() == 0 {
+            return Ok(before);
+        }
+
+        let tx = conn.transaction()?;
+        let orphan_ids: Vec<i64> = {
+            let mut stmt = tx.prepare(
+                "SELECT v.rowid FROM memories_vec v
+                 WHERE NOT EXISTS (SELECT 1 FROM memories m WHERE m.id = v.rowid)",
+            )?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        for id in orphan_ids {
+            tx.execute("DELETE FROM memories_vec WHERE rowid = ?1", params![id])?;
+        }
+        tx.execute(
+            "UPDATE memories SET embedding_model = NULL
+             WHERE embedding_model IS NOT NULL
+               AND NOT EXISTS (SELECT 1 FROM memories_vec v WHERE v.rowid = memories.id)",
+            [],
+        )?;
+        tx.commit()?;
+
+        let after = vector_index_desync_locked(&conn)?;
+        if after.total() != 0 {
+            return Err(MicroClawError::ToolExecution(format!(
+                "vector index still out of sync after rebuild: {} missing, {} orphan",
+                after.missing_vectors, after.orphan_vectors
+            )));
+        }
+        Ok(before)
+    }
+
+    /// `(id, retrieval text)` of every active memory, i.e. the text that gets
+    /// embedded (see [`Memory::retrieval_text`]).
     pub fn get_all_active_memories(&self) -> Result<Vec<(i64, String)>, MicroClawError> {
         let conn = self.lock_conn();
-        let mut stmt =
-            conn.prepare("SELECT id, content FROM memories WHERE is_archived = 0 ORDER BY id")?;
+        let mut stmt = conn.prepare(
+            "SELECT id, title, COALESCE(search_text, content) FROM memories
+             WHERE is_archived = 0 ORDER BY id",
+        )?;
         let rows = stmt.query_map([], |row| {
-            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            let title: Option<String> = row.get(1)?;
+            let body: String = row.get(2)?;
+            Ok((
+                row.get::<_, i64>(0)?,
+                compose_retrieval_text(title.as_deref(), &body).into_owned(),
+            ))
         })?;
         rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
     }
 
+    /// Nearest memories to `query_vec` visible from `chat_id`, closest first,
+    /// as `(id, similarity)` pairs scored by the index's [`VectorMetric`].
     #[cfg(feature = "sqlite-vec")]
     pub fn knn_memories(
         &self,
         chat_id: i64,
         query_vec: &[f32],
         k: usize,
+    ) -> Result<Vec<(i64, f32)>, MicroClawError> {
+        self.knn_memories_inner(chat_id, query_vec, k, None)
+    }
+
+    /// Like [`Database::knn_memories`], but drops neighbours created before
+    /// `since`. The index can only return the nearest rows overall, so the
+    /// search widens (up to `MAX_KNN_CANDIDATES`) until `k` recent ones are
+    /// found or the index runs out.
+    #[cfg(feature = "sqlite-vec")]
+    pub fn knn_memories_since(
+        &self,
+        chat_id: i64,
+        query_vec: &[f32],
+        k: usize,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<(i64, f32)>, MicroClawError> {
+        self.knn_memories_inner(chat_id, query_vec, k, Some(since))
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    fn knn_memories_inner(
+        &self,
+        chat_id: i64,
+        query_vec: &[f32],
+        k: usize,
+        since: Option<chrono::DateTime<chrono::Utc>>,
     ) -> Result<Vec<(i64, f32)>, MicroClawError> {
         let conn = self.lock_conn();
+        let metric = vector_metric_locked(&conn)?;
         let vector_json = serde_json::to_string(query_vec)?;
-        let mut stmt = conn.prepare(
+        let mut stmt = conn.prepare_cached(
             "SELECT m.id, v.distance
              FROM (
                 SELECT rowid, distance
@@ -2489,38 +3360,49 @@ impl Database {
              ) v
              JOIN memories m ON m.id = v.rowid
              WHERE (m.chat_id = ?3 OR m.chat_id IS NULL)
+               AND (?4 IS NULL OR julianday(m.created_at) >= julianday(?4))
              ORDER BY v.distance ASC",
         )?;
-        let rows = stmt.query_map(params![vector_json, k as i64, chat_id], |row| {
-            Ok((row.get::<_, i64>(0)?, row.get::<_, f32>(1)?))
-        })?;
-        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        let want = k.min(MAX_KNN_CANDIDATES);
+        if want == 0 {
+            return Ok(Vec::new());
+        }
+        let indexed = match since {
+            Some(_) => conn.query_row("SELECT COUNT(*) FROM memories_vec", [], |row| {
+                row.get::<_, i64>(0)
+            })? as usize,
+            None => 0,
+        };
+        let since = since.map(|t| t.to_rfc3339());
+        let mut fetch = want;
+        loop {
+            let rows =
+                stmt.query_map(params![vector_json, fetch as i64, chat_id, since], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        metric.similarity(row.get::<_, f32>(1)?),
+                    ))
+                })?;
+            let mut hits = rows.collect::<Result<Vec<_>, _>>()?;
+            if since.is_none()
+                || hits.len() >= want
+                || fetch >= indexed
+                || fetch >= MAX_KNN_CANDIDATES
+            {
+                hits.truncate(want);
+                return Ok(hits);
+            }
+            fetch = (fetch * 4).min(MAX_KNN_CANDIDATES);
+        }
     }
 
     /// Get a single memory by id.
     pub fn get_memory_by_id(&self, id: i64) -> Result<Option<Memory>, MicroClawError> {
         let conn = self.lock_conn();
         let result = conn.query_row(
-            "SELECT id, chat_id, content, category, created_at, updated_at, embedding_model,
-                    confidence, source, last_seen_at, is_archived, archived_at
-             FROM memories WHERE id = ?1",
+            &format!("SELECT {MEMORY_SELECT_COLUMNS} FROM memories WHERE id = ?1"),
             params![id],
-            |row| {
-                Ok(Memory {
-                    id: row.get(0)?,
-                    chat_id: row.get(1)?,
-                    content: row.get(2)?,
-                    category: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    embedding_model: row.get(6)?,
-                    confidence: row.get(7)?,
-                    source: row.get(8)?,
-                    last_seen_at: row.get(9)?,
-                    is_archived: row.get::<_, i64>(10)? != 0,
-                    archived_at: row.get(11)?,
-                })
-            },
+            memory_from_row,
         );
         match result {
             Ok(m) => Ok(Some(m)),
@@ -2529,6 +3411,25 @@ impl Database {
         }
     }
 
+    /// Fetch several active memories in one query, returned in the order of
+    /// `ids`. Unknown or archived ids are skipped.
+    pub fn get_memories_by_ids(&self, ids: &[i64]) -> Result<Vec<Memory>, MicroClawError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.lock_conn();
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {MEMORY_SELECT_COLUMNS}
+             FROM memories WHERE id IN ({placeholders}) AND is_archived = 0"
+        ))?;
+        let mut by_id: std::collections::HashMap<i64, Memory> = stmt
+            .query_map(rusqlite::params_from_iter(ids), memory_from_row)?
+            .map(|r| r.map(|m| (m.id, m)))
+            .collect::<Result<_, _>>()?;
+        Ok(ids.iter().filter_map(|id| by_id.remove(id)).collect())
+    }
+
     pub fn touch_memory_last_seen(
         &self,
         id: i64,
@@ -2565,6 +3466,65 @@ impl Database {
         Ok(rows > 0)
     }
 
+    pub fn pin_memory(&self, id: i64) -> Result<bool, MicroClawError> {
+        self.set_memory_pinned(id, true)
+    }
+
+    pub fn unpin_memory(&self, id: i64) -> Result<bool, MicroClawError> {
+        self.set_memory_pinned(id, false)
+    }
+
+    /// Attach (or with `None`, detach) a file reference to a memory. The path
+    /// is stored as given; nothing is read from disk.
+    pub fn set_memory_attachment(
+        &self,
+        id: i64,
+        attachment_path: Option<&str>,
+    ) -> Result<bool, MicroClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE memories SET attachment_path = ?1 WHERE id = ?2",
+            params![attachment_path, id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Set (or with `None`, clear) the normalized text a memory is searched
+    /// and embedded by. The embedding is marked stale so backfill redoes it.
+    pub fn set_memory_search_text(
+        &self,
+        id: i64,
+        search_text: Option<&str>,
+    ) -> Result<bool, MicroClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE memories SET search_text = ?1, embedding_model = NULL WHERE id = ?2",
+            params![search_text, id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Set (or with `None` or a blank string, clear) a memory's title. The
+    /// embedding is marked stale so backfill redoes it with the title.
+    pub fn set_memory_title(&self, id: i64, title: Option<&str>) -> Result<bool, MicroClawError> {
+        let title = title.map(str::trim).filter(|t| !t.is_empty());
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE memories SET title = ?1, embedding_model = NULL WHERE id = ?2",
+            params![title, id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn set_memory_pinned(&self, id: i64, pinned: bool) -> Result<bool, MicroClawError> {
+        let conn = self.lock_conn();
+        let rows = conn.execute(
+            "UPDATE memories SET pinned = ?1 WHERE id = ?2",
+            params![pinned as i64, id],
+        )?;
+        Ok(rows > 0)
+    }
+
     pub fn archive_stale_memories(&self, stale_days: i64) -> Result<usize, MicroClawError> {
         let conn = self.lock_conn();
         let cutoff = (chrono::Utc::now() - chrono::Duration::days(stale_days.max(1))).to_rfc3339();
@@ -2573,6 +3533,7 @@ impl Database {
             "UPDATE memories
              SET is_archived = 1, archived_at = ?1, updated_at = ?1
              WHERE is_archived = 0
+               AND pinned = 0
                AND confidence < 0.35
                AND COALESCE(last_seen_at, updated_at, created_at) < ?2",
             params![now, cutoff],
@@ -2580,6 +3541,123 @@ impl Database {
         Ok(rows)
     }
 
+    /// Copy the whole `memories` table into a shadow table under `label`,
+    /// replacing any earlier snapshot with the same label.
+    pub fn snapshot_memories(&self, label: &str) -> Result<MemorySnapshot, MicroClawError> {
+        let conn = self.lock_conn();
+        let tx = conn.unchecked_transaction()?;
+        if let Some(old_id) = tx
+            .query_row(
+                "SELECT id FROM memory_snapshots WHERE label = ?1",
+                params![label],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?
+        {
+            tx.execute_batch(&format!("DROP TABLE IF EXISTS memory_snapshot_{old_id}"))?;
+            tx.execute(
+                "DELETE FROM memory_snapshots WHERE id = ?1",
+                params![old_id],
+            )?;
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        tx.execute(
+            "INSERT INTO memory_snapshots(label, created_at) VALUES(?1, ?2)",
+            params![label, now],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.execute_batch(&format!(
+            "CREATE TABLE memory_snapshot_{id} AS SELECT * FROM memories"
+        ))?;
+        let row_count: i64 = tx.query_row(
+            &format!("SELECT COUNT(*) FROM memory_snapshot_{id}"),
+            [],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "UPDATE memory_snapshots SET row_count = ?1 WHERE id = ?2",
+            params![row_count, id],
+        )?;
+        tx.commit()?;
+        Ok(MemorySnapshot {
+            label: label.to_string(),
+            created_at: now,
+            row_count,
+        })
+    }
+
+    /// Replace the contents of `memories` with the snapshot taken under
+    /// `label`. Returns false if no such snapshot exists. Restored rows lose
+    /// their embeddings and are re-embedded by the next backfill.
+    pub fn restore_memory_snapshot(&self, label: &str) -> Result<bool, MicroClawError> {
+        let conn = self.lock_conn();
+        let Some(id) = conn
+            .query_row(
+                "SELECT id FROM memory_snapshots WHERE label = ?1",
+                params![label],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?
+        else {
+            return Ok(false);
+        };
+
+        let columns: Vec<String> = {
+            let mut stmt = conn.prepare(&format!("PRAGMA table_info(memory_snapshot_{id})"))?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        let column_list = columns.join(", ");
+
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM memories", [])?;
+        tx.execute_batch(&format!(
+            "INSERT INTO memories ({column_list})
+             SELECT {column_list} FROM memory_snapshot_{id}"
+        ))?;
+        tx.execute("UPDATE memories SET embedding_model = NULL", [])?;
+        #[cfg(feature = "sqlite-vec")]
+        if table_exists(&tx, "memories_vec")? {
+            tx.execute("DELETE FROM memories_vec", [])?;
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Snapshots, newest first.
+    pub fn list_memory_snapshots(&self) -> Result<Vec<MemorySnapshot>, MicroClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT label, created_at, row_count FROM memory_snapshots ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(MemorySnapshot {
+                label: row.get(0)?,
+                created_at: row.get(1)?,
+                row_count: row.get(2)?,
+            })
+        })?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+    }
+
+    /// Drop all but the `keep` newest snapshots. Returns how many were removed.
+    pub fn prune_memory_snapshots(&self, keep: usize) -> Result<usize, MicroClawError> {
+        let conn = self.lock_conn();
+        let stale: Vec<i64> = {
+            let mut stmt = conn
+                .prepare("SELECT id FROM memory_snapshots ORDER BY id DESC LIMIT -1 OFFSET ?1")?;
+            let rows = stmt.query_map(params![keep as i64], |row| row.get(0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        let tx = conn.unchecked_transaction()?;
+        for id in &stale {
+            tx.execute_batch(&format!("DROP TABLE IF EXISTS memory_snapshot_{id}"))?;
+            tx.execute("DELETE FROM memory_snapshots WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(stale.len())
+    }
+
     pub fn supersede_memory(
         &self,
         from_memory_id: i64,
@@ -2698,6 +3776,25 @@ impl Database {
         Ok(conn.last_insert_rowid())
     }
 
+    /// Active memory counts per category in one query. With `chat_id`, counts
+    /// cover that chat plus global memories, matching the observability summary.
+    pub fn count_memories_by_category(
+        &self,
+        chat_id: Option<i64>,
+    ) -> Result<Vec<(String, usize)>, MicroClawError> {
+        let conn = self.lock_conn();
+        let mut stmt = conn.prepare(
+            "SELECT category, COUNT(*) FROM memories
+             WHERE is_archived = 0 AND (?1 IS NULL OR chat_id = ?1 OR chat_id IS NULL)
+             GROUP BY category
+             ORDER BY category",
+        )?;
+        let rows = stmt.query_map(params![chat_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
     pub fn get_memory_observability_summary(
         &self,
         chat_id: Option<i64>,
@@ -2983,6 +4080,30 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn test_in_memory_database_leaves_no_files() {
+        let db = Database::new(IN_MEMORY_DATA_DIR).unwrap();
+        db.insert_memory(Some(1), "user likes tea", "PROFILE")
+            .unwrap();
+        assert_eq!(db.search_memories(1, "tea", 10).unwrap().len(), 1);
+        assert!(!Path::new(IN_MEMORY_DATA_DIR).exists());
+    }
+
+    #[test]
+    fn test_open_file_creates_parent_and_reopens() {
+        let dir = std::env::temp_dir().join(format!("microclaw_db_path_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("fast").join("custom.db");
+        {
+            let db = Database::open_file(&path).unwrap();
+            db.insert_memory(Some(1), "user likes tea", "PROFILE")
+                .unwrap();
+        }
+        assert!(path.exists());
+        let db = Database::open_file(&path).unwrap();
+        assert_eq!(db.search_memories(1, "tea", 10).unwrap().len(), 1);
+        cleanup(&dir);
+    }
+
     #[test]
     fn test_schema_version_is_tracked() {
         let (db, dir) = test_db();
@@ -4074,24 +5195,71 @@ mod tests {
         let results = db.search_memories(100, "nonexistent_xyz", 10).unwrap();
         assert!(results.is_empty());
 
+        for (mode, expected) in [
+            (MemoryMatchMode::Phrase, 0),
+            (MemoryMatchMode::All, 1),
+            (MemoryMatchMode::Any, 3),
+        ] {
+            let results = db
+                .search_memories_with_options(100, "rust user", mode, 10, false, true, None)
+                .unwrap();
+            assert_eq!(results.len(), expected, "{mode:?}");
+        }
+
         cleanup(&dir);
     }
 
     #[test]
-    fn test_archive_memory_hides_from_search_and_context() {
+    fn test_search_memories_any_ranks_by_matched_terms() {
         let (db, dir) = test_db();
-        let id = db
-            .insert_memory(Some(100), "User prefers concise summaries", "PROFILE")
+        let full = db
+            .insert_memory(Some(100), "Deploy staging with docker compose", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(Some(100), "Docker is installed on the laptop", "KNOWLEDGE")
+            .unwrap();
+        let newest = db
+            .insert_memory(Some(100), "Staging moved to a new host", "KNOWLEDGE")
+            .unwrap();
+        db.lock_conn()
+            .execute(
+                "UPDATE memories SET updated_at = '2000-01-01T00:00:00Z' WHERE id = ?1",
+                params![full],
+            )
             .unwrap();
-        assert!(db.archive_memory(id).unwrap());
-
-        let mem = db.get_memory_by_id(id).unwrap().unwrap();
-        assert!(mem.is_archived);
-        assert!(mem.archived_at.is_some());
 
-        let search = db.search_memories(100, "concise", 10).unwrap();
-        assert!(search.is_empty());
-        let context = db.get_memories_for_context(100, 10).unwrap();
+        let results = db
+            .search_memories_with_options(
+                100,
+                "staging docker compose",
+                MemoryMatchMode::Any,
+                10,
+                false,
+                true,
+                None,
+            )
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].id, full);
+        assert_ne!(results[0].id, newest);
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_archive_memory_hides_from_search_and_context() {
+        let (db, dir) = test_db();
+        let id = db
+            .insert_memory(Some(100), "User prefers concise summaries", "PROFILE")
+            .unwrap();
+        assert!(db.archive_memory(id).unwrap());
+
+        let mem = db.get_memory_by_id(id).unwrap().unwrap();
+        assert!(mem.is_archived);
+        assert!(mem.archived_at.is_some());
+
+        let search = db.search_memories(100, "concise", 10).unwrap();
+        assert!(search.is_empty());
+        let context = db.get_memories_for_context(100, 10).unwrap();
         assert!(context.is_empty());
 
         cleanup(&dir);
@@ -4136,6 +5304,57 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn test_record_memory_access_and_most_accessed() {
+        let (db, dir) = test_db();
+        let a = db
+            .insert_memory(Some(100), "memory a", "KNOWLEDGE")
+            .unwrap();
+        let b = db
+            .insert_memory(Some(100), "memory b", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(Some(100), "never recalled", "KNOWLEDGE")
+            .unwrap();
+
+        assert_eq!(db.record_memory_access(&[a, b]).unwrap(), 2);
+        assert_eq!(db.record_memory_access(&[b]).unwrap(), 1);
+        assert_eq!(db.record_memory_access(&[]).unwrap(), 0);
+
+        let top = db.get_most_accessed_memories(10).unwrap();
+        let ranked: Vec<(i64, i64)> = top.iter().map(|m| (m.id, m.access_count)).collect();
+        assert_eq!(ranked, vec![(b, 2), (a, 1)]);
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_count_memories_by_category() {
+        let (db, dir) = test_db();
+        db.insert_memory(Some(100), "likes tea", "PREFERENCE")
+            .unwrap();
+        db.insert_memory(Some(100), "likes coffee", "PREFERENCE")
+            .unwrap();
+        db.insert_memory(None, "team uses rust", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(Some(200), "other chat fact", "KNOWLEDGE")
+            .unwrap();
+        let archived = db.insert_memory(Some(100), "old event", "EVENT").unwrap();
+        db.archive_memory(archived).unwrap();
+
+        let counts = db.count_memories_by_category(Some(100)).unwrap();
+        assert_eq!(
+            counts,
+            vec![("KNOWLEDGE".to_string(), 1), ("PREFERENCE".to_string(), 2)]
+        );
+        let all = db.count_memories_by_category(None).unwrap();
+        assert_eq!(
+            all,
+            vec![("KNOWLEDGE".to_string(), 2), ("PREFERENCE".to_string(), 2)]
+        );
+
+        cleanup(&dir);
+    }
+
     #[test]
     fn test_supersede_memory_creates_edge_and_archives_old() {
         let (db, dir) = test_db();
@@ -4177,6 +5396,28 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn test_check_writable_leaves_no_row() {
+        let (db, dir) = test_db();
+        db.check_writable().unwrap();
+        let leftover: i64 = db
+            .lock_conn()
+            .query_row(
+                "SELECT COUNT(*) FROM db_meta WHERE key = 'health_check'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(leftover, 0);
+
+        db.lock_conn()
+            .execute_batch("PRAGMA query_only = ON")
+            .unwrap();
+        assert!(db.check_writable().is_err());
+
+        cleanup(&dir);
+    }
+
     #[test]
     fn test_delete_memory() {
         let (db, dir) = test_db();
@@ -4191,6 +5432,65 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn test_delete_memories_bulk() {
+        let (db, dir) = test_db();
+        let a = db.insert_memory(Some(100), "first", "EVENT").unwrap();
+        let b = db.insert_memory(Some(100), "second", "EVENT").unwrap();
+        let keep = db.insert_memory(Some(100), "third", "EVENT").unwrap();
+
+        assert_eq!(db.delete_memories(&[]).unwrap(), 0);
+        assert_eq!(db.delete_memories(&[a, b, 9999]).unwrap(), 2);
+        assert!(db.get_memory_by_id(a).unwrap().is_none());
+        assert!(db.get_memory_by_id(b).unwrap().is_none());
+        assert!(db.get_memory_by_id(keep).unwrap().is_some());
+
+        cleanup(&dir);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_delete_memories_removes_vectors() {
+        let (db, dir) = test_db();
+        db.prepare_vector_index(3).unwrap();
+        let a = db.insert_memory(Some(100), "first", "EVENT").unwrap();
+        let b = db.insert_memory(Some(100), "second", "EVENT").unwrap();
+        for id in [a, b] {
+            db.upsert_memory_vec(id, &[1.0, 0.0, 0.0]).unwrap();
+            db.update_memory_embedding_model(id, "m").unwrap();
+        }
+
+        assert!(db.delete_memory(a).unwrap());
+        assert_eq!(db.delete_memories(&[b]).unwrap(), 1);
+        assert_eq!(db.vector_index_desync().unwrap().total(), 0);
+        assert!(db
+            .knn_memories(100, &[1.0, 0.0, 0.0], 5)
+            .unwrap()
+            .is_empty());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_get_memories_by_ids_keeps_requested_order() {
+        let (db, dir) = test_db();
+        let a = db.insert_memory(Some(100), "first", "EVENT").unwrap();
+        let b = db.insert_memory(Some(100), "second", "EVENT").unwrap();
+        let archived = db.insert_memory(Some(100), "third", "EVENT").unwrap();
+        assert!(db.archive_memory(archived).unwrap());
+
+        assert!(db.get_memories_by_ids(&[]).unwrap().is_empty());
+        let ids: Vec<i64> = db
+            .get_memories_by_ids(&[b, 9999, archived, a])
+            .unwrap()
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, vec![b, a]);
+
+        cleanup(&dir);
+    }
+
     #[test]
     fn test_update_memory_content() {
         let (db, dir) = test_db();
@@ -4212,6 +5512,72 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn test_search_text_is_matched_and_cleared_on_update() {
+        let (db, dir) = test_db();
+        let id = db
+            .insert_memory(Some(100), "Dr. O'Brien's Café", "PROFILE")
+            .unwrap();
+        assert!(db
+            .set_memory_search_text(id, Some("dr obrien cafe"))
+            .unwrap());
+
+        let hits = db.search_memories(100, "obrien cafe", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].content, "Dr. O'Brien's Café");
+        assert_eq!(hits[0].retrieval_text(), "dr obrien cafe");
+
+        db.update_memory_content(id, "Dr. Kim's Bakery", "PROFILE")
+            .unwrap();
+        let mem = db.get_memory_by_id(id).unwrap().unwrap();
+        assert_eq!(mem.search_text, None);
+        assert!(db.search_memories(100, "obrien", 10).unwrap().is_empty());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_title_hits_rank_above_body_hits() {
+        let (db, dir) = test_db();
+        let body_hit = db
+            .insert_memory(
+                Some(100),
+                "Ask about the garden before visiting",
+                "KNOWLEDGE",
+            )
+            .unwrap();
+        let titled = db
+            .insert_memory(
+                Some(100),
+                "Tomatoes go in after the last frost",
+                "KNOWLEDGE",
+            )
+            .unwrap();
+        assert!(db.set_memory_title(titled, Some("  Garden plan ")).unwrap());
+
+        let hits = db.search_memories(100, "garden", 10).unwrap();
+        let ids: Vec<i64> = hits.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![titled, body_hit]);
+        assert_eq!(hits[0].title.as_deref(), Some("Garden plan"));
+        assert_eq!(
+            hits[0].display_text(),
+            "Garden plan: Tomatoes go in after the last frost"
+        );
+        assert_eq!(
+            hits[0].retrieval_text(),
+            "Garden plan\nGarden plan\n\nTomatoes go in after the last frost"
+        );
+        let active = db.get_all_active_memories().unwrap();
+        assert!(active.contains(&(titled, hits[0].retrieval_text().into_owned())));
+
+        assert!(db.set_memory_title(titled, Some(" ")).unwrap());
+        let mem = db.get_memory_by_id(titled).unwrap().unwrap();
+        assert_eq!(mem.title, None);
+        assert_eq!(mem.retrieval_text(), mem.content);
+
+        cleanup(&dir);
+    }
+
     #[test]
     fn test_get_memory_by_id() {
         let (db, dir) = test_db();
@@ -4261,6 +5627,59 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn test_store_memory_embedding_skips_stale_content() {
+        let (db, dir) = test_db();
+        #[cfg(feature = "sqlite-vec")]
+        db.prepare_vector_index(3).unwrap();
+        let id = db
+            .insert_memory(Some(100), "old text", "KNOWLEDGE")
+            .unwrap();
+
+        // Content changed while the embedding for "old text" was in flight.
+        db.update_memory_content(id, "new text", "KNOWLEDGE")
+            .unwrap();
+        assert!(!db
+            .store_memory_embedding_if_current(id, "old text", &[1.0, 0.0, 0.0], "m")
+            .unwrap());
+        assert!(db
+            .get_memory_by_id(id)
+            .unwrap()
+            .unwrap()
+            .embedding_model
+            .is_none());
+
+        assert!(db
+            .store_memory_embedding_if_current(id, "new text", &[0.0, 1.0, 0.0], "m")
+            .unwrap());
+        assert_eq!(
+            db.get_memory_by_id(id)
+                .unwrap()
+                .unwrap()
+                .embedding_model
+                .as_deref(),
+            Some("m")
+        );
+        assert!(!db
+            .store_memory_embedding_if_current(9999, "new text", &[0.0, 1.0, 0.0], "m")
+            .unwrap());
+
+        // A degenerate vector clears the stored one instead of replacing it.
+        for bad in [&[][..], &[0.0, 0.0, 0.0][..]] {
+            assert!(!db
+                .store_memory_embedding_if_current(id, "new text", bad, "m")
+                .unwrap());
+            assert!(db
+                .get_memory_by_id(id)
+                .unwrap()
+                .unwrap()
+                .embedding_model
+                .is_none());
+        }
+
+        cleanup(&dir);
+    }
+
     #[test]
     fn test_api_key_expiry_and_rotation_and_audit_logs() {
         let (db, dir) = test_db();
@@ -4331,7 +5750,407 @@ mod tests {
         let nearest = db.knn_memories(100, &[0.95, 0.05, 0.0], 1).unwrap();
         assert_eq!(nearest.len(), 1);
         assert_eq!(nearest[0].0, id1);
-        assert!(nearest[0].1 >= 0.0);
+        assert!(nearest[0].1 > 0.9 && nearest[0].1 <= 1.0);
+
+        assert_eq!(
+            db.get_memory_embedding(id2).unwrap(),
+            Some(vec![0.0, 1.0, 0.0])
+        );
+        assert_eq!(db.get_memory_embedding(9999).unwrap(), None);
+        assert_eq!(
+            db.get_memory_embeddings(&[id2, 9999, id2]).unwrap(),
+            vec![Some(vec![0.0, 1.0, 0.0]), None, Some(vec![0.0, 1.0, 0.0])]
+        );
+        assert!(db.get_memory_embeddings(&[]).unwrap().is_empty());
+
+        cleanup(&dir);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_prepare_vector_index_reports_dimension_change() {
+        let (db, dir) = test_db();
+        assert_eq!(db.prepare_vector_index(3).unwrap(), None);
+        let id = db
+            .insert_memory(Some(100), "vector one", "KNOWLEDGE")
+            .unwrap();
+        db.upsert_memory_vec(id, &[1.0, 0.0, 0.0]).unwrap();
+        db.update_memory_embedding_model(id, "m").unwrap();
+        assert_eq!(db.prepare_vector_index(3).unwrap(), None);
+
+        let change = db.prepare_vector_index(4).unwrap();
+        assert_eq!(
+            change,
+            Some(VectorDimensionChange {
+                previous: 3,
+                current: 4,
+                previous_metric: VectorMetric::Cosine,
+                current_metric: VectorMetric::Cosine,
+                cleared_embeddings: 1,
+            })
+        );
+        let pending = db.get_memories_without_embedding(Some(100), 10).unwrap();
+        assert_eq!(pending.len(), 1);
+        db.upsert_memory_vec(id, &[0.0, 0.0, 0.0, 1.0]).unwrap();
+
+        cleanup(&dir);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_prepare_vector_index_metric_change_rebuilds() {
+        let (db, dir) = test_db();
+        db.prepare_vector_index(3).unwrap();
+        let id = db
+            .insert_memory(Some(100), "vector one", "KNOWLEDGE")
+            .unwrap();
+        db.upsert_memory_vec(id, &[1.0, 0.0, 0.0]).unwrap();
+        db.update_memory_embedding_model(id, "m").unwrap();
+
+        let change = db
+            .prepare_vector_index_with_metric(3, VectorMetric::L2)
+            .unwrap();
+        assert_eq!(
+            change,
+            Some(VectorDimensionChange {
+                previous: 3,
+                current: 3,
+                previous_metric: VectorMetric::Cosine,
+                current_metric: VectorMetric::L2,
+                cleared_embeddings: 1,
+            })
+        );
+        assert_eq!(
+            db.prepare_vector_index_with_metric(3, VectorMetric::L2)
+                .unwrap(),
+            None
+        );
+
+        db.upsert_memory_vec(id, &[3.0, 0.0, 0.0]).unwrap();
+        let nearest = db.knn_memories(100, &[1.0, 0.0, 0.0], 1).unwrap();
+        assert_eq!(nearest[0].0, id);
+        assert!((nearest[0].1 - VectorMetric::L2.similarity(2.0)).abs() < 1e-5);
+
+        cleanup(&dir);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_rebuild_vector_index_repairs_desync() {
+        let (db, dir) = test_db();
+        db.prepare_vector_index(3).unwrap();
+        let kept = db
+            .insert_memory(Some(100), "kept vector", "KNOWLEDGE")
+            .unwrap();
+        let missing = db
+            .insert_memory(Some(100), "missing vector", "KNOWLEDGE")
+            .unwrap();
+        db.upsert_memory_vec(kept, &[1.0, 0.0, 0.0]).unwrap();
+        db.update_memory_embedding_model(kept, "m").unwrap();
+        db.update_memory_embedding_model(missing, "m").unwrap();
+        db.upsert_memory_vec(9999, &[0.0, 1.0, 0.0]).unwrap();
+
+        let desync = db.vector_index_desync().unwrap();
+        assert_eq!(desync.missing_vectors, 1);
+        assert_eq!(desync.orphan_vectors, 1);
+
+        let repaired = db.rebuild_vector_index().unwrap();
+        assert_eq!(repaired, desync);
+        assert_eq!(db.vector_index_desync().unwrap().total(), 0);
+        let pending = db.get_memories_without_embedding(Some(100), 10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, missing);
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_pinned_memory_survives_stale_archive_and_surfaces() {
+        let (db, dir) = test_db();
+        let pinned = db
+            .insert_memory_with_metadata(Some(100), "persona rule", "PROFILE", "explicit", 0.2)
+            .unwrap();
+        let stale = db
+            .insert_memory_with_metadata(Some(100), "stale note", "KNOWLEDGE", "reflector", 0.2)
+            .unwrap();
+        assert!(db.pin_memory(pinned).unwrap());
+        {
+            let conn = db.lock_conn();
+            conn.execute(
+                "UPDATE memories SET last_seen_at = '2000-01-01T00:00:00Z',
+                    updated_at = '2000-01-01T00:00:00Z'",
+                [],
+            )
+            .unwrap();
+        }
+
+        assert_eq!(db.archive_stale_memories(30).unwrap(), 1);
+        assert!(db.get_memory_by_id(stale).unwrap().unwrap().is_archived);
+        let context = db.get_memories_for_context(100, 10).unwrap();
+        assert_eq!(context.len(), 1);
+        assert_eq!(context[0].id, pinned);
+        assert!(context[0].pinned);
+
+        assert!(db.unpin_memory(pinned).unwrap());
+        assert!(!db.get_memory_by_id(pinned).unwrap().unwrap().pinned);
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_update_memory_content_preserves_created_at() {
+        let (db, dir) = test_db();
+        let id = db
+            .insert_memory(Some(100), "old fact", "KNOWLEDGE")
+            .unwrap();
+        db.update_memory_embedding_model(id, "m").unwrap();
+        {
+            let conn = db.lock_conn();
+            conn.execute(
+                "UPDATE memories SET created_at = '2000-01-01T00:00:00Z' WHERE id = ?1",
+                params![id],
+            )
+            .unwrap();
+        }
+
+        assert!(db
+            .update_memory_content(id, "new fact", "KNOWLEDGE")
+            .unwrap());
+        let mem = db.get_memory_by_id(id).unwrap().unwrap();
+        assert_eq!(mem.content, "new fact");
+        assert_eq!(mem.created_at, "2000-01-01T00:00:00Z");
+        assert_ne!(mem.updated_at, mem.created_at);
+        assert!(mem.embedding_model.is_none(), "edit must queue a re-embed");
+
+        assert!(!db
+            .update_memory_content(id + 1000, "x", "KNOWLEDGE")
+            .unwrap());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_memory_snapshot_restore_and_prune() {
+        let (db, dir) = test_db();
+        let kept = db
+            .insert_memory(Some(100), "original fact", "KNOWLEDGE")
+            .unwrap();
+        let snap = db.snapshot_memories("before-rewrite").unwrap();
+        assert_eq!(snap.row_count, 1);
+
+        db.update_memory_content(kept, "rewritten fact", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(Some(100), "added later", "EVENT").unwrap();
+
+        assert!(db.restore_memory_snapshot("before-rewrite").unwrap());
+        let all = db.get_all_memories_for_chat(Some(100)).unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].id, kept);
+        assert_eq!(all[0].content, "original fact");
+        assert!(!db.restore_memory_snapshot("missing").unwrap());
+
+        db.snapshot_memories("second").unwrap();
+        db.snapshot_memories("third").unwrap();
+        let labels: Vec<String> = db
+            .list_memory_snapshots()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.label)
+            .collect();
+        assert_eq!(labels, vec!["third", "second", "before-rewrite"]);
+        assert_eq!(db.prune_memory_snapshots(1).unwrap(), 2);
+        assert_eq!(db.list_memory_snapshots().unwrap().len(), 1);
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_memory_timestamps_parse_to_utc() {
+        let (db, dir) = test_db();
+        let id = db.insert_memory(Some(100), "tz fact", "KNOWLEDGE").unwrap();
+        {
+            let conn = db.lock_conn();
+            conn.execute(
+                "UPDATE memories SET created_at = '2026-03-01T09:00:00+09:00' WHERE id = ?1",
+                params![id],
+            )
+            .unwrap();
+        }
+        let mem = db.get_memory_by_id(id).unwrap().unwrap();
+        assert_eq!(
+            mem.created_at_utc().unwrap().to_rfc3339(),
+            "2026-03-01T00:00:00+00:00"
+        );
+        assert!(mem.updated_at_utc().is_some());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_search_memories_grouped_caps_each_category() {
+        use crate::memory_quality::MemoryCategory;
+        let (db, dir) = test_db();
+        for i in 0..3 {
+            db.insert_memory(Some(100), &format!("deploy task {i}"), "TASK")
+                .unwrap();
+        }
+        db.insert_memory(Some(100), "deploy target is fly.io", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(Some(100), "likes tea", "PROFILE").unwrap();
+
+        let groups = db
+            .search_memories_grouped(100, "deploy", MemoryMatchMode::Phrase, 2)
+            .unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[&MemoryCategory::Task].len(), 2);
+        assert_eq!(groups[&MemoryCategory::Knowledge].len(), 1);
+        assert!(!groups.contains_key(&MemoryCategory::Profile));
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_co_recall_links_rank_related_memories() {
+        let (db, dir) = test_db();
+        let a = db
+            .insert_memory(Some(100), "uses postgres", "KNOWLEDGE")
+            .unwrap();
+        let b = db
+            .insert_memory(Some(100), "db port 5433", "KNOWLEDGE")
+            .unwrap();
+        let c = db.insert_memory(Some(100), "likes tea", "PROFILE").unwrap();
+
+        assert_eq!(db.record_co_recall(&[a, b, c]).unwrap(), 3);
+        db.record_co_recall(&[b, a]).unwrap();
+        assert_eq!(db.record_co_recall(&[a]).unwrap(), 0);
+
+        assert_eq!(db.related_memories(a, 10).unwrap(), vec![(b, 2), (c, 1)]);
+        assert_eq!(db.related_memories(c, 1).unwrap(), vec![(a, 1)]);
+
+        db.delete_memory(b).unwrap();
+        assert_eq!(db.related_memories(a, 10).unwrap(), vec![(c, 1)]);
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_backup_to_produces_standalone_copy() {
+        let (db, dir) = test_db();
+        db.insert_memory(Some(100), "backed up fact", "KNOWLEDGE")
+            .unwrap();
+        let dest = dir.join("backups").join("copy.db");
+        db.backup_to(&dest).unwrap();
+        // Later writes do not reach the copy.
+        db.insert_memory(Some(100), "after backup", "KNOWLEDGE")
+            .unwrap();
+
+        let copy = Database::open_file(&dest).unwrap();
+        let memories = copy.get_all_memories_for_chat(Some(100)).unwrap();
+        assert_eq!(memories.len(), 1);
+        assert_eq!(memories[0].content, "backed up fact");
+        assert!(!dir.join("backups").join("copy.db.partial").exists());
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_busy_timeout_waits_for_other_writer() {
+        let (db, dir) = test_db();
+        let path = dir.join("microclaw.db");
+        let other = Connection::open(&path).unwrap();
+        other.execute_batch("BEGIN IMMEDIATE").unwrap();
+
+        db.set_busy_timeout(std::time::Duration::ZERO).unwrap();
+        let err = db
+            .insert_memory(Some(100), "blocked", "KNOWLEDGE")
+            .unwrap_err();
+        assert!(err.to_string().contains("locked"), "{err}");
+
+        db.set_busy_timeout(std::time::Duration::from_secs(10))
+            .unwrap();
+        let releaser = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            other.execute_batch("COMMIT").unwrap();
+        });
+        db.insert_memory(Some(100), "waited", "KNOWLEDGE").unwrap();
+        releaser.join().unwrap();
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_search_memories_since_filters_by_created_at() {
+        let (db, dir) = test_db();
+        let old = db
+            .insert_memory(Some(100), "standup notes: old", "KNOWLEDGE")
+            .unwrap();
+        let recent = db
+            .insert_memory(Some(100), "standup notes: new", "KNOWLEDGE")
+            .unwrap();
+        {
+            let conn = db.lock_conn();
+            // Same instant boundary written with different offsets.
+            conn.execute(
+                "UPDATE memories SET created_at = '2026-03-01T08:59:00+09:00' WHERE id = ?1",
+                params![old],
+            )
+            .unwrap();
+            conn.execute(
+                "UPDATE memories SET created_at = '2026-03-01T00:01:00Z' WHERE id = ?1",
+                params![recent],
+            )
+            .unwrap();
+        }
+        let since = chrono::DateTime::parse_from_rfc3339("2026-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+
+        assert_eq!(db.search_memories(100, "standup", 10).unwrap().len(), 2);
+        let hits = db.search_memories_since(100, "standup", 10, since).unwrap();
+        assert_eq!(hits.iter().map(|m| m.id).collect::<Vec<_>>(), vec![recent]);
+
+        cleanup(&dir);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_knn_memories_since_looks_past_older_neighbours() {
+        let (db, dir) = test_db();
+        db.prepare_vector_index(3).unwrap();
+        let mut old = Vec::new();
+        for i in 0..5 {
+            let id = db
+                .insert_memory(Some(100), &format!("old {i}"), "KNOWLEDGE")
+                .unwrap();
+            db.upsert_memory_vec(id, &[1.0, 0.01 * i as f32, 0.0])
+                .unwrap();
+            old.push(id);
+        }
+        let recent = db.insert_memory(Some(100), "recent", "KNOWLEDGE").unwrap();
+        db.upsert_memory_vec(recent, &[0.0, 1.0, 0.0]).unwrap();
+        {
+            let conn = db.lock_conn();
+            for id in &old {
+                conn.execute(
+                    "UPDATE memories SET created_at = '2020-01-01T00:00:00Z' WHERE id = ?1",
+                    params![id],
+                )
+                .unwrap();
+            }
+        }
+        let since = chrono::DateTime::parse_from_rfc3339("2021-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+
+        // The single nearest neighbour is old; the recent one is still found.
+        let hits = db
+            .knn_memories_since(100, &[1.0, 0.0, 0.0], 1, since)
+            .unwrap();
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), vec![recent]);
+        let hits = db
+            .knn_memories_since(100, &[1.0, 0.0, 0.0], 3, since)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
 
         cleanup(&dir);
     }
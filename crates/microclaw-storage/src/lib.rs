@@ -3,4 +3,7 @@
 pub mod db;
 pub mod memory;
 pub mod memory_quality;
+#[cfg(feature = "binary-recall")]
+pub mod recall_codec;
 pub mod usage;
+pub mod vector;
@@ -1,13 +1,153 @@
-use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+const INCLUDE_OPEN: &str = "{{include:";
+/// Nesting limit for `{{include: ...}}` in AGENTS.md files.
+const MAX_INCLUDE_DEPTH: usize = 4;
+/// Includes resolved per memory file; bounds fan-out, which depth alone
+/// does not (a file including itself N times would expand N^depth times).
+const MAX_INCLUDES: usize = 32;
+/// Total bytes that includes may add to one memory file.
+const MAX_INCLUDE_BYTES: usize = 64 * 1024;
+
+/// File contents keyed by path, tagged with the (mtime, len) they were read at.
+type FileCache = Mutex<HashMap<PathBuf, (Option<(SystemTime, u64)>, Option<String>)>>;
+
+/// How the global and chat AGENTS.md sections, and recalled structured
+/// memories, are wrapped in the prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemoryContextFormat {
+    /// `<global_memory>` / `<chat_memory>` tags.
+    #[default]
+    XmlTags,
+    /// `## Global Memory` / `## Chat Memory` headings.
+    Markdown,
+    /// The file contents alone, separated by a blank line.
+    Plain,
+}
+
+impl MemoryContextFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemoryContextFormat::XmlTags => "xml",
+            MemoryContextFormat::Markdown => "markdown",
+            MemoryContextFormat::Plain => "plain",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "xml" | "xml_tags" => Some(MemoryContextFormat::XmlTags),
+            "markdown" | "md" => Some(MemoryContextFormat::Markdown),
+            "plain" => Some(MemoryContextFormat::Plain),
+            _ => None,
+        }
+    }
+
+    /// Append `body` to `context` wrapped as `<tag>`, `## heading` or bare text.
+    pub fn push_section(self, context: &mut String, tag: &str, heading: &str, body: &str) {
+        match self {
+            MemoryContextFormat::XmlTags => {
+                context.push_str(&format!("<{tag}>\n{body}\n</{tag}>\n\n"));
+            }
+            MemoryContextFormat::Markdown => {
+                context.push_str(&format!("## {heading}\n\n{body}\n\n"));
+            }
+            MemoryContextFormat::Plain => {
+                context.push_str(body);
+                context.push_str("\n\n");
+            }
+        }
+    }
+}
 
 pub struct MemoryManager {
     data_dir: PathBuf,
+    /// Read-only layers consulted before `data_dir`, in order. Each uses the
+    /// same layout: `AGENTS.md` for global memory, `<chat_id>/AGENTS.md` per chat.
+    base_dirs: Vec<PathBuf>,
+    /// When set, AGENTS.md reads are served from memory until the file's
+    /// mtime or size changes, so edits are picked up without a restart.
+    cache: Option<FileCache>,
+    format: MemoryContextFormat,
 }
 
 impl MemoryManager {
     pub fn new(data_dir: &str) -> Self {
         MemoryManager {
             data_dir: PathBuf::from(data_dir).join("groups"),
+            base_dirs: Vec::new(),
+            cache: None,
+            format: MemoryContextFormat::default(),
+        }
+    }
+
+    pub fn with_context_format(mut self, format: MemoryContextFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_file_cache(mut self, enabled: bool) -> Self {
+        self.cache = enabled.then(|| Mutex::new(HashMap::new()));
+        self
+    }
+
+    /// Drop every cached file so the next read goes to disk.
+    pub fn reload(&self) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+
+    fn invalidate(&self, path: &Path) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap_or_else(|e| e.into_inner()).remove(path);
+        }
+    }
+
+    fn read_file(&self, path: &Path) -> Option<String> {
+        let Some(cache) = &self.cache else {
+            return std::fs::read_to_string(path).ok();
+        };
+        let stamp = std::fs::metadata(path)
+            .ok()
+            .and_then(|m| Some((m.modified().ok()?, m.len())));
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached_stamp, content)) = cache.get(path) {
+            if *cached_stamp == stamp {
+                return content.clone();
+            }
+        }
+        let content = stamp.and_then(|_| std::fs::read_to_string(path).ok());
+        cache.insert(path.to_path_buf(), (stamp, content.clone()));
+        content
+    }
+
+    pub fn with_base_dirs<I, P>(mut self, base_dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.base_dirs = base_dirs.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Concatenate `relative` from every base dir and then `data_dir`, so later
+    /// layers augment earlier ones. Empty files are skipped.
+    fn read_layered(&self, relative: &Path) -> Option<String> {
+        let layers: Vec<String> = self
+            .base_dirs
+            .iter()
+            .chain(std::iter::once(&self.data_dir))
+            .filter_map(|dir| self.read_file(&dir.join(relative)))
+            .filter(|content| !content.trim().is_empty())
+            .collect();
+        if layers.is_empty() {
+            None
+        } else {
+            Some(layers.join("\n\n"))
         }
     }
 
@@ -20,13 +160,11 @@ impl MemoryManager {
     }
 
     pub fn read_global_memory(&self) -> Option<String> {
-        let path = self.global_memory_path();
-        std::fs::read_to_string(path).ok()
+        self.read_file(&self.global_memory_path())
     }
 
     pub fn read_chat_memory(&self, chat_id: i64) -> Option<String> {
-        let path = self.chat_memory_path(chat_id);
-        std::fs::read_to_string(path).ok()
+        self.read_file(&self.chat_memory_path(chat_id))
     }
 
     #[allow(dead_code)]
@@ -35,7 +173,9 @@ impl MemoryManager {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        std::fs::write(path, content)
+        std::fs::write(&path, content)?;
+        self.invalidate(&path);
+        Ok(())
     }
 
     #[allow(dead_code)]
@@ -44,29 +184,104 @@ impl MemoryManager {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        std::fs::write(path, content)
+        std::fs::write(&path, content)?;
+        self.invalidate(&path);
+        Ok(())
     }
 
-    pub fn build_memory_context(&self, chat_id: i64) -> String {
-        let mut context = String::new();
+    /// Layered AGENTS.md text with `{{include: path}}` directives expanded.
+    fn read_memory_file(&self, relative: &Path) -> Option<String> {
+        self.read_layered(relative).map(|content| {
+            let mut budget = IncludeBudget {
+                includes: MAX_INCLUDES,
+                bytes: MAX_INCLUDE_BYTES,
+            };
+            self.expand_includes(&content, 0, &mut budget)
+        })
+    }
 
-        if let Some(global) = self.read_global_memory() {
-            if !global.trim().is_empty() {
-                context.push_str("<global_memory>\n");
-                context.push_str(&global);
-                context.push_str("\n</global_memory>\n\n");
-            }
+    /// Inline every `{{include: path}}` in `content`. Paths are relative to
+    /// the groups dir and may not be absolute, contain `..`, or point into a
+    /// chat's own directory (another chat's memory). Nesting stops at
+    /// [`MAX_INCLUDE_DEPTH`] so include cycles terminate, and `budget` caps
+    /// how many includes and bytes one file can pull in.
+    fn expand_includes(&self, content: &str, depth: usize, budget: &mut IncludeBudget) -> String {
+        let mut out = String::with_capacity(content.len());
+        let mut rest = content;
+        while let Some(start) = rest.find(INCLUDE_OPEN) {
+            let after = &rest[start + INCLUDE_OPEN.len()..];
+            let Some(end) = after.find("}}") else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            out.push_str(&self.resolve_include(after[..end].trim(), depth, budget));
+            rest = &after[end + 2..];
         }
+        out.push_str(rest);
+        out
+    }
 
-        if let Some(chat) = self.read_chat_memory(chat_id) {
-            if !chat.trim().is_empty() {
-                context.push_str("<chat_memory>\n");
-                context.push_str(&chat);
-                context.push_str("\n</chat_memory>\n\n");
+    fn resolve_include(&self, target: &str, depth: usize, budget: &mut IncludeBudget) -> String {
+        let relative = Path::new(target);
+        let is_safe = !target.is_empty()
+            && relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        let in_chat_dir = relative
+            .components()
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .is_some_and(|first| first.parse::<i64>().is_ok());
+        if !is_safe || in_chat_dir {
+            return format!("[include rejected: {target}]");
+        }
+        if depth >= MAX_INCLUDE_DEPTH {
+            return format!("[include depth limit reached: {target}]");
+        }
+        if budget.includes == 0 {
+            return format!("[include limit reached: {target}]");
+        }
+        budget.includes -= 1;
+        match self.read_file(&self.data_dir.join(relative)) {
+            Some(included) if included.len() > budget.bytes => {
+                budget.bytes = 0;
+                format!("[include limit reached: {target}]")
+            }
+            Some(included) => {
+                budget.bytes -= included.len();
+                self.expand_includes(&included, depth + 1, budget)
             }
+            None => format!("[include not found: {target}]"),
         }
+    }
 
-        context
+    pub fn build_memory_context(&self, chat_id: i64) -> String {
+        let global = self.read_memory_file(Path::new("AGENTS.md"));
+        let chat = self.read_memory_file(&Path::new(&chat_id.to_string()).join("AGENTS.md"));
+        format_memory_context(self.format, global.as_deref(), chat.as_deref())
+    }
+
+    /// Like [`MemoryManager::build_memory_context`], but keeps the AGENTS.md
+    /// text within `max_chars`. Each file gets half the budget, and a file
+    /// that needs less hands the rest to the other. An oversized file keeps
+    /// its most recent whole `#` sections, or just its tail when even the
+    /// last section is too long, behind a truncation marker.
+    pub fn build_memory_context_budgeted(&self, chat_id: i64, max_chars: usize) -> String {
+        let global = self.read_memory_file(Path::new("AGENTS.md"));
+        let chat = self.read_memory_file(&Path::new(&chat_id.to_string()).join("AGENTS.md"));
+        let global_len = global.as_deref().map_or(0, |g| g.chars().count());
+        let chat_len = chat.as_deref().map_or(0, |c| c.chars().count());
+        let half = max_chars / 2;
+        let (global_budget, chat_budget) = if global_len <= half {
+            (global_len, max_chars - global_len)
+        } else if chat_len <= max_chars - half {
+            (max_chars - chat_len, chat_len)
+        } else {
+            (half, max_chars - half)
+        };
+        let global = global.map(|g| truncate_memory_text(&g, global_budget));
+        let chat = chat.map(|c| truncate_memory_text(&c, chat_budget));
+        format_memory_context(self.format, global.as_deref(), chat.as_deref())
     }
 
     #[allow(dead_code)]
@@ -75,6 +290,53 @@ impl MemoryManager {
     }
 }
 
+fn format_memory_context(
+    format: MemoryContextFormat,
+    global: Option<&str>,
+    chat: Option<&str>,
+) -> String {
+    let mut context = String::new();
+
+    if let Some(global) = global.filter(|g| !g.trim().is_empty()) {
+        format.push_section(&mut context, "global_memory", "Global Memory", global);
+    }
+
+    if let Some(chat) = chat.filter(|c| !c.trim().is_empty()) {
+        format.push_section(&mut context, "chat_memory", "Chat Memory", chat);
+    }
+
+    context
+}
+
+/// What is left of a memory file's include allowance.
+struct IncludeBudget {
+    includes: usize,
+    bytes: usize,
+}
+
+const MEMORY_TRUNCATION_MARKER: &str = "[... earlier memory truncated ...]\n";
+
+/// Cut `text` down to at most `max_chars` characters (marker included),
+/// dropping from the front so the most recently appended notes survive.
+fn truncate_memory_text(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let keep = max_chars.saturating_sub(MEMORY_TRUNCATION_MARKER.chars().count());
+    // Markdown headings mark section starts; the earliest one whose suffix
+    // fits keeps the most whole sections.
+    let section_start = text
+        .match_indices('#')
+        .map(|(i, _)| i)
+        .filter(|&i| i > 0 && text.as_bytes()[i - 1] == b'\n')
+        .find(|&i| text[i..].chars().count() <= keep);
+    let start = section_start.unwrap_or_else(|| match keep {
+        0 => text.len(),
+        n => text.char_indices().rev().nth(n - 1).map_or(0, |(i, _)| i),
+    });
+    format!("{MEMORY_TRUNCATION_MARKER}{}", &text[start..])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,6 +433,39 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn test_build_memory_context_formats() {
+        let (mm, dir) = test_memory_manager();
+        mm.write_global_memory("global stuff").unwrap();
+        mm.write_chat_memory(100, "chat stuff").unwrap();
+
+        let mm = mm.with_context_format(MemoryContextFormat::Markdown);
+        assert_eq!(
+            mm.build_memory_context(100),
+            "## Global Memory\n\nglobal stuff\n\n## Chat Memory\n\nchat stuff\n\n"
+        );
+
+        let mm = mm.with_context_format(MemoryContextFormat::Plain);
+        assert_eq!(
+            mm.build_memory_context(100),
+            "global stuff\n\nchat stuff\n\n"
+        );
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_memory_context_format_parse() {
+        assert_eq!(
+            MemoryContextFormat::parse(" Markdown "),
+            Some(MemoryContextFormat::Markdown)
+        );
+        assert_eq!(
+            MemoryContextFormat::parse("xml"),
+            Some(MemoryContextFormat::XmlTags)
+        );
+        assert_eq!(MemoryContextFormat::parse("yaml"), None);
+    }
+
     #[test]
     fn test_build_memory_context_ignores_whitespace_only() {
         let (mm, dir) = test_memory_manager();
@@ -181,6 +476,144 @@ mod tests {
         cleanup(&dir);
     }
 
+    #[test]
+    fn test_build_memory_context_layers_base_dirs() {
+        let (mm, dir) = test_memory_manager();
+        let base = dir.join("base");
+        std::fs::create_dir_all(base.join("100")).unwrap();
+        std::fs::write(base.join("AGENTS.md"), "shared persona").unwrap();
+        std::fs::write(base.join("100").join("AGENTS.md"), "base chat notes").unwrap();
+        let mm = mm.with_base_dirs([base]);
+        mm.write_global_memory("local override").unwrap();
+
+        let ctx = mm.build_memory_context(100);
+        let shared = ctx.find("shared persona").unwrap();
+        let local = ctx.find("local override").unwrap();
+        assert!(shared < local);
+        assert!(ctx.contains("base chat notes"));
+        assert!(!mm.build_memory_context(200).contains("base chat notes"));
+        // Direct reads still only see the writable data dir.
+        assert_eq!(mm.read_global_memory().unwrap(), "local override");
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_build_memory_context_budgeted_keeps_recent_sections() {
+        let (mm, dir) = test_memory_manager();
+        mm.write_global_memory("short global").unwrap();
+        let chat = format!("# Old\n{}# New\nnew notes", "old notes here\n".repeat(3));
+        mm.write_chat_memory(100, &chat).unwrap();
+        assert_eq!(
+            mm.build_memory_context_budgeted(100, 10_000),
+            mm.build_memory_context(100)
+        );
+
+        // The global file fits its half; the chat file gets the rest and
+        // keeps only its last section.
+        let ctx = mm.build_memory_context_budgeted(100, 70);
+        assert!(ctx.contains("short global"));
+        assert!(ctx.contains(MEMORY_TRUNCATION_MARKER));
+        assert!(ctx.contains("# New\nnew notes"));
+        assert!(!ctx.contains("old notes"));
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_truncate_memory_text_falls_back_to_tail() {
+        let marker_len = MEMORY_TRUNCATION_MARKER.chars().count();
+        let text = "no headings, just a long line ending in ünïcödé";
+        let out = truncate_memory_text(text, marker_len + 7);
+        assert_eq!(out, format!("{MEMORY_TRUNCATION_MARKER}ünïcödé"));
+        assert_eq!(truncate_memory_text(text, 1), MEMORY_TRUNCATION_MARKER);
+        assert_eq!(truncate_memory_text(text, 1000), text);
+    }
+
+    #[test]
+    fn test_build_memory_context_expands_includes() {
+        let (mm, dir) = test_memory_manager();
+        let shared = dir.join("groups").join("shared");
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::write(
+            shared.join("tone.md"),
+            "Be terse. {{include: shared/sig.md}}",
+        )
+        .unwrap();
+        std::fs::write(shared.join("sig.md"), "Sign as Claw.").unwrap();
+        std::fs::write(shared.join("loop.md"), "loop {{include: shared/loop.md}}").unwrap();
+        mm.write_global_memory(
+            "Persona:\n{{include: shared/tone.md}}\n{{include: ../secret}} {{include: shared/none.md}}",
+        )
+        .unwrap();
+        mm.write_chat_memory(100, "{{include: shared/loop.md}}")
+            .unwrap();
+
+        let ctx = mm.build_memory_context(100);
+        assert!(ctx.contains("Persona:\nBe terse. Sign as Claw.\n"));
+        assert!(ctx.contains("[include rejected: ../secret]"));
+        assert!(ctx.contains("[include not found: shared/none.md]"));
+        assert_eq!(ctx.matches("loop ").count(), MAX_INCLUDE_DEPTH);
+        assert!(ctx.contains("[include depth limit reached: shared/loop.md]"));
+        // Reads for the memory tools still return the file verbatim.
+        assert!(mm
+            .read_global_memory()
+            .unwrap()
+            .contains("{{include: shared/tone.md}}"));
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_includes_are_capped_and_cannot_read_other_chats() {
+        let (mm, dir) = test_memory_manager();
+        let shared = dir.join("groups").join("shared");
+        std::fs::create_dir_all(&shared).unwrap();
+        // Ten self-includes per level would be 10^4 copies without a cap.
+        std::fs::write(
+            shared.join("fan.md"),
+            format!("x{}", "{{include: shared/fan.md}}".repeat(10)),
+        )
+        .unwrap();
+        mm.write_chat_memory(200, "chat 200 secret").unwrap();
+        mm.write_chat_memory(
+            100,
+            "{{include: 200/AGENTS.md}} {{include: -5/AGENTS.md}} {{include: shared/fan.md}}",
+        )
+        .unwrap();
+
+        let ctx = mm.build_memory_context(100);
+        assert!(!ctx.contains("chat 200 secret"));
+        assert!(ctx.contains("[include rejected: 200/AGENTS.md]"));
+        assert!(ctx.contains("[include rejected: -5/AGENTS.md]"));
+        assert_eq!(ctx.matches('x').count(), MAX_INCLUDES);
+        assert!(ctx.contains("[include limit reached: shared/fan.md]"));
+
+        std::fs::write(shared.join("big.md"), "y".repeat(MAX_INCLUDE_BYTES + 1)).unwrap();
+        mm.write_chat_memory(100, "{{include: shared/big.md}}")
+            .unwrap();
+        let ctx = mm.build_memory_context(100);
+        assert!(ctx.contains("[include limit reached: shared/big.md]"));
+        cleanup(&dir);
+    }
+
+    #[test]
+    fn test_file_cache_picks_up_edits_and_reload() {
+        let (mm, dir) = test_memory_manager();
+        let mm = mm.with_file_cache(true);
+        assert!(mm.read_global_memory().is_none());
+        mm.write_global_memory("first").unwrap();
+        assert_eq!(mm.read_global_memory().unwrap(), "first");
+
+        // Edits made outside the manager are seen once the size changes.
+        std::fs::write(mm.global_memory_path(), "second edit").unwrap();
+        assert_eq!(mm.read_global_memory().unwrap(), "second edit");
+
+        // Same-size edits may share an mtime tick; reload() forces a re-read.
+        std::fs::write(mm.global_memory_path(), "third edit!").unwrap();
+        std::fs::write(mm.global_memory_path(), "final edit!").unwrap();
+        mm.reload();
+        assert!(mm.build_memory_context(1).contains("final edit!"));
+        cleanup(&dir);
+    }
+
     #[test]
     fn test_groups_dir() {
         let (mm, dir) = test_memory_manager();
@@ -1,3 +1,57 @@
+/// Category assigned to a structured memory row. Stored as the upper-case
+/// string returned by [`MemoryCategory::as_str`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryCategory {
+    Profile,
+    Knowledge,
+    Event,
+    Task,
+    Preference,
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 5] = [
+        MemoryCategory::Profile,
+        MemoryCategory::Knowledge,
+        MemoryCategory::Event,
+        MemoryCategory::Task,
+        MemoryCategory::Preference,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemoryCategory::Profile => "PROFILE",
+            MemoryCategory::Knowledge => "KNOWLEDGE",
+            MemoryCategory::Event => "EVENT",
+            MemoryCategory::Task => "TASK",
+            MemoryCategory::Preference => "PREFERENCE",
+        }
+    }
+
+    /// Case-insensitive parse; surrounding whitespace is ignored.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        Self::ALL
+            .into_iter()
+            .find(|c| c.as_str().eq_ignore_ascii_case(value))
+    }
+
+    /// All category names joined with `", "`, for prompts and error messages.
+    pub fn names_joined() -> String {
+        Self::ALL
+            .iter()
+            .map(|c| c.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl std::fmt::Display for MemoryCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 pub fn normalize_memory_content(input: &str, max_chars: usize) -> Option<String> {
     let cleaned = input.split_whitespace().collect::<Vec<_>>().join(" ");
     let mut content = cleaned.trim().to_string();
@@ -118,6 +172,23 @@ pub fn memory_topic_key(content: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_memory_category_round_trip() {
+        for category in MemoryCategory::ALL {
+            assert_eq!(MemoryCategory::parse(category.as_str()), Some(category));
+        }
+        assert_eq!(
+            MemoryCategory::parse(" preference "),
+            Some(MemoryCategory::Preference)
+        );
+        assert_eq!(MemoryCategory::parse("task"), Some(MemoryCategory::Task));
+        assert_eq!(MemoryCategory::parse("GOSSIP"), None);
+        assert_eq!(
+            MemoryCategory::names_joined(),
+            "PROFILE, KNOWLEDGE, EVENT, TASK, PREFERENCE"
+        );
+    }
+
     #[test]
     fn test_extract_explicit_memory_command() {
         // Strong prefixes — always save
@@ -0,0 +1,89 @@
+//! Compact binary encoding of recall results for callers that move them
+//! over their own RPC layer. Enabled by the `binary-recall` feature.
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Database, Memory};
+use microclaw_core::error::MicroClawError;
+
+/// The parts of a [`Memory`] a remote caller needs to use a recall hit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecalledMemory {
+    pub id: i64,
+    pub chat_id: Option<i64>,
+    pub title: Option<String>,
+    pub content: String,
+    pub category: String,
+    pub confidence: f64,
+    pub pinned: bool,
+    pub attachment_path: Option<String>,
+}
+
+impl From<Memory> for RecalledMemory {
+    fn from(m: Memory) -> Self {
+        RecalledMemory {
+            id: m.id,
+            chat_id: m.chat_id,
+            title: m.title,
+            content: m.content,
+            category: m.category,
+            confidence: m.confidence,
+            pinned: m.pinned,
+            attachment_path: m.attachment_path,
+        }
+    }
+}
+
+impl Database {
+    /// Keyword search results encoded with [`encode_recall`]; decode them
+    /// with [`decode_recall`]. This is [`Database::search_memories`], a
+    /// case-insensitive substring match on the whole `query`, not the ranked
+    /// keyword-plus-vector recall that builds the prompt.
+    pub fn search_bytes(
+        &self,
+        chat_id: i64,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<u8>, MicroClawError> {
+        let memories = self.search_memories(chat_id, query, limit)?;
+        encode_recall(&memories.into_iter().map(Into::into).collect::<Vec<_>>())
+    }
+}
+
+pub fn encode_recall(memories: &[RecalledMemory]) -> Result<Vec<u8>, MicroClawError> {
+    postcard::to_allocvec(memories).map_err(invalid_data)
+}
+
+pub fn decode_recall(bytes: &[u8]) -> Result<Vec<RecalledMemory>, MicroClawError> {
+    postcard::from_bytes(bytes).map_err(invalid_data)
+}
+
+fn invalid_data(e: postcard::Error) -> MicroClawError {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_bytes_round_trip() {
+        let dir = std::env::temp_dir().join(format!("microclaw_codec_{}", uuid::Uuid::new_v4()));
+        let db = Database::new(dir.to_str().unwrap()).unwrap();
+        let id = db
+            .insert_memory(Some(100), "User prefers dark mode", "PROFILE")
+            .unwrap();
+        db.insert_memory(Some(100), "Unrelated fact", "KNOWLEDGE")
+            .unwrap();
+
+        let bytes = db.search_bytes(100, "dark mode", 10).unwrap();
+        let recalled = decode_recall(&bytes).unwrap();
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].id, id);
+        assert_eq!(recalled[0].content, "User prefers dark mode");
+        assert_eq!(recalled[0].category, "PROFILE");
+
+        assert!(decode_recall(&bytes[..bytes.len() - 1]).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
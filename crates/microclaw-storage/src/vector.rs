@@ -0,0 +1,138 @@
+//! Rust-side vector scoring helpers.
+//!
+//! KNN candidate selection runs inside sqlite-vec; these helpers score
+//! full-precision vectors in process once candidates have been loaded.
+
+fn norm(v: &[f32]) -> f32 {
+    v.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+/// Cosine similarity of two vectors. Returns 0.0 for mismatched lengths or
+/// zero-norm inputs.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    dot / denom
+}
+
+/// Score every candidate against `query`, computing the query norm once.
+/// Output order matches `candidates`.
+pub fn cosine_similarity_batch<C: AsRef<[f32]>>(query: &[f32], candidates: &[C]) -> Vec<f32> {
+    let query_norm = norm(query);
+    candidates
+        .iter()
+        .map(|c| {
+            let c = c.as_ref();
+            if query_norm == 0.0 || c.len() != query.len() || c.is_empty() {
+                return 0.0;
+            }
+            let mut dot = 0.0f32;
+            let mut c_sq = 0.0f32;
+            for (q, x) in query.iter().zip(c) {
+                dot += q * x;
+                c_sq += x * x;
+            }
+            let denom = query_norm * c_sq.sqrt();
+            if denom == 0.0 {
+                0.0
+            } else {
+                dot / denom
+            }
+        })
+        .collect()
+}
+
+/// True if `v` is worth indexing: non-empty, finite and not all zeros. An
+/// empty or zero vector has no direction, so it would score 0 against every
+/// query for as long as it stays stored.
+pub fn is_usable_embedding(v: &[f32]) -> bool {
+    !v.is_empty() && v.iter().all(|x| x.is_finite()) && v.iter().any(|&x| x != 0.0)
+}
+
+/// Maximal Marginal Relevance ordering. Greedily picks the candidate with
+/// the best `lambda * relevance - (1 - lambda) * similarity`, where the
+/// similarity is the highest cosine similarity to anything already picked.
+/// Returns indices into `relevance` in pick order. `lambda = 1.0` keeps pure
+/// relevance order; lower values trade relevance for diversity. Candidates
+/// without a vector (missing or empty) are never penalised.
+pub fn mmr_order(relevance: &[f32], vectors: &[Vec<f32>], lambda: f32) -> Vec<usize> {
+    let lambda = lambda.clamp(0.0, 1.0);
+    let vector = |i: usize| vectors.get(i).map_or(&[][..], Vec::as_slice);
+    let mut remaining: Vec<usize> = (0..relevance.len()).collect();
+    let mut max_similarity = vec![0.0f32; relevance.len()];
+    let mut picked = Vec::with_capacity(relevance.len());
+    while !remaining.is_empty() {
+        let mut best_pos = 0;
+        let mut best_score = f32::NEG_INFINITY;
+        for (pos, &i) in remaining.iter().enumerate() {
+            let score = lambda * relevance[i] - (1.0 - lambda) * max_similarity[i];
+            if score > best_score {
+                best_pos = pos;
+                best_score = score;
+            }
+        }
+        let best = remaining.remove(best_pos);
+        let candidates: Vec<&[f32]> = remaining.iter().map(|&i| vector(i)).collect();
+        let similarities = cosine_similarity_batch(vector(best), &candidates);
+        for (&i, similarity) in remaining.iter().zip(similarities) {
+            max_similarity[i] = max_similarity[i].max(similarity);
+        }
+        picked.push(best);
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity_basic() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn test_cosine_similarity_batch_matches_pairwise() {
+        let query = vec![0.3, -0.2, 0.9];
+        let candidates = vec![
+            vec![0.3, -0.2, 0.9],
+            vec![1.0, 0.0, 0.0],
+            vec![-0.3, 0.2, -0.9],
+            vec![0.0, 0.0, 0.0],
+            vec![1.0, 2.0],
+        ];
+        let batch = cosine_similarity_batch(&query, &candidates);
+        assert_eq!(batch.len(), candidates.len());
+        for (score, c) in batch.iter().zip(&candidates) {
+            assert!((score - cosine_similarity(&query, c)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_is_usable_embedding() {
+        assert!(is_usable_embedding(&[0.0, 0.5]));
+        assert!(!is_usable_embedding(&[]));
+        assert!(!is_usable_embedding(&[0.0, 0.0]));
+        assert!(!is_usable_embedding(&[f32::NAN, 1.0]));
+    }
+
+    #[test]
+    fn test_mmr_order_promotes_diverse_candidates() {
+        let relevance = [0.95, 0.94, 0.80];
+        let vectors = vec![vec![1.0, 0.0], vec![0.99, 0.01], vec![0.0, 1.0]];
+        assert_eq!(mmr_order(&relevance, &vectors, 1.0), vec![0, 1, 2]);
+        // The near-duplicate of the top hit drops below the distinct one.
+        assert_eq!(mmr_order(&relevance, &vectors, 0.5), vec![0, 2, 1]);
+        // Missing vectors carry no penalty.
+        assert_eq!(mmr_order(&relevance, &[], 0.5), vec![0, 1, 2]);
+    }
+}
@@ -9,6 +9,53 @@ use serde_json::json;
 
 use crate::types::WorkingDirIsolation;
 
+/// A file a tool produced, reported alongside its text output so channels can
+/// deliver it as a document or photo.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub mime: String,
+    pub label: String,
+}
+
+impl Artifact {
+    /// Describe `path`, guessing the MIME type from its extension and using
+    /// the file name as the label.
+    pub fn from_path(path: PathBuf) -> Self {
+        let mime = mime_for_extension(path.extension().and_then(|e| e.to_str()).unwrap_or(""));
+        let label = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Artifact {
+            path,
+            mime: mime.to_string(),
+            label,
+        }
+    }
+}
+
+fn mime_for_extension(ext: &str) -> &'static str {
+    match ext.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "csv" => "text/csv",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+#[derive(Clone)]
 pub struct ToolResult {
     pub content: String,
     pub is_error: bool,
@@ -16,6 +63,8 @@ pub struct ToolResult {
     pub bytes: usize,
     pub duration_ms: Option<u128>,
     pub error_type: Option<String>,
+    /// Files produced by the run, kept out of `content`.
+    pub artifacts: Vec<Artifact>,
 }
 
 impl ToolResult {
@@ -28,6 +77,7 @@ impl ToolResult {
             bytes,
             duration_ms: None,
             error_type: None,
+            artifacts: Vec::new(),
         }
     }
 
@@ -40,6 +90,7 @@ impl ToolResult {
             bytes,
             duration_ms: None,
             error_type: Some("tool_error".to_string()),
+            artifacts: Vec::new(),
         }
     }
 
@@ -52,6 +103,11 @@ impl ToolResult {
         self.error_type = Some(error_type.into());
         self
     }
+
+    pub fn with_artifacts(mut self, artifacts: Vec<Artifact>) -> Self {
+        self.artifacts = artifacts;
+        self
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -73,7 +129,7 @@ impl ToolRisk {
 
 pub fn tool_risk(name: &str) -> ToolRisk {
     match name {
-        "bash" => ToolRisk::High,
+        "bash" | "run_command" => ToolRisk::High,
         "write_file"
         | "edit_file"
         | "write_memory"
@@ -217,6 +273,14 @@ pub fn resolve_tool_working_dir(
     resolved
 }
 
+/// Empty every working directory `resolve_tool_working_dir` can hand out
+/// under `base_working_dir` (the shared dir and all per-chat dirs). Other
+/// files in `base_working_dir` are left alone.
+pub fn clear_tool_working_dirs(base_working_dir: &Path) -> std::io::Result<()> {
+    crate::sandbox::clear_dir_contents(&base_working_dir.join("shared"))?;
+    crate::sandbox::clear_dir_contents(&base_working_dir.join("chat"))
+}
+
 fn requires_high_risk_approval(name: &str, auth: &ToolAuthContext) -> bool {
     tool_risk(name) == ToolRisk::High && (auth.caller_channel == "web" || auth.is_control_chat())
 }
@@ -283,3 +347,76 @@ pub fn schema_object(properties: serde_json::Value, required: &[&str]) -> serde_
         "required": required,
     })
 }
+
+fn json_type_matches(expected: &str, value: &serde_json::Value) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        // Unknown type keywords are not ours to enforce.
+        _ => true,
+    }
+}
+
+/// Check `input` against the top level of a tool's `input_schema`: required
+/// fields must be present and non-null, and present fields must match their
+/// declared `type` and `enum`. Extra fields are allowed (the registry injects
+/// `__microclaw_auth`). Returns one message per problem.
+pub fn validate_tool_input(
+    schema: &serde_json::Value,
+    input: &serde_json::Value,
+) -> Result<(), Vec<String>> {
+    let Some(fields) = input.as_object() else {
+        return Err(vec!["input must be a JSON object".to_string()]);
+    };
+    let mut problems = Vec::new();
+
+    if let Some(required) = schema.get("required").and_then(|v| v.as_array()) {
+        for name in required.iter().filter_map(|v| v.as_str()) {
+            if fields.get(name).is_none_or(|v| v.is_null()) {
+                problems.push(format!("missing required field '{name}'"));
+            }
+        }
+    }
+
+    if let Some(properties) = schema.get("properties").and_then(|v| v.as_object()) {
+        for (name, spec) in properties {
+            let Some(value) = fields.get(name).filter(|v| !v.is_null()) else {
+                continue;
+            };
+            let expected: Vec<&str> = match spec.get("type") {
+                Some(serde_json::Value::String(t)) => vec![t.as_str()],
+                Some(serde_json::Value::Array(ts)) => {
+                    ts.iter().filter_map(|t| t.as_str()).collect()
+                }
+                _ => Vec::new(),
+            };
+            if !expected.is_empty() && !expected.iter().any(|t| json_type_matches(t, value)) {
+                problems.push(format!("field '{name}' must be {}", expected.join(" or ")));
+                continue;
+            }
+            if let Some(allowed) = spec.get("enum").and_then(|v| v.as_array()) {
+                if !allowed.contains(value) {
+                    let options = allowed
+                        .iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    problems.push(format!("field '{name}' must be one of: {options}"));
+                }
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
@@ -1,7 +1,8 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
@@ -33,6 +34,10 @@ fn default_sandbox_require_runtime() -> bool {
     false
 }
 
+fn default_sandbox_working_dir_cleanup() -> WorkingDirCleanup {
+    WorkingDirCleanup::Never
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum SandboxMode {
@@ -47,6 +52,17 @@ pub enum SandboxBackend {
     Docker,
 }
 
+/// When tool working directories are emptied. Files are kept by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkingDirCleanup {
+    Never,
+    /// Empty the run's working directory after every bash command.
+    AfterEachRun,
+    /// Empty every tool working directory once when the process starts.
+    OnStartup,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SandboxConfig {
     #[serde(default = "default_sandbox_mode")]
@@ -59,6 +75,11 @@ pub struct SandboxConfig {
     pub container_prefix: String,
     #[serde(default = "default_sandbox_no_network")]
     pub no_network: bool,
+    /// Docker network to attach when `no_network` is false, e.g. an internal
+    /// network whose only egress is an allowlisting proxy. Unset uses
+    /// Docker's default bridge.
+    #[serde(default)]
+    pub network: Option<String>,
     #[serde(default = "default_sandbox_require_runtime")]
     pub require_runtime: bool,
     #[serde(default)]
@@ -67,6 +88,10 @@ pub struct SandboxConfig {
     pub cpu_quota: Option<f64>,
     #[serde(default)]
     pub pids_limit: Option<u32>,
+    #[serde(default)]
+    pub working_dir_quota_mb: Option<u64>,
+    #[serde(default = "default_sandbox_working_dir_cleanup")]
+    pub working_dir_cleanup: WorkingDirCleanup,
 }
 
 impl Default for SandboxConfig {
@@ -77,10 +102,13 @@ impl Default for SandboxConfig {
             image: default_sandbox_image(),
             container_prefix: default_sandbox_container_prefix(),
             no_network: default_sandbox_no_network(),
+            network: None,
             require_runtime: default_sandbox_require_runtime(),
             memory_limit: None,
             cpu_quota: None,
             pids_limit: None,
+            working_dir_quota_mb: None,
+            working_dir_cleanup: default_sandbox_working_dir_cleanup(),
         }
     }
 }
@@ -157,6 +185,16 @@ impl DockerSandbox {
         )
     }
 
+    fn network_args(&self) -> Vec<String> {
+        if self.config.no_network {
+            return vec!["--network=none".to_string()];
+        }
+        match self.config.network.as_deref().map(str::trim) {
+            Some(network) if !network.is_empty() => vec![format!("--network={network}")],
+            _ => Vec::new(),
+        }
+    }
+
     fn resource_args(&self) -> Vec<String> {
         let mut args = Vec::new();
         if let Some(mem) = &self.config.memory_limit {
@@ -201,9 +239,7 @@ impl Sandbox for DockerSandbox {
             "--security-opt".to_string(),
             "no-new-privileges".to_string(),
         ];
-        if self.config.no_network {
-            args.push("--network=none".to_string());
-        }
+        args.extend(self.network_args());
         args.extend(self.resource_args());
         let mount = self.mount_dir.display().to_string();
         args.extend(["-v".to_string(), format!("{mount}:{mount}:rw")]);
@@ -234,25 +270,35 @@ impl Sandbox for DockerSandbox {
             args.extend(["-w".to_string(), dir.display().to_string()]);
         }
         args.push(name);
-        args.extend(["sh".to_string(), "-c".to_string(), command.to_string()]);
+        // Killing the local `docker exec` client leaves the command running in
+        // the container, so bound it in there too when `timeout` exists.
+        args.extend([
+            "sh".to_string(),
+            "-c".to_string(),
+            "if command -v timeout >/dev/null 2>&1; then exec timeout -s KILL \"$1\" sh -c \"$2\"; else exec sh -c \"$2\"; fi".to_string(),
+            "sh".to_string(),
+            opts.timeout.as_secs().max(1).to_string(),
+            command.to_string(),
+        ]);
         let child = tokio::process::Command::new("docker")
             .args(&args)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .stdin(std::process::Stdio::null())
+            .kill_on_drop(true)
             .spawn()
             .context("failed to spawn docker exec")?;
-        match tokio::time::timeout(opts.timeout, child.wait_with_output()).await {
-            Ok(Ok(output)) => Ok(SandboxExecResult {
+        match wait_or_kill(child, opts.timeout).await {
+            Ok(Some(output)) => Ok(SandboxExecResult {
                 stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
                 stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
                 exit_code: output.status.code().unwrap_or(-1),
             }),
-            Ok(Err(e)) => bail!("docker exec failed: {e}"),
-            Err(_) => bail!(
+            Ok(None) => bail!(
                 "docker exec timed out after {} seconds",
                 opts.timeout.as_secs()
             ),
+            Err(e) => bail!("docker exec failed: {e}"),
         }
     }
 }
@@ -322,16 +368,169 @@ pub async fn exec_host_command(
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
     cmd.stdin(std::process::Stdio::null());
+    cmd.kill_on_drop(true);
+    // Its own process group, so a timeout also kills whatever it forked.
+    #[cfg(unix)]
+    cmd.process_group(0);
     let child = cmd.spawn().context("failed to start shell command")?;
-    match tokio::time::timeout(opts.timeout, child.wait_with_output()).await {
-        Ok(Ok(output)) => Ok(SandboxExecResult {
+    match wait_or_kill(child, opts.timeout).await {
+        Ok(Some(output)) => Ok(SandboxExecResult {
             stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
             stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
             exit_code: output.status.code().unwrap_or(-1),
         }),
-        Ok(Err(e)) => bail!("failed to run command: {e}"),
-        Err(_) => bail!("command timed out after {} seconds", opts.timeout.as_secs()),
+        Ok(None) => bail!("command timed out after {} seconds", opts.timeout.as_secs()),
+        Err(e) => bail!("failed to run command: {e}"),
+    }
+}
+
+/// Wait for `child` and collect its output, or kill it once `timeout` passes
+/// and return `None`. A child that leads its own process group is killed
+/// along with the rest of the group, so spawn with `process_group(0)` to
+/// reap anything it forks.
+pub async fn wait_or_kill(
+    mut child: tokio::process::Child,
+    timeout: Duration,
+) -> std::io::Result<Option<std::process::Output>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let collect = async {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let read_out = async {
+            match stdout.as_mut() {
+                Some(pipe) => pipe.read_to_end(&mut out).await.map(|_| ()),
+                None => Ok(()),
+            }
+        };
+        let read_err = async {
+            match stderr.as_mut() {
+                Some(pipe) => pipe.read_to_end(&mut err).await.map(|_| ()),
+                None => Ok(()),
+            }
+        };
+        let (status, read_out, read_err) = tokio::join!(child.wait(), read_out, read_err);
+        read_out?;
+        read_err?;
+        Ok::<_, std::io::Error>((status?, out, err))
+    };
+    if let Ok(result) = tokio::time::timeout(timeout, collect).await {
+        let (status, stdout, stderr) = result?;
+        return Ok(Some(std::process::Output {
+            status,
+            stdout,
+            stderr,
+        }));
+    }
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: kill(2) has no memory-safety preconditions; a pid that does
+        // not lead a group just yields ESRCH.
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+    let _ = child.kill().await;
+    Ok(None)
+}
+
+/// Total size in bytes of regular files under `dir`. Symlinks are not followed;
+/// unreadable entries are skipped.
+pub fn dir_size_bytes(dir: &Path) -> u64 {
+    let mut total = 0u64;
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                stack.push(entry.path());
+            } else if meta.is_file() {
+                total = total.saturating_add(meta.len());
+            }
+        }
+    }
+    total
+}
+
+/// (modified time, size) of each regular file, keyed by path.
+pub type FileSnapshot = HashMap<PathBuf, (Option<SystemTime>, u64)>;
+
+/// Directory levels below the root that [`snapshot_files`] descends into.
+const SNAPSHOT_MAX_DEPTH: usize = 3;
+/// Files [`snapshot_files`] stamps before giving up on a tree.
+const SNAPSHOT_MAX_FILES: usize = 2000;
+
+/// Stamp every regular file under `dir`, so the files a command writes can be
+/// found afterwards. Symlinks are not followed. Returns `None` when the tree
+/// holds more than `SNAPSHOT_MAX_FILES` files within `SNAPSHOT_MAX_DEPTH`
+/// levels (a checked-out repo or `node_modules`), so callers skip it rather
+/// than stat the whole tree on every run.
+pub fn snapshot_files(dir: &Path) -> Option<FileSnapshot> {
+    let mut files = HashMap::new();
+    let mut stack = vec![(dir.to_path_buf(), 0usize)];
+    while let Some((current, depth)) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                if depth < SNAPSHOT_MAX_DEPTH {
+                    stack.push((entry.path(), depth + 1));
+                }
+            } else if meta.is_file() {
+                if files.len() == SNAPSHOT_MAX_FILES {
+                    return None;
+                }
+                files.insert(entry.path(), (meta.modified().ok(), meta.len()));
+            }
+        }
+    }
+    Some(files)
+}
+
+/// Files under `dir` that are new or changed since `before`, sorted by path
+/// and capped at `limit`. Empty when the tree has outgrown [`snapshot_files`].
+pub fn changed_files(dir: &Path, before: &FileSnapshot, limit: usize) -> Vec<PathBuf> {
+    let Some(after) = snapshot_files(dir) else {
+        return Vec::new();
+    };
+    let mut changed: Vec<PathBuf> = after
+        .into_iter()
+        .filter(|(path, stamp)| before.get(path) != Some(stamp))
+        .map(|(path, _)| path)
+        .collect();
+    changed.sort();
+    changed.truncate(limit);
+    changed
+}
+
+/// Remove everything inside `dir` but keep the directory itself. A missing
+/// directory counts as already empty.
+pub fn clear_dir_contents(dir: &Path) -> std::io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
     }
+    Ok(())
 }
 
 fn docker_available() -> bool {
@@ -368,15 +567,138 @@ fn sanitize_segment(input: &str) -> String {
 mod tests {
     use super::*;
 
+    /// An empty directory unique to this test run, so a stale one left by an
+    /// earlier failed run can't satisfy or break the assertions.
+    fn fresh_test_dir(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("microclaw_sandbox_{name}_{}", uuid::Uuid::new_v4()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
     #[test]
     fn test_sanitize_segment() {
         assert_eq!(sanitize_segment("Web:10001"), "web-10001");
     }
 
+    #[test]
+    fn test_dir_size_bytes_counts_nested_files() {
+        let root = fresh_test_dir("size");
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        std::fs::write(root.join("a.txt"), vec![0u8; 10]).unwrap();
+        std::fs::write(root.join("nested").join("b.txt"), vec![0u8; 32]).unwrap();
+        assert_eq!(dir_size_bytes(&root), 42);
+        assert_eq!(dir_size_bytes(&root.join("missing")), 0);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_clear_dir_contents_keeps_dir() {
+        let root = fresh_test_dir("clear");
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("nested").join("b.txt"), "b").unwrap();
+
+        clear_dir_contents(&root).unwrap();
+        assert!(root.is_dir());
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+        clear_dir_contents(&root.join("missing")).unwrap();
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_changed_files_reports_new_and_modified() {
+        let root = fresh_test_dir("changed");
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        std::fs::write(root.join("kept.txt"), "a").unwrap();
+        std::fs::write(root.join("edited.txt"), "a").unwrap();
+        let before = snapshot_files(&root).unwrap();
+
+        std::fs::write(root.join("edited.txt"), "ab").unwrap();
+        std::fs::write(root.join("nested").join("plot.png"), "png").unwrap();
+        assert_eq!(
+            changed_files(&root, &before, 10),
+            vec![
+                root.join("edited.txt"),
+                root.join("nested").join("plot.png")
+            ]
+        );
+        assert_eq!(changed_files(&root, &before, 1).len(), 1);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_snapshot_files_gives_up_on_large_trees() {
+        let root = fresh_test_dir("large");
+        let deep = root.join("a").join("b").join("c").join("d");
+        std::fs::create_dir_all(&deep).unwrap();
+        std::fs::write(deep.join("too_deep.txt"), "x").unwrap();
+        assert_eq!(snapshot_files(&root).unwrap().len(), 0);
+
+        for i in 0..=SNAPSHOT_MAX_FILES {
+            std::fs::write(root.join(format!("{i}.txt")), "x").unwrap();
+        }
+        assert!(snapshot_files(&root).is_none());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_network_args_follow_config() {
+        let mut config = SandboxConfig {
+            network: Some("egress-proxy".into()),
+            ..SandboxConfig::default()
+        };
+        let sandbox = DockerSandbox::new(config.clone(), PathBuf::from("/tmp"));
+        assert_eq!(sandbox.network_args(), vec!["--network=none"]);
+
+        config.no_network = false;
+        let sandbox = DockerSandbox::new(config.clone(), PathBuf::from("/tmp"));
+        assert_eq!(sandbox.network_args(), vec!["--network=egress-proxy"]);
+
+        config.network = None;
+        let sandbox = DockerSandbox::new(config, PathBuf::from("/tmp"));
+        assert!(sandbox.network_args().is_empty());
+    }
+
     #[test]
     fn test_router_default_backend_name() {
         let router = SandboxRouter::new(SandboxConfig::default(), Path::new("./tmp"));
         let name = router.backend_name();
         assert!(name == "docker" || name == "none");
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_host_command_is_killed_on_timeout() {
+        let root = fresh_test_dir("kill");
+        let opts = SandboxExecOptions {
+            timeout: Duration::from_millis(100),
+            working_dir: Some(root.clone()),
+        };
+        let err = exec_host_command("sleep 1 && touch finished", &opts)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!root.join("finished").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_host_command_timeout_kills_background_children() {
+        let root = fresh_test_dir("kill_group");
+        let opts = SandboxExecOptions {
+            timeout: Duration::from_millis(100),
+            working_dir: Some(root.clone()),
+        };
+        let err = exec_host_command("(sleep 1 && touch finished) & wait", &opts)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!root.join("finished").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
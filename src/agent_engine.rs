@@ -1,11 +1,11 @@
 use async_trait::async_trait;
 use tokio::sync::mpsc::UnboundedSender;
-use tracing::{info, warn};
+use tracing::{field, info, info_span, warn, Instrument};
 
-use crate::embedding::EmbeddingProvider;
+use crate::embedding::{EmbeddingProvider, RecallCache};
 use crate::hooks::HookOutcome;
 use crate::runtime::AppState;
-use crate::tools::ToolAuthContext;
+use crate::tools::{Artifact, ToolAuthContext};
 use microclaw_core::llm_types::{
     ContentBlock, ImageSource, Message, MessageContent, ResponseContentBlock,
 };
@@ -35,6 +35,7 @@ pub enum AgentEvent {
         status_code: Option<i32>,
         bytes: usize,
         error_type: Option<String>,
+        artifacts: Vec<Artifact>,
     },
     TextDelta {
         delta: String,
@@ -236,6 +237,15 @@ async fn maybe_handle_explicit_memory_command(
         )));
     }
 
+    let store_span = info_span!(
+        "memory_store",
+        chat_id,
+        source = "explicit",
+        memory_id = field::Empty,
+        embedded = false,
+        duration_ms = field::Empty,
+    );
+    let started = std::time::Instant::now();
     let content_for_insert = explicit_content.clone();
     let inserted_id = call_blocking(state.db.clone(), move |db| {
         db.insert_memory_with_metadata(
@@ -246,22 +256,37 @@ async fn maybe_handle_explicit_memory_command(
             0.95,
         )
     })
+    .instrument(store_span.clone())
     .await?;
+    store_span.record("memory_id", inserted_id);
 
-    #[cfg(feature = "sqlite-vec")]
-    {
+    if let Some(queue) = &state.embedding_queue {
+        queue.enqueue(inserted_id, explicit_content.clone());
+    } else {
+        #[cfg(feature = "sqlite-vec")]
         if let Some(provider) = &state.embedding {
-            if let Ok(embedding) = provider.embed(&explicit_content).await {
+            if let Ok(embedding) = provider
+                .embed_document(&explicit_content)
+                .instrument(store_span.clone())
+                .await
+            {
                 let provider_model = provider.model().to_string();
-                let _ = call_blocking(state.db.clone(), move |db| {
-                    db.upsert_memory_vec(inserted_id, &embedding)?;
-                    db.update_memory_embedding_model(inserted_id, &provider_model)?;
-                    Ok(())
+                let content = explicit_content.clone();
+                let stored = call_blocking(state.db.clone(), move |db| {
+                    db.store_memory_embedding_if_current(
+                        inserted_id,
+                        &content,
+                        &embedding,
+                        &provider_model,
+                    )
                 })
+                .instrument(store_span.clone())
                 .await;
+                store_span.record("embedded", matches!(stored, Ok(true)));
             }
         }
     }
+    store_span.record("duration_ms", started.elapsed().as_millis() as u64);
 
     Ok(Some(format!(
         "Noted. Saved memory #{inserted_id}: {explicit_content}"
@@ -351,13 +376,19 @@ pub(crate) async fn process_with_agent_impl(
         .collect();
 
     // Build system prompt
-    let file_memory = state.memory.build_memory_context(chat_id);
+    let file_memory = match state.config.memory_file_max_chars {
+        0 => state.memory.build_memory_context(chat_id),
+        max_chars => state
+            .memory
+            .build_memory_context_budgeted(chat_id, max_chars),
+    };
     let db_memory = build_db_memory_context(
         &state.db,
         &state.embedding,
+        state.recall_cache.as_ref(),
         chat_id,
         &query,
-        state.config.memory_token_budget,
+        &RecallOptions::from_config(&state.config),
     )
     .await;
     let memory_context = format!("{}{}", file_memory, db_memory);
@@ -418,7 +449,7 @@ pub(crate) async fn process_with_agent_impl(
         .await;
     }
 
-    let tool_defs = state.tools.definitions().to_vec();
+    let tool_defs = state.tools.definitions();
     let tool_auth = ToolAuthContext {
         caller_channel: context.caller_channel.to_string(),
         caller_chat_id: chat_id,
@@ -759,6 +790,7 @@ pub(crate) async fn process_with_agent_impl(
                             status_code: result.status_code,
                             bytes: result.bytes,
                             error_type: result.error_type.clone(),
+                            artifacts: result.artifacts.clone(),
                         });
                     }
                     tool_results.push(ContentBlock::ToolResult {
@@ -896,28 +928,177 @@ fn tokenize_for_relevance(text: &str) -> std::collections::HashSet<String> {
     out
 }
 
+/// Common English words that appear in almost every memory and would
+/// otherwise outweigh the query terms that matter in keyword recall.
+const RECALL_STOPWORDS: &[&str] = &[
+    "about", "an", "and", "are", "as", "at", "be", "by", "can", "did", "do", "does", "for", "from",
+    "had", "has", "have", "how", "in", "is", "it", "its", "me", "my", "of", "on", "or", "our",
+    "so", "that", "the", "there", "this", "to", "was", "we", "were", "what", "which", "who",
+    "with", "you", "your",
+];
+
+/// Drop stopwords from recall query tokens, unless that would leave nothing
+/// to match on.
+fn strip_stopwords(
+    tokens: std::collections::HashSet<String>,
+    extra: &[String],
+) -> std::collections::HashSet<String> {
+    let kept: std::collections::HashSet<String> = tokens
+        .iter()
+        .filter(|t| {
+            !RECALL_STOPWORDS.contains(&t.as_str())
+                && !extra.iter().any(|w| w.to_lowercase() == **t)
+        })
+        .cloned()
+        .collect();
+    if kept.is_empty() {
+        tokens
+    } else {
+        kept
+    }
+}
+
+fn char_trigrams(token: &str) -> std::collections::HashSet<String> {
+    let chars: Vec<char> = token.chars().collect();
+    chars.windows(3).map(|w| w.iter().collect()).collect()
+}
+
+/// Minimum trigram Jaccard similarity for a query token to count as a typo
+/// of a content token ("kubernets" vs "kubernetes" scores ~0.67).
+const FUZZY_TOKEN_MIN_SIMILARITY: f64 = 0.5;
+
+/// Returns (exact token matches, typo-tolerant matches). Fuzzy matching only
+/// considers query tokens of 4+ characters that had no exact match, and ranks
+/// below exact hits.
 fn score_relevance_with_cache(
     content: &str,
     query_tokens: &std::collections::HashSet<String>,
-) -> usize {
+) -> (usize, usize) {
     if query_tokens.is_empty() {
-        return 0;
+        return (0, 0);
     }
     let content_tokens = tokenize_for_relevance(content);
-    content_tokens
+    let exact = content_tokens
         .iter()
         .filter(|t| query_tokens.contains(*t))
-        .count()
+        .count();
+    let fuzzy = query_tokens
+        .iter()
+        .filter(|q| q.chars().count() >= 4 && !content_tokens.contains(*q))
+        .filter(|q| {
+            let q_grams = char_trigrams(q);
+            content_tokens.iter().any(|t| {
+                let t_grams = char_trigrams(t);
+                let shared = q_grams.intersection(&t_grams).count();
+                let union = q_grams.len() + t_grams.len() - shared;
+                union > 0 && shared as f64 / union as f64 >= FUZZY_TOKEN_MIN_SIMILARITY
+            })
+        })
+        .count();
+    (exact, fuzzy)
+}
+
+/// Added to the cosine similarity of chat-scoped KNN hits so they outrank
+/// global memories that are only marginally closer.
+#[cfg(feature = "sqlite-vec")]
+const CHAT_LOCAL_SIMILARITY_BOOST: f32 = 0.05;
+
+/// Scales a log-damped popularity bonus (`ln(1 + access_count)`) added to KNN
+/// similarity, so frequently recalled memories win close calls.
+#[cfg(feature = "sqlite-vec")]
+const POPULARITY_SIMILARITY_WEIGHT: f32 = 0.01;
+
+/// Changes whenever a candidate memory is added, edited, pinned, or archived,
+/// so cached recall results never outlive the rows they were computed from.
+#[cfg(feature = "sqlite-vec")]
+fn recall_fingerprint(memories: &[microclaw_storage::db::Memory]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for m in memories {
+        (m.id, &m.updated_at, m.pinned).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Recall tuning taken from [`crate::config::Config`], so call sites don't
+/// thread each knob through positionally.
+#[derive(Clone, Debug)]
+pub(crate) struct RecallOptions {
+    pub token_budget: usize,
+    pub min_similarity: f32,
+    pub knn_candidates: usize,
+    pub extra_stopwords: Vec<String>,
+    pub mmr_lambda: f32,
+    pub track_co_recall: bool,
+    /// Rank by keywords only, even when an embedding provider is configured.
+    pub keyword_only: bool,
+    /// Hard cutoff: only memories created at or after this instant are
+    /// recalled, by both keyword and vector search.
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Wrapper around the recalled block, shared with AGENTS.md memory.
+    pub format: microclaw_storage::memory::MemoryContextFormat,
+}
+
+impl RecallOptions {
+    pub(crate) fn from_config(config: &crate::config::Config) -> Self {
+        Self {
+            token_budget: config.memory_token_budget,
+            min_similarity: config.memory_min_similarity,
+            knn_candidates: config.memory_knn_candidates,
+            extra_stopwords: config.memory_recall_stopwords.clone(),
+            mmr_lambda: config.memory_mmr_lambda,
+            track_co_recall: config.memory_co_recall_tracking,
+            keyword_only: config.memory_recall_keyword_only,
+            since: None,
+            format: config.memory_context_format(),
+        }
+    }
 }
 
 pub(crate) async fn build_db_memory_context(
     db: &std::sync::Arc<Database>,
     embedding: &Option<std::sync::Arc<dyn EmbeddingProvider>>,
+    recall_cache: Option<&RecallCache>,
+    chat_id: i64,
+    query: &str,
+    options: &RecallOptions,
+) -> String {
+    let span = info_span!(
+        "memory_recall",
+        chat_id,
+        method = field::Empty,
+        candidates = field::Empty,
+        selected = field::Empty,
+        duration_ms = field::Empty,
+    );
+    let started = std::time::Instant::now();
+    let out = recall_db_memory_context(db, embedding, recall_cache, chat_id, query, options)
+        .instrument(span.clone())
+        .await;
+    span.record("duration_ms", started.elapsed().as_millis() as u64);
+    out
+}
+
+async fn recall_db_memory_context(
+    db: &std::sync::Arc<Database>,
+    embedding: &Option<std::sync::Arc<dyn EmbeddingProvider>>,
+    recall_cache: Option<&RecallCache>,
     chat_id: i64,
     query: &str,
-    token_budget: usize,
+    options: &RecallOptions,
 ) -> String {
-    let memories = match call_blocking(db.clone(), move |db| {
+    let RecallOptions {
+        token_budget,
+        min_similarity,
+        knn_candidates,
+        ref extra_stopwords,
+        mmr_lambda,
+        track_co_recall,
+        keyword_only,
+        since,
+        format,
+    } = *options;
+    let mut memories = match call_blocking(db.clone(), move |db| {
         db.get_memories_for_context(chat_id, 100)
     })
     .await
@@ -925,12 +1106,32 @@ pub(crate) async fn build_db_memory_context(
         Ok(m) => m,
         Err(_) => return String::new(),
     };
+    if let Some(since) = since {
+        // The context window is the most recently updated rows; keyword
+        // matches created in the window can sit outside it, so add those.
+        memories.retain(|m| m.created_at_utc().is_some_and(|t| t >= since));
+        let query_owned = query.to_string();
+        if let Ok(recent) = call_blocking(db.clone(), move |db| {
+            db.search_memories_since(chat_id, &query_owned, knn_candidates, since)
+        })
+        .await
+        {
+            for m in recent {
+                if (m.confidence >= 0.45 || m.pinned) && !memories.iter().any(|c| c.id == m.id) {
+                    memories.push(m);
+                }
+            }
+        }
+    }
 
     if memories.is_empty() {
         return String::new();
     }
 
     let mut ordered: Vec<&microclaw_storage::db::Memory> = Vec::new();
+    // Ids that actually matched the query, as opposed to filling the budget
+    // by recency; only these count as recalled.
+    let mut matched_ids: std::collections::HashSet<i64> = std::collections::HashSet::new();
     #[cfg(feature = "sqlite-vec")]
     let mut retrieval_method = "keyword";
     #[cfg(not(feature = "sqlite-vec"))]
@@ -938,25 +1139,96 @@ pub(crate) async fn build_db_memory_context(
 
     #[cfg(feature = "sqlite-vec")]
     {
-        if let Some(provider) = embedding {
+        if let Some(provider) = embedding.as_ref().filter(|_| !keyword_only) {
             if !query.trim().is_empty() {
-                if let Ok(query_vec) = provider.embed(query).await {
-                    let knn_result = call_blocking(db.clone(), move |db| {
-                        db.knn_memories(chat_id, &query_vec, 20)
-                    })
-                    .await;
-                    if let Ok(knn_rows) = knn_result {
-                        let by_id: std::collections::HashMap<i64, &microclaw_storage::db::Memory> =
-                            memories.iter().map(|m| (m.id, m)).collect();
-                        for (id, _) in knn_rows {
-                            if let Some(mem) = by_id.get(&id) {
-                                ordered.push(*mem);
+                let fingerprint = recall_fingerprint(&memories);
+                // Cached rows were computed without the window, so skip the
+                // cache while one is set.
+                let recall_cache = recall_cache.filter(|_| since.is_none());
+                let mut knn_result = recall_cache.and_then(|c| c.get(chat_id, query, fingerprint));
+                if knn_result.is_none() {
+                    if let Ok(query_vec) = provider.embed_query(query).await {
+                        if let Ok(rows) = call_blocking(db.clone(), move |db| match since {
+                            Some(since) => {
+                                db.knn_memories_since(chat_id, &query_vec, knn_candidates, since)
                             }
+                            None => db.knn_memories(chat_id, &query_vec, knn_candidates),
+                        })
+                        .await
+                        {
+                            if let Some(cache) = recall_cache {
+                                cache.insert(chat_id, query, fingerprint, rows.clone());
+                            }
+                            knn_result = Some(rows);
                         }
-                        if !ordered.is_empty() {
-                            retrieval_method = "knn";
+                    }
+                }
+                if let Some(knn_rows) = knn_result {
+                    // KNN can surface memories outside the context window
+                    // loaded above; resolve them in a single query.
+                    let missing: Vec<i64> = knn_rows
+                        .iter()
+                        .map(|(id, _)| *id)
+                        .filter(|id| !memories.iter().any(|m| m.id == *id))
+                        .collect();
+                    if !missing.is_empty() {
+                        if let Ok(extra) =
+                            call_blocking(db.clone(), move |db| db.get_memories_by_ids(&missing))
+                                .await
+                        {
+                            memories.extend(
+                                extra
+                                    .into_iter()
+                                    .filter(|m| m.confidence >= 0.45 || m.pinned),
+                            );
                         }
                     }
+                    let by_id: std::collections::HashMap<i64, &microclaw_storage::db::Memory> =
+                        memories.iter().map(|m| (m.id, m)).collect();
+                    let mut hits: Vec<(f32, &microclaw_storage::db::Memory)> = Vec::new();
+                    for (id, similarity) in knn_rows {
+                        if min_similarity > 0.0 && similarity < min_similarity {
+                            continue;
+                        }
+                        if let Some(mem) = by_id.get(&id) {
+                            let boost = if mem.chat_id.is_some() {
+                                CHAT_LOCAL_SIMILARITY_BOOST
+                            } else {
+                                0.0
+                            };
+                            let popularity = POPULARITY_SIMILARITY_WEIGHT
+                                * (mem.access_count.max(0) as f32).ln_1p();
+                            hits.push((similarity + boost + popularity, *mem));
+                        }
+                    }
+                    hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+                    if mmr_lambda < 1.0 && hits.len() > 1 {
+                        let ids: Vec<i64> = hits.iter().map(|(_, m)| m.id).collect();
+                        if let Ok(vectors) = call_blocking(db.clone(), move |db| {
+                            db.get_memory_embeddings(&ids).map(|vectors| {
+                                vectors
+                                    .into_iter()
+                                    .map(Option::unwrap_or_default)
+                                    .collect::<Vec<_>>()
+                            })
+                        })
+                        .await
+                        {
+                            let relevance: Vec<f32> =
+                                hits.iter().map(|(score, _)| *score).collect();
+                            hits = microclaw_storage::vector::mmr_order(
+                                &relevance, &vectors, mmr_lambda,
+                            )
+                            .into_iter()
+                            .map(|i| hits[i])
+                            .collect();
+                        }
+                    }
+                    matched_ids.extend(hits.iter().map(|(_, m)| m.id));
+                    ordered.extend(hits.into_iter().map(|(_, m)| m));
+                    if !ordered.is_empty() {
+                        retrieval_method = "knn";
+                    }
                 }
             }
         }
@@ -964,37 +1236,70 @@ pub(crate) async fn build_db_memory_context(
 
     #[cfg(not(feature = "sqlite-vec"))]
     {
-        let _ = embedding;
+        let _ = (
+            embedding,
+            recall_cache,
+            min_similarity,
+            knn_candidates,
+            mmr_lambda,
+            keyword_only,
+        );
     }
 
     if ordered.is_empty() {
-        // Score by relevance to current query; preserve recency for ties.
-        let query_tokens = tokenize_for_relevance(query);
-        let mut scored: Vec<(usize, usize, &microclaw_storage::db::Memory)> = memories
+        // Score by relevance to current query (exact token hits, then typo
+        // matches); on ties prefer chat-local over global memories, then the
+        // most often recalled, then recency.
+        let query_tokens = strip_stopwords(tokenize_for_relevance(query), extra_stopwords);
+        let mut scored: Vec<((usize, usize), usize, &microclaw_storage::db::Memory)> = memories
             .iter()
             .enumerate()
             .map(|(idx, m)| {
                 (
-                    score_relevance_with_cache(&m.content, &query_tokens),
+                    score_relevance_with_cache(&m.retrieval_text(), &query_tokens),
                     idx,
                     m,
                 )
             })
             .collect();
         if !query.is_empty() {
-            scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            scored.sort_by(|a, b| {
+                b.0.cmp(&a.0)
+                    .then_with(|| b.2.chat_id.is_some().cmp(&a.2.chat_id.is_some()))
+                    .then_with(|| b.2.access_count.cmp(&a.2.access_count))
+                    .then_with(|| a.1.cmp(&b.1))
+            });
         }
+        matched_ids.extend(
+            scored
+                .iter()
+                .filter(|(score, _, _)| *score != (0, 0))
+                .map(|(_, _, m)| m.id),
+        );
         ordered = scored.into_iter().map(|(_, _, m)| m).collect();
     }
 
-    let mut out = String::from("<structured_memories>\n");
+    // Pinned memories always surface first, even when KNN or keyword scoring
+    // would have ranked them low or dropped them.
+    let (mut pinned, rest): (Vec<_>, Vec<_>) = ordered.into_iter().partition(|m| m.pinned);
+    for m in memories.iter().filter(|m| m.pinned) {
+        if !pinned.iter().any(|p| p.id == m.id) {
+            pinned.push(m);
+        }
+    }
+    pinned.extend(rest);
+    ordered = pinned;
+
+    let mut body = String::new();
     let mut used_tokens = 0usize;
     let mut omitted = 0usize;
 
     let budget = token_budget.max(1);
 
     for (idx, m) in ordered.iter().enumerate() {
-        let estimated_tokens = (m.content.len() / 4) + 10;
+        let text = m.display_text();
+        let attachment_len = m.attachment_path.as_ref().map_or(0, |p| p.len());
+        let estimated_tokens = ((text.len() + attachment_len) / 4) + 10;
         if used_tokens + estimated_tokens > budget {
             omitted = ordered.len().saturating_sub(idx);
             break;
@@ -1006,16 +1311,46 @@ pub(crate) async fn build_db_memory_context(
         } else {
             "chat"
         };
-        out.push_str(&format!("[{}] [{}] {}\n", m.category, scope, m.content));
+        match &m.attachment_path {
+            Some(path) => body.push_str(&format!(
+                "[{}] [{}] {} (attachment: {path})\n",
+                m.category, scope, text
+            )),
+            None => body.push_str(&format!("[{}] [{}] {}\n", m.category, scope, text)),
+        }
     }
     if omitted > 0 {
-        out.push_str(&format!("(+{omitted} memories omitted)\n"));
-    }
-    out.push_str("</structured_memories>\n");
+        body.push_str(&format!("(+{omitted} memories omitted)\n"));
+    }
+    let mut out = String::new();
+    format.push_section(
+        &mut out,
+        "structured_memories",
+        "Structured Memories",
+        body.trim_end(),
+    );
     let candidate_count = ordered.len();
     let selected_count = candidate_count.saturating_sub(omitted);
+    let span = tracing::Span::current();
+    span.record("method", retrieval_method);
+    span.record("candidates", candidate_count as u64);
+    span.record("selected", selected_count as u64);
     let retrieval_method_owned = retrieval_method.to_string();
+    let recalled_ids: Vec<i64> = ordered
+        .iter()
+        .take(selected_count)
+        .map(|m| m.id)
+        .filter(|id| matched_ids.contains(id))
+        .collect();
     let _ = call_blocking(db.clone(), move |d| {
+        d.record_memory_access(&recalled_ids)?;
+        // Co-recall links are a ranking hint; losing them must not drop the
+        // injection log below.
+        if track_co_recall {
+            if let Err(e) = d.record_co_recall(&recalled_ids) {
+                warn!("Failed to record co-recall links for chat {chat_id}: {e}");
+            }
+        }
         d.log_memory_injection(
             chat_id,
             &retrieval_method_owned,
@@ -1480,7 +1815,10 @@ async fn compact_messages(
 
 #[cfg(test)]
 mod tests {
-    use super::{build_db_memory_context, process_with_agent, AgentRequestContext};
+    use super::{
+        build_db_memory_context, process_with_agent, score_relevance_with_cache, strip_stopwords,
+        tokenize_for_relevance, AgentRequestContext, RecallOptions,
+    };
     use crate::config::{Config, WorkingDirIsolation};
     use crate::llm::LlmProvider;
     use crate::memory::MemoryManager;
@@ -1494,6 +1832,7 @@ mod tests {
         Message, MessagesResponse, ResponseContentBlock, ToolDefinition,
     };
     use microclaw_storage::db::{Database, StoredMessage};
+    use microclaw_storage::memory::MemoryContextFormat;
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
 
@@ -1590,10 +1929,26 @@ mod tests {
             hooks: Arc::new(crate::hooks::HookManager::from_config(&cfg)),
             llm,
             embedding: None,
+            embedding_queue: None,
+            recall_cache: None,
             tools: ToolRegistry::new(&cfg, channel_registry, db),
         })
     }
 
+    fn recall_options(token_budget: usize) -> RecallOptions {
+        RecallOptions {
+            token_budget,
+            min_similarity: 0.0,
+            knn_candidates: 20,
+            extra_stopwords: Vec::new(),
+            mmr_lambda: 1.0,
+            track_co_recall: false,
+            keyword_only: false,
+            since: None,
+            format: MemoryContextFormat::default(),
+        }
+    }
+
     fn store_user_message(db: &Database, chat_id: i64, text: &str) {
         let msg = StoredMessage {
             id: format!("msg-{}", uuid::Uuid::new_v4()),
@@ -1616,7 +1971,8 @@ mod tests {
         db.insert_memory(Some(100), "short memory three", "EVENT")
             .unwrap();
 
-        let context = build_db_memory_context(&db, &None, 100, "short", 20).await;
+        let context =
+            build_db_memory_context(&db, &None, None, 100, "short", &recall_options(20)).await;
         assert!(context.contains("<structured_memories>"));
         assert!(context.contains("(+"));
         assert!(context.contains("memories omitted"));
@@ -1633,7 +1989,8 @@ mod tests {
         db.insert_memory(Some(100), "user likes coffee", "PROFILE")
             .unwrap();
 
-        let context = build_db_memory_context(&db, &None, 100, "likes", 10_000).await;
+        let context =
+            build_db_memory_context(&db, &None, None, 100, "likes", &recall_options(10_000)).await;
         assert!(context.contains("user likes rust"));
         assert!(context.contains("user likes coffee"));
         assert!(!context.contains("memories omitted"));
@@ -1641,6 +1998,287 @@ mod tests {
         let _ = std::fs::remove_dir_all(dir);
     }
 
+    #[cfg(feature = "sqlite-vec")]
+    struct UnitVectorProvider;
+
+    #[cfg(feature = "sqlite-vec")]
+    #[async_trait::async_trait]
+    impl crate::embedding::EmbeddingProvider for UnitVectorProvider {
+        async fn embed(&self, _text: &str) -> anyhow::Result<Vec<f32>> {
+            Ok(vec![1.0, 0.0])
+        }
+
+        fn model(&self) -> &str {
+            "unit"
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[tokio::test]
+    async fn test_build_db_memory_context_keyword_only_skips_knn() {
+        let (db, dir) = test_db();
+        db.prepare_vector_index(2).unwrap();
+        let id = db
+            .insert_memory(None, "deploys go to fly.io", "KNOWLEDGE")
+            .unwrap();
+        db.upsert_memory_vec(id, &[1.0, 0.0]).unwrap();
+        db.update_memory_embedding_model(id, "unit").unwrap();
+        let embedding: Option<Arc<dyn crate::embedding::EmbeddingProvider>> =
+            Some(Arc::new(UnitVectorProvider));
+
+        for (chat_id, keyword_only, method) in [(100, false, "knn"), (200, true, "keyword")] {
+            let options = RecallOptions {
+                keyword_only,
+                ..recall_options(10_000)
+            };
+            let context =
+                build_db_memory_context(&db, &embedding, None, chat_id, "deploys", &options).await;
+            assert!(context.contains("fly.io"), "{context}");
+            let logs = db
+                .get_memory_injection_logs(Some(chat_id), None, 10, 0)
+                .unwrap();
+            assert_eq!(logs.len(), 1);
+            assert_eq!(logs[0].retrieval_method, method);
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[tokio::test]
+    async fn test_build_db_memory_context_since_filters_knn() {
+        let (db, dir) = test_db();
+        db.prepare_vector_index(2).unwrap();
+        let old = db
+            .insert_memory(None, "old deploy notes", "KNOWLEDGE")
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let since = chrono::Utc::now();
+        let new = db
+            .insert_memory(None, "new rollout plan", "KNOWLEDGE")
+            .unwrap();
+        for id in [old, new] {
+            db.upsert_memory_vec(id, &[1.0, 0.0]).unwrap();
+            db.update_memory_embedding_model(id, "unit").unwrap();
+        }
+        let embedding: Option<Arc<dyn crate::embedding::EmbeddingProvider>> =
+            Some(Arc::new(UnitVectorProvider));
+
+        let options = RecallOptions {
+            since: Some(since),
+            ..recall_options(10_000)
+        };
+        let context =
+            build_db_memory_context(&db, &embedding, None, 100, "semantic query", &options).await;
+        assert!(context.contains("new rollout plan"), "{context}");
+        assert!(!context.contains("old deploy notes"), "{context}");
+        let logs = db
+            .get_memory_injection_logs(Some(100), None, 10, 0)
+            .unwrap();
+        assert_eq!(logs[0].retrieval_method, "knn");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_build_db_memory_context_follows_context_format() {
+        let (db, dir) = test_db();
+        db.insert_memory(Some(100), "user likes rust", "PROFILE")
+            .unwrap();
+
+        let options = RecallOptions {
+            format: MemoryContextFormat::Markdown,
+            ..recall_options(10_000)
+        };
+        let context = build_db_memory_context(&db, &None, None, 100, "rust", &options).await;
+        assert!(
+            context.starts_with("## Structured Memories\n\n"),
+            "{context}"
+        );
+        assert!(context.contains("user likes rust"));
+        assert!(!context.contains("<structured_memories>"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_build_db_memory_context_prefers_chat_local_on_ties() {
+        let (db, dir) = test_db();
+        db.insert_memory(Some(100), "deploy target is staging", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(None, "deploy target is production", "KNOWLEDGE")
+            .unwrap();
+
+        let context = build_db_memory_context(
+            &db,
+            &None,
+            None,
+            100,
+            "deploy target",
+            &recall_options(10_000),
+        )
+        .await;
+        let first_line = context
+            .lines()
+            .find(|line| line.starts_with('['))
+            .unwrap_or("");
+        assert!(first_line.contains("[chat]"), "{context}");
+        assert!(context.contains("deploy target is production"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_build_db_memory_context_ignores_stopwords() {
+        let (db, dir) = test_db();
+        db.insert_memory(Some(100), "Database is Postgres 16", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(
+            Some(100),
+            "What is the plan for the offsite? The venue is in Lisbon",
+            "KNOWLEDGE",
+        )
+        .unwrap();
+
+        let context = build_db_memory_context(
+            &db,
+            &None,
+            None,
+            100,
+            "what is the database",
+            &recall_options(10_000),
+        )
+        .await;
+        let first_line = context
+            .lines()
+            .find(|line| line.starts_with('['))
+            .unwrap_or("");
+        assert!(first_line.contains("Postgres"), "{context}");
+
+        let only_stopwords = strip_stopwords(tokenize_for_relevance("what is the"), &[]);
+        assert_eq!(only_stopwords.len(), 3);
+        let extra = strip_stopwords(
+            tokenize_for_relevance("database please"),
+            &["Please".to_string()],
+        );
+        assert!(extra.contains("database") && !extra.contains("please"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_build_db_memory_context_records_access_and_ranks_popular() {
+        let (db, dir) = test_db();
+        let quiet = db
+            .insert_memory(Some(100), "editor is helix", "PREFERENCE")
+            .unwrap();
+        let popular = db
+            .insert_memory(Some(100), "editor is neovim", "PREFERENCE")
+            .unwrap();
+        db.record_memory_access(&[popular, popular]).unwrap();
+        db.record_memory_access(&[popular]).unwrap();
+
+        let context =
+            build_db_memory_context(&db, &None, None, 100, "editor", &recall_options(10_000)).await;
+        let neovim = context.find("neovim").unwrap();
+        let helix = context.find("helix").unwrap();
+        assert!(neovim < helix, "{context}");
+        assert_eq!(db.get_memory_by_id(quiet).unwrap().unwrap().access_count, 1);
+        assert_eq!(
+            db.get_memory_by_id(popular).unwrap().unwrap().access_count,
+            3
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_build_db_memory_context_records_access_for_matches_only() {
+        let (db, dir) = test_db();
+        let matched = db
+            .insert_memory(Some(100), "editor is helix", "PREFERENCE")
+            .unwrap();
+        let filler = db.insert_memory(Some(100), "likes tea", "PROFILE").unwrap();
+
+        let context =
+            build_db_memory_context(&db, &None, None, 100, "editor", &recall_options(10_000)).await;
+        assert!(context.contains("likes tea"), "{context}");
+        assert_eq!(
+            db.get_memory_by_id(matched).unwrap().unwrap().access_count,
+            1
+        );
+        assert_eq!(
+            db.get_memory_by_id(filler).unwrap().unwrap().access_count,
+            0
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_build_db_memory_context_since_drops_older_memories() {
+        let (db, dir) = test_db();
+        db.insert_memory(Some(100), "standup moved to 10am", "EVENT")
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let since = chrono::Utc::now();
+        db.insert_memory(Some(100), "standup cancelled today", "EVENT")
+            .unwrap();
+
+        let options = RecallOptions {
+            since: Some(since),
+            ..recall_options(10_000)
+        };
+        let context = build_db_memory_context(&db, &None, None, 100, "standup", &options).await;
+        assert!(context.contains("standup cancelled today"), "{context}");
+        assert!(!context.contains("10am"), "{context}");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_build_db_memory_context_links_matches_only() {
+        let (db, dir) = test_db();
+        let helix = db
+            .insert_memory(Some(100), "editor is helix", "PREFERENCE")
+            .unwrap();
+        let neovim = db
+            .insert_memory(Some(100), "editor is neovim", "PREFERENCE")
+            .unwrap();
+        db.insert_memory(Some(100), "likes tea", "PROFILE").unwrap();
+
+        let options = RecallOptions {
+            track_co_recall: true,
+            ..recall_options(10_000)
+        };
+        let context = build_db_memory_context(&db, &None, None, 100, "editor", &options).await;
+        assert!(context.contains("likes tea"), "{context}");
+        assert_eq!(db.related_memories(helix, 10).unwrap(), vec![(neovim, 1)]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_score_relevance_tolerates_typos_below_exact() {
+        let query = tokenize_for_relevance("kubernets cluster");
+        assert_eq!(
+            score_relevance_with_cache("Deploys to a kubernetes cluster", &query),
+            (1, 1)
+        );
+        assert_eq!(
+            score_relevance_with_cache("Runs a kubernets cluster", &query),
+            (2, 0)
+        );
+        assert_eq!(
+            score_relevance_with_cache("Prefers tea over coffee", &query),
+            (0, 0)
+        );
+    }
+
     #[tokio::test]
     async fn test_build_db_memory_context_cjk_relevance() {
         let (db, dir) = test_db();
@@ -1649,7 +2287,9 @@ mod tests {
         db.insert_memory(Some(100), "User prefers Rust and tea", "PROFILE")
             .unwrap();
 
-        let context = build_db_memory_context(&db, &None, 100, "喜欢 咖啡", 10_000).await;
+        let context =
+            build_db_memory_context(&db, &None, None, 100, "喜欢 咖啡", &recall_options(10_000))
+                .await;
         let first_line = context
             .lines()
             .find(|line| line.starts_with('['))
@@ -1721,8 +2361,15 @@ mod tests {
 
             // Restart simulation: new AppState reading the same runtime data.
             let restarted = test_state_with_base_dir(&base_dir);
-            let recalled =
-                build_db_memory_context(&restarted.db, &None, chat_id, "database port", 1500).await;
+            let recalled = build_db_memory_context(
+                &restarted.db,
+                &None,
+                None,
+                chat_id,
+                "database port",
+                &recall_options(1500),
+            )
+            .await;
             assert!(
                 recalled.contains("production database port is 5433"),
                 "expected memory recall after restart, got: {recalled}"
@@ -8,7 +8,7 @@ use crate::codex_auth::{
     codex_auth_file_has_access_token, is_openai_codex_provider, provider_allows_empty_api_key,
 };
 use microclaw_core::error::MicroClawError;
-pub use microclaw_tools::sandbox::{SandboxBackend, SandboxConfig, SandboxMode};
+pub use microclaw_tools::sandbox::{SandboxBackend, SandboxConfig, SandboxMode, WorkingDirCleanup};
 pub use microclaw_tools::types::WorkingDirIsolation;
 
 fn default_telegram_bot_token() -> String {
@@ -32,6 +32,9 @@ fn default_max_tokens() -> u32 {
 fn default_max_tool_iterations() -> usize {
     100
 }
+fn default_tool_result_cache_ttl_secs() -> u64 {
+    60
+}
 fn default_compaction_timeout_secs() -> u64 {
     180
 }
@@ -44,9 +47,33 @@ fn default_max_document_size_mb() -> u64 {
 fn default_memory_token_budget() -> usize {
     1500
 }
+fn default_embedding_distance_metric() -> String {
+    "cosine".into()
+}
+fn default_embedding_ensemble_mode() -> String {
+    "concat".into()
+}
+fn default_memory_context_format() -> String {
+    "xml".into()
+}
+fn default_memory_mmr_lambda() -> f32 {
+    1.0
+}
+fn default_memory_knn_candidates() -> usize {
+    20
+}
+fn default_memory_recall_cache_ttl_secs() -> u64 {
+    30
+}
 fn default_data_dir() -> String {
     "./microclaw.data".into()
 }
+fn default_db_backup_keep() -> usize {
+    7
+}
+fn default_db_busy_timeout_ms() -> u64 {
+    5000
+}
 fn default_working_dir() -> String {
     "./tmp".into()
 }
@@ -153,6 +180,22 @@ pub struct ModelPrice {
     pub output_per_million_usd: f64,
 }
 
+/// A backup or ensemble embedding endpoint. Unset fields are not inherited
+/// from the primary provider, since keys and URLs rarely carry over between
+/// providers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmbeddingFallback {
+    pub provider: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub endpoint_path: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     // --- LLM / API ---
@@ -168,6 +211,15 @@ pub struct Config {
     pub max_tokens: u32,
     #[serde(default = "default_max_tool_iterations")]
     pub max_tool_iterations: usize,
+    /// Tools whose successful results are reused for identical inputs; empty disables the cache.
+    #[serde(default)]
+    pub tool_result_cache_tools: Vec<String>,
+    /// Seconds a cached tool result stays valid.
+    #[serde(default = "default_tool_result_cache_ttl_secs")]
+    pub tool_result_cache_ttl_secs: u64,
+    /// Max concurrent executions per tool name; tools not listed are unlimited.
+    #[serde(default)]
+    pub tool_concurrency_limits: HashMap<String, usize>,
     #[serde(default = "default_compaction_timeout_secs")]
     pub compaction_timeout_secs: u64,
     #[serde(default = "default_max_history_messages")]
@@ -186,6 +238,18 @@ pub struct Config {
     // --- Paths & environment ---
     #[serde(default = "default_data_dir")]
     pub data_dir: String,
+    /// SQLite database file; defaults to `<data_dir>/runtime/microclaw.db`. Set it to keep the database on a different volume.
+    #[serde(default)]
+    pub db_path: Option<String>,
+    /// Hours between online backups of the database into `<data_dir>/runtime/backups`; 0 disables them.
+    #[serde(default)]
+    pub db_backup_interval_hours: u64,
+    /// How many of the newest backups to keep; older ones are deleted after each backup.
+    #[serde(default = "default_db_backup_keep")]
+    pub db_backup_keep: usize,
+    /// Milliseconds a database call waits for a lock held by another connection before failing with "database is locked"; 0 fails immediately.
+    #[serde(default = "default_db_busy_timeout_ms")]
+    pub db_busy_timeout_ms: u64,
     #[serde(default = "default_working_dir")]
     pub working_dir: String,
     #[serde(default = "default_working_dir_isolation")]
@@ -230,10 +294,70 @@ pub struct Config {
     pub embedding_api_key: Option<String>,
     #[serde(default)]
     pub embedding_base_url: Option<String>,
+    /// Path (or full URL) template for the OpenAI-compatible embeddings endpoint;
+    /// `{model}` is replaced with the embedding model. Defaults to `/embeddings`.
+    #[serde(default)]
+    pub embedding_endpoint_path: Option<String>,
+    /// JSON file of precomputed vectors for `embedding_provider: precomputed`: an object mapping the SHA-256 hex of each text to its vector.
+    #[serde(default)]
+    pub embedding_precomputed_path: Option<String>,
     #[serde(default)]
     pub embedding_model: Option<String>,
     #[serde(default)]
     pub embedding_dim: Option<usize>,
+    /// Distance metric for the sqlite-vec index: `cosine` or `l2` (Euclidean). Changing it rebuilds the index.
+    /// Similarity thresholds elsewhere (`memory_min_similarity`, reflector dedup) are tuned for cosine.
+    #[serde(default = "default_embedding_distance_metric")]
+    pub embedding_distance_metric: String,
+    /// Prepended to recall queries before embedding, for instruction-tuned models (e.g. "query: ").
+    #[serde(default)]
+    pub embedding_query_prefix: Option<String>,
+    /// Prepended to memory content before embedding (e.g. "passage: ").
+    #[serde(default)]
+    pub embedding_document_prefix: Option<String>,
+    /// Providers tried in order when the primary embedding provider fails; must serve the same model.
+    #[serde(default)]
+    pub embedding_fallbacks: Vec<EmbeddingFallback>,
+    /// Extra providers embedded alongside the primary and combined into one vector, for multi-model retrieval.
+    #[serde(default)]
+    pub embedding_ensemble: Vec<EmbeddingFallback>,
+    /// How ensemble vectors are combined: "concat" or "average".
+    #[serde(default = "default_embedding_ensemble_mode")]
+    pub embedding_ensemble_mode: String,
+    /// Requests per minute allowed per embedding provider; calls are paced to stay under it. 0 means unlimited.
+    #[serde(default)]
+    pub embedding_requests_per_minute: u32,
+    /// Estimated input tokens per minute allowed per embedding provider; 0 means unlimited.
+    #[serde(default)]
+    pub embedding_tokens_per_minute: u32,
+    /// Minimum similarity (0.0-1.0) for KNN memory hits; 0 keeps every hit.
+    /// Tuned for the cosine metric: under `l2` similarity is `1 / (1 + distance)`.
+    #[serde(default)]
+    pub memory_min_similarity: f32,
+    /// Nearest neighbours fetched from the vector index per recall before filtering.
+    #[serde(default = "default_memory_knn_candidates")]
+    pub memory_knn_candidates: usize,
+    /// Relevance/diversity trade-off (0.0-1.0) for reranking KNN hits with MMR; 1.0 keeps pure relevance order.
+    #[serde(default = "default_memory_mmr_lambda")]
+    pub memory_mmr_lambda: f32,
+    /// Skip semantic recall and rank memories by keyword relevance only, even when an embedding provider is configured.
+    #[serde(default)]
+    pub memory_recall_keyword_only: bool,
+    /// Count which memories are recalled together (`memory_links`), so related memories can be looked up.
+    #[serde(default)]
+    pub memory_co_recall_tracking: bool,
+    /// Extra words ignored in keyword memory recall, on top of the built-in English stopwords.
+    #[serde(default)]
+    pub memory_recall_stopwords: Vec<String>,
+    /// Embed explicitly stored memories on a background worker instead of inline.
+    #[serde(default)]
+    pub embedding_async: bool,
+    /// Number of recent recall results to cache per process; 0 disables the cache.
+    #[serde(default)]
+    pub memory_recall_cache_size: usize,
+    /// Seconds a cached recall result stays valid.
+    #[serde(default = "default_memory_recall_cache_ttl_secs")]
+    pub memory_recall_cache_ttl_secs: u64,
     #[serde(default)]
     pub openai_api_key: Option<String>,
 
@@ -252,6 +376,18 @@ pub struct Config {
     /// If not set, looks for SOUL.md in data_dir root, then current directory.
     #[serde(default = "default_soul_path")]
     pub soul_path: Option<String>,
+    /// Read-only directories layered under `<data_dir>/runtime/groups` for AGENTS.md memory.
+    #[serde(default)]
+    pub memory_base_dirs: Vec<String>,
+    /// Cache AGENTS.md contents in memory, re-reading a file only when its mtime or size changes.
+    #[serde(default)]
+    pub memory_file_cache: bool,
+    /// Cap on AGENTS.md characters injected into the prompt, keeping the most recent sections; 0 means no limit.
+    #[serde(default)]
+    pub memory_file_max_chars: usize,
+    /// Wrapper around AGENTS.md and structured memories in the prompt: "xml", "markdown" or "plain".
+    #[serde(default = "default_memory_context_format")]
+    pub memory_context_format: String,
 
     // --- ClawHub ---
     #[serde(flatten)]
@@ -285,11 +421,18 @@ impl Config {
             llm_base_url: None,
             max_tokens: 8192,
             max_tool_iterations: 100,
+            tool_result_cache_tools: Vec::new(),
+            tool_result_cache_ttl_secs: 60,
+            tool_concurrency_limits: HashMap::new(),
             compaction_timeout_secs: 180,
             max_history_messages: 50,
             max_document_size_mb: 100,
             memory_token_budget: 1500,
             data_dir: "./microclaw.data".into(),
+            db_path: None,
+            db_backup_interval_hours: 0,
+            db_backup_keep: 7,
+            db_busy_timeout_ms: 5000,
             working_dir: "./tmp".into(),
             working_dir_isolation: WorkingDirIsolation::Chat,
             sandbox: SandboxConfig::default(),
@@ -316,11 +459,34 @@ impl Config {
             embedding_provider: None,
             embedding_api_key: None,
             embedding_base_url: None,
+            embedding_endpoint_path: None,
+            embedding_precomputed_path: None,
             embedding_model: None,
             embedding_dim: None,
+            embedding_distance_metric: "cosine".into(),
+            embedding_query_prefix: None,
+            embedding_document_prefix: None,
+            embedding_fallbacks: Vec::new(),
+            embedding_ensemble: Vec::new(),
+            embedding_ensemble_mode: "concat".into(),
+            embedding_requests_per_minute: 0,
+            embedding_tokens_per_minute: 0,
+            memory_min_similarity: 0.0,
+            memory_knn_candidates: 20,
+            memory_mmr_lambda: 1.0,
+            memory_recall_keyword_only: false,
+            memory_co_recall_tracking: false,
+            memory_recall_stopwords: Vec::new(),
+            embedding_async: false,
+            memory_recall_cache_size: 0,
+            memory_recall_cache_ttl_secs: 30,
             reflector_enabled: true,
             reflector_interval_mins: 15,
             soul_path: None,
+            memory_base_dirs: Vec::new(),
+            memory_file_cache: false,
+            memory_file_max_chars: 0,
+            memory_context_format: "xml".into(),
             clawhub: ClawHubConfig::default(),
             channels: HashMap::new(),
         }
@@ -339,6 +505,20 @@ impl Config {
             .to_string()
     }
 
+    /// SQLite database file: `db_path` when set, else `microclaw.db` in the
+    /// runtime data dir.
+    pub fn database_path(&self) -> PathBuf {
+        match &self.db_path {
+            Some(path) => PathBuf::from(path),
+            None => PathBuf::from(self.runtime_data_dir()).join("microclaw.db"),
+        }
+    }
+
+    /// Directory for scheduled database backups.
+    pub fn database_backup_dir(&self) -> PathBuf {
+        PathBuf::from(self.runtime_data_dir()).join("backups")
+    }
+
     /// Skills directory under data root.
     /// Handles the case where data_dir was overridden to the runtime subdirectory
     /// (e.g. `microclaw.data/runtime`) — skills always live under the true root.
@@ -389,6 +569,27 @@ impl Config {
         }
     }
 
+    /// Distance metric for the vector index; `post_deserialize` has already
+    /// rejected unknown names.
+    pub fn vector_metric(&self) -> microclaw_storage::db::VectorMetric {
+        microclaw_storage::db::VectorMetric::parse(&self.embedding_distance_metric)
+            .unwrap_or_default()
+    }
+
+    /// How ensemble member vectors are combined; `post_deserialize` has
+    /// already rejected unknown names.
+    pub fn embedding_ensemble_mode(&self) -> crate::embedding::EnsembleMode {
+        crate::embedding::EnsembleMode::parse(&self.embedding_ensemble_mode)
+            .unwrap_or(crate::embedding::EnsembleMode::Concat)
+    }
+
+    /// Wrapper for AGENTS.md memory in the prompt; `post_deserialize` has
+    /// already rejected unknown names.
+    pub fn memory_context_format(&self) -> microclaw_storage::memory::MemoryContextFormat {
+        microclaw_storage::memory::MemoryContextFormat::parse(&self.memory_context_format)
+            .unwrap_or_default()
+    }
+
     fn explicit_channel_enabled(&self, channel: &str) -> Option<bool> {
         self.channels
             .get(channel)
@@ -452,6 +653,10 @@ impl Config {
         if self.working_dir.trim().is_empty() {
             self.working_dir = default_working_dir();
         }
+        if let Some(v) = &self.db_path {
+            let p = v.trim().to_string();
+            self.db_path = if p.is_empty() { None } else { Some(p) };
+        }
         self.sandbox.image = self.sandbox.image.trim().to_string();
         if self.sandbox.image.is_empty() {
             self.sandbox.image = default_sandbox_image();
@@ -460,6 +665,16 @@ impl Config {
         if self.sandbox.container_prefix.is_empty() {
             self.sandbox.container_prefix = default_sandbox_container_prefix();
         }
+        // A shared dir is in use by every chat at once; emptying it after one
+        // chat's command would delete files other chats are working on.
+        if self.sandbox.working_dir_cleanup == WorkingDirCleanup::AfterEachRun
+            && self.working_dir_isolation == WorkingDirIsolation::Shared
+        {
+            return Err(MicroClawError::Config(
+                "sandbox.working_dir_cleanup: after_each_run requires working_dir_isolation: chat"
+                    .into(),
+            ));
+        }
         if self.web_host.trim().is_empty() {
             self.web_host = default_web_host();
         }
@@ -491,6 +706,33 @@ impl Config {
                 self.embedding_dim = None;
             }
         }
+        match microclaw_storage::db::VectorMetric::parse(&self.embedding_distance_metric) {
+            Some(metric) => self.embedding_distance_metric = metric.as_str().to_string(),
+            None => {
+                return Err(MicroClawError::Config(format!(
+                    "embedding_distance_metric must be 'cosine' or 'l2', got '{}'",
+                    self.embedding_distance_metric
+                )))
+            }
+        }
+        match crate::embedding::EnsembleMode::parse(&self.embedding_ensemble_mode) {
+            Some(mode) => self.embedding_ensemble_mode = mode.as_str().to_string(),
+            None => {
+                return Err(MicroClawError::Config(format!(
+                    "embedding_ensemble_mode must be 'concat' or 'average', got '{}'",
+                    self.embedding_ensemble_mode
+                )))
+            }
+        }
+        match microclaw_storage::memory::MemoryContextFormat::parse(&self.memory_context_format) {
+            Some(format) => self.memory_context_format = format.as_str().to_string(),
+            None => {
+                return Err(MicroClawError::Config(format!(
+                    "memory_context_format must be 'xml', 'markdown' or 'plain', got '{}'",
+                    self.memory_context_format
+                )))
+            }
+        }
         let web_enabled_effective = self
             .explicit_channel_enabled("web")
             .unwrap_or(self.web_enabled);
@@ -524,6 +766,33 @@ impl Config {
         if self.memory_token_budget == 0 {
             self.memory_token_budget = default_memory_token_budget();
         }
+        if self.memory_knn_candidates == 0 {
+            self.memory_knn_candidates = default_memory_knn_candidates();
+        }
+        if self.memory_recall_cache_ttl_secs == 0 {
+            self.memory_recall_cache_ttl_secs = default_memory_recall_cache_ttl_secs();
+        }
+        if self.memory_knn_candidates > microclaw_storage::db::MAX_KNN_CANDIDATES {
+            return Err(MicroClawError::Config(format!(
+                "memory_knn_candidates must be at most {}",
+                microclaw_storage::db::MAX_KNN_CANDIDATES
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.memory_min_similarity) {
+            return Err(MicroClawError::Config(
+                "memory_min_similarity must be between 0.0 and 1.0".into(),
+            ));
+        }
+        if self.db_backup_interval_hours > 0 && self.db_backup_keep == 0 {
+            return Err(MicroClawError::Config(
+                "db_backup_keep must be at least 1 when db_backup_interval_hours is set".into(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.memory_mmr_lambda) {
+            return Err(MicroClawError::Config(
+                "memory_mmr_lambda must be between 0.0 and 1.0".into(),
+            ));
+        }
         for price in &mut self.model_prices {
             price.model = price.model.trim().to_string();
             if price.model.is_empty() {
@@ -783,6 +1052,21 @@ mod tests {
         assert_eq!(config.working_dir, "./tmp");
     }
 
+    #[test]
+    fn test_database_path_defaults_under_runtime_dir() {
+        let mut config = Config::test_defaults();
+        config.data_dir = "/srv/microclaw".into();
+        assert_eq!(
+            config.database_path(),
+            PathBuf::from("/srv/microclaw/runtime/microclaw.db")
+        );
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\ndb_path: /mnt/ssd/brain.db\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.database_path(), PathBuf::from("/mnt/ssd/brain.db"));
+    }
+
     #[test]
     fn test_post_deserialize_zero_memory_budget_uses_default() {
         let yaml =
@@ -792,6 +1076,76 @@ mod tests {
         assert_eq!(config.memory_token_budget, 1500);
     }
 
+    #[test]
+    fn test_post_deserialize_memory_knn_candidates_above_max_rejected() {
+        let yaml =
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nmemory_knn_candidates: 100000\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("memory_knn_candidates"));
+    }
+
+    #[test]
+    fn test_post_deserialize_embedding_distance_metric() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nembedding_distance_metric: Euclidean\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.embedding_distance_metric, "l2");
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nembedding_distance_metric: dot\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("embedding_distance_metric"));
+    }
+
+    #[test]
+    fn test_post_deserialize_embedding_ensemble_mode() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nembedding_ensemble_mode: Average\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.embedding_ensemble_mode, "average");
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nembedding_ensemble_mode: sum\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("embedding_ensemble_mode"));
+    }
+
+    #[test]
+    fn test_post_deserialize_memory_context_format() {
+        let yaml =
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nmemory_context_format: MD\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+        assert_eq!(config.memory_context_format, "markdown");
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nmemory_context_format: html\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("memory_context_format"));
+    }
+
+    #[test]
+    fn test_post_deserialize_memory_min_similarity_out_of_range_rejected() {
+        let yaml =
+            "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nmemory_min_similarity: 1.5\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("memory_min_similarity"));
+    }
+
+    #[test]
+    fn test_post_deserialize_rejects_after_each_run_cleanup_with_shared_dir() {
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nworking_dir_isolation: shared\nsandbox:\n  working_dir_cleanup: after_each_run\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        let err = config.post_deserialize().unwrap_err();
+        assert!(err.to_string().contains("after_each_run"));
+
+        let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\nworking_dir_isolation: chat\nsandbox:\n  working_dir_cleanup: after_each_run\n";
+        let mut config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.post_deserialize().unwrap();
+    }
+
     #[test]
     fn test_config_working_dir_isolation_defaults_to_chat() {
         let yaml = "telegram_bot_token: tok\nbot_username: bot\napi_key: key\n";
@@ -1,26 +1,129 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use futures_util::{StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{field, info_span, warn, Instrument};
 
 use crate::config::Config;
+use microclaw_storage::db::{call_blocking, Database};
 
 #[async_trait]
 pub trait EmbeddingProvider: Send + Sync {
     async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+    /// Embed several texts, preserving input order. Providers without a batch
+    /// endpoint get a sequential loop over `embed`.
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut out = Vec::with_capacity(texts.len());
+        for text in texts {
+            out.push(self.embed(text).await?);
+        }
+        Ok(out)
+    }
+    /// Like `embed_batch`, but with one result per input so a single bad item
+    /// doesn't discard the rest. If the batch call fails as a whole, each
+    /// input is retried on its own through `embed`.
+    async fn embed_batch_partial(&self, texts: &[String]) -> Vec<Result<Vec<f32>>> {
+        match self.embed_batch(texts).await {
+            Ok(vectors) if vectors.len() == texts.len() => vectors.into_iter().map(Ok).collect(),
+            _ => {
+                let mut out = Vec::with_capacity(texts.len());
+                for text in texts {
+                    out.push(self.embed(text).await);
+                }
+                out
+            }
+        }
+    }
     fn model(&self) -> &str;
+    /// Vector size, or 0 while it is still unknown (learned from the first
+    /// successful response).
     fn dimension(&self) -> usize;
+    /// True while calls are being short-circuited after repeated failures.
+    fn is_degraded(&self) -> bool {
+        false
+    }
+    /// True when `embed_batch` sends the whole batch in one request rather
+    /// than one request per text.
+    fn batches_natively(&self) -> bool {
+        false
+    }
+    /// Prepended to recall queries for instruction-tuned models (e.g. `"query: "`).
+    fn query_prefix(&self) -> &str {
+        ""
+    }
+    /// Prepended to stored memory text (e.g. `"passage: "`).
+    fn document_prefix(&self) -> &str {
+        ""
+    }
+    /// Embed a recall query. Use this instead of `embed` for search text.
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(&format!("{}{text}", self.query_prefix())).await
+    }
+    /// Embed memory content for storage in the vector index.
+    async fn embed_document(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(&format!("{}{text}", self.document_prefix()))
+            .await
+    }
+    /// Batch form of `embed_document`.
+    async fn embed_documents(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let prefix = self.document_prefix();
+        if prefix.is_empty() {
+            return self.embed_batch(texts).await;
+        }
+        let prefixed: Vec<String> = texts.iter().map(|t| format!("{prefix}{t}")).collect();
+        self.embed_batch(&prefixed).await
+    }
+    /// Per-item form of `embed_documents`; see `embed_batch_partial`.
+    async fn embed_documents_partial(&self, texts: &[String]) -> Vec<Result<Vec<f32>>> {
+        let prefix = self.document_prefix();
+        if prefix.is_empty() {
+            return self.embed_batch_partial(texts).await;
+        }
+        let prefixed: Vec<String> = texts.iter().map(|t| format!("{prefix}{t}")).collect();
+        self.embed_batch_partial(&prefixed).await
+    }
 }
 
+/// How `OpenAIEmbeddingProvider` presents its API key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    /// `Authorization: Bearer <key>` (OpenAI and most compatible gateways).
+    Bearer,
+    /// `api-key: <key>` header, as used by Azure OpenAI.
+    ApiKeyHeader,
+}
+
+/// Azure routes by deployment name, which the `embedding_model` field holds.
+#[cfg(feature = "sqlite-vec")]
+const AZURE_EMBEDDINGS_PATH: &str = "/openai/deployments/{model}/embeddings?api-version=2024-02-01";
+
 pub struct OpenAIEmbeddingProvider {
     client: reqwest::Client,
     base_url: String,
     api_key: String,
     model: String,
-    dim: usize,
+    /// Configured or known up front; otherwise set by the first response
+    /// and enforced on every later one.
+    dim: OnceLock<usize>,
+    auth: AuthMode,
+    /// Overrides the default `/embeddings` path for gateways with their own
+    /// layout. `{model}` is substituted; a full URL replaces `base_url`.
+    endpoint_path: Option<String>,
+    /// Sent as `dimensions` so Matryoshka models (text-embedding-3) return
+    /// shortened vectors; only set for models that accept the parameter.
+    dimensions: Option<usize>,
 }
 
+/// In-flight requests per Ollama batch.
+const OLLAMA_BATCH_CONCURRENCY: usize = 4;
+
 pub struct OllamaEmbeddingProvider {
     client: reqwest::Client,
     base_url: String,
@@ -28,10 +131,23 @@ pub struct OllamaEmbeddingProvider {
     dim: usize,
 }
 
+pub struct GeminiEmbeddingProvider {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+    dim: usize,
+    /// Sent as `outputDimensionality` when the dimension was configured
+    /// explicitly; otherwise the model's native size is used.
+    output_dim: Option<usize>,
+}
+
 #[derive(Debug, Serialize)]
 struct OpenAIEmbeddingRequest<'a> {
     model: &'a str,
     input: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -55,35 +171,164 @@ struct OllamaEmbeddingResponse {
     embedding: Vec<f32>,
 }
 
+#[derive(Debug, Serialize)]
+struct GeminiPart<'a> {
+    text: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct GeminiContent<'a> {
+    parts: [GeminiPart<'a>; 1],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiEmbedContentRequest<'a> {
+    model: String,
+    content: GeminiContent<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dimensionality: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct GeminiBatchEmbedRequest<'a> {
+    requests: Vec<GeminiEmbedContentRequest<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiEmbedding {
+    values: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiEmbedContentResponse {
+    embedding: GeminiEmbedding,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiBatchEmbedResponse {
+    #[serde(default)]
+    embeddings: Vec<GeminiEmbedding>,
+}
+
+/// `None` means the size is learned from the first response: gateways and
+/// Azure deployments can serve any model under any name.
 #[cfg(feature = "sqlite-vec")]
-fn infer_default_dim(provider: &str, model: &str) -> usize {
+fn infer_default_dim(provider: &str, model: &str) -> Option<usize> {
     match provider {
-        "openai" => {
-            if model.contains("3-large") {
-                3072
-            } else {
-                1536
-            }
+        "openai" | "azure" if model.contains("3-large") => Some(3072),
+        "openai" | "azure" if model.contains("3-small") || model.contains("ada-002") => Some(1536),
+        "openai" | "azure" => None,
+        "ollama" => Some(1024),
+        "gemini" => Some(768),
+        _ => Some(1536),
+    }
+}
+
+/// Build the `memories_vec` index for the provider's vector size, or confirm
+/// it already matches. A lazily sized provider may not answer at startup, so
+/// this runs again before each embedding backfill; it changes nothing once
+/// the index matches. Returns false while the size is still unknown.
+#[cfg(feature = "sqlite-vec")]
+pub async fn prepare_vector_index(
+    db: &Database,
+    provider: Option<&dyn EmbeddingProvider>,
+    config: &Config,
+) -> bool {
+    let dim = match provider {
+        Some(p) => detect_dimension(p).await,
+        None => Some(config.embedding_dim.unwrap_or(1536)),
+    };
+    let Some(dim) = dim else {
+        warn!("Embedding dimension unknown until the provider responds; the vector index is prepared once it does");
+        return false;
+    };
+    match db.prepare_vector_index_with_metric(dim, config.vector_metric()) {
+        Ok(Some(change)) => {
+            warn!(
+                "Vector index settings changed (dimension {} -> {}, metric {} -> {}): semantic recall is rebuilt from scratch and {} stored embeddings were cleared. \
+                 Memories fall back to keyword recall until the reflector backfills them; run `microclaw reembed` to do it now.",
+                change.previous,
+                change.current,
+                change.previous_metric.as_str(),
+                change.current_metric.as_str(),
+                change.cleared_embeddings
+            );
+            true
+        }
+        Ok(None) => true,
+        Err(e) => {
+            warn!("Failed to initialize sqlite-vec index: {e}");
+            false
         }
-        "ollama" => 1024,
-        _ => 1536,
     }
 }
 
-#[async_trait]
-impl EmbeddingProvider for OpenAIEmbeddingProvider {
-    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
-        let url = format!("{}/embeddings", self.base_url.trim_end_matches('/'));
-        let response = self
-            .client
-            .post(url)
-            .bearer_auth(&self.api_key)
-            .json(&OpenAIEmbeddingRequest {
-                model: &self.model,
-                input: text,
-            })
-            .send()
-            .await?;
+/// Vector size of `provider`, embedding a probe text first if the size is
+/// only learned from responses. `None` when that probe fails.
+#[cfg(feature = "sqlite-vec")]
+pub async fn detect_dimension(provider: &dyn EmbeddingProvider) -> Option<usize> {
+    if provider.dimension() == 0 {
+        if let Err(e) = provider.embed("dimension probe").await {
+            warn!("Embedding dimension probe failed: {e}");
+            return None;
+        }
+    }
+    Some(provider.dimension()).filter(|&dim| dim > 0)
+}
+
+/// Run one provider HTTP call inside its own span so provider latency can be
+/// told apart from DB time in memory recall/store spans.
+async fn traced_request<F>(provider: &'static str, model: &str, request: F) -> Result<Vec<f32>>
+where
+    F: Future<Output = Result<Vec<f32>>>,
+{
+    let span = info_span!(
+        "embedding_request",
+        provider,
+        model,
+        duration_ms = field::Empty,
+        ok = field::Empty,
+    );
+    let started = Instant::now();
+    let result = request.instrument(span.clone()).await;
+    span.record("duration_ms", started.elapsed().as_millis() as u64);
+    span.record("ok", result.is_ok());
+    result
+}
+
+impl OpenAIEmbeddingProvider {
+    fn embeddings_url(&self) -> String {
+        let path = match self.endpoint_path.as_deref().map(str::trim) {
+            Some(template) if !template.is_empty() => template.replace("{model}", &self.model),
+            _ => return format!("{}/embeddings", self.base_url.trim_end_matches('/')),
+        };
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return path;
+        }
+        format!(
+            "{}/{}",
+            self.base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    fn request_body<'a>(&'a self, text: &'a str) -> OpenAIEmbeddingRequest<'a> {
+        OpenAIEmbeddingRequest {
+            model: &self.model,
+            input: text,
+            dimensions: self.dimensions,
+        }
+    }
+
+    async fn request(&self, text: &str) -> Result<Vec<f32>> {
+        let url = self.embeddings_url();
+        let request = self.client.post(url);
+        let request = match self.auth {
+            AuthMode::Bearer => request.bearer_auth(&self.api_key),
+            AuthMode::ApiKeyHeader => request.header("api-key", &self.api_key),
+        };
+        let response = request.json(&self.request_body(text)).send().await?;
 
         if !response.status().is_success() {
             let body = response.text().await.unwrap_or_default();
@@ -97,21 +342,50 @@ impl EmbeddingProvider for OpenAIEmbeddingProvider {
             .next()
             .ok_or_else(|| anyhow!("empty embedding response"))?
             .embedding;
+        if let Some(expected) = self.dimensions {
+            if embedding.len() != expected {
+                return Err(anyhow!(
+                    "embedding has {} dimensions, requested {expected}",
+                    embedding.len()
+                ));
+            }
+        }
+        self.check_dimension(&embedding)?;
         Ok(embedding)
     }
 
+    fn check_dimension(&self, embedding: &[f32]) -> Result<()> {
+        if embedding.is_empty() {
+            return Err(anyhow!("empty embedding vector"));
+        }
+        let expected = *self.dim.get_or_init(|| embedding.len());
+        if embedding.len() != expected {
+            return Err(anyhow!(
+                "embedding has {} dimensions, expected {expected}",
+                embedding.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAIEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        traced_request("openai", &self.model, self.request(text)).await
+    }
+
     fn model(&self) -> &str {
         &self.model
     }
 
     fn dimension(&self) -> usize {
-        self.dim
+        self.dim.get().copied().unwrap_or(0)
     }
 }
 
-#[async_trait]
-impl EmbeddingProvider for OllamaEmbeddingProvider {
-    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+impl OllamaEmbeddingProvider {
+    async fn request(&self, text: &str) -> Result<Vec<f32>> {
         let url = format!("{}/api/embeddings", self.base_url.trim_end_matches('/'));
         let response = self
             .client
@@ -131,6 +405,32 @@ impl EmbeddingProvider for OllamaEmbeddingProvider {
         let body: OllamaEmbeddingResponse = response.json().await?;
         Ok(body.embedding)
     }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        traced_request("ollama", &self.model, self.request(text)).await
+    }
+
+    /// Ollama's `/api/embeddings` takes one prompt per call, so run a few
+    /// requests at a time instead of awaiting each in turn. The cap keeps a
+    /// large backfill batch from flooding a local server.
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let requests: Vec<_> = texts.iter().map(|text| self.embed(text)).collect();
+        futures_util::stream::iter(requests)
+            .buffered(OLLAMA_BATCH_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    async fn embed_batch_partial(&self, texts: &[String]) -> Vec<Result<Vec<f32>>> {
+        let requests: Vec<_> = texts.iter().map(|text| self.embed(text)).collect();
+        futures_util::stream::iter(requests)
+            .buffered(OLLAMA_BATCH_CONCURRENCY)
+            .collect()
+            .await
+    }
 
     fn model(&self) -> &str {
         &self.model
@@ -141,104 +441,1972 @@ impl EmbeddingProvider for OllamaEmbeddingProvider {
     }
 }
 
-pub fn create_provider(config: &Config) -> Option<Arc<dyn EmbeddingProvider>> {
-    #[cfg(not(feature = "sqlite-vec"))]
-    {
-        let _ = config;
-        None
+impl GeminiEmbeddingProvider {
+    fn model_path(&self) -> String {
+        format!("models/{}", self.model.trim_start_matches("models/"))
     }
 
-    #[cfg(feature = "sqlite-vec")]
-    {
-        let provider = config
-            .embedding_provider
-            .as_deref()
-            .unwrap_or("")
-            .trim()
-            .to_lowercase();
-        if provider.is_empty() {
-            return None;
+    fn content_request<'a>(&self, text: &'a str) -> GeminiEmbedContentRequest<'a> {
+        GeminiEmbedContentRequest {
+            model: self.model_path(),
+            content: GeminiContent {
+                parts: [GeminiPart { text }],
+            },
+            output_dimensionality: self.output_dim,
         }
+    }
 
-        let model = config
-            .embedding_model
-            .clone()
-            .unwrap_or_else(|| match provider.as_str() {
-                "openai" => "text-embedding-3-small".to_string(),
-                "ollama" => "nomic-embed-text".to_string(),
-                _ => "text-embedding-3-small".to_string(),
-            });
-        let dim = config
-            .embedding_dim
-            .unwrap_or_else(|| infer_default_dim(&provider, &model));
-        let client = reqwest::Client::new();
-
-        match provider.as_str() {
-            "openai" => {
-                let api_key = config.embedding_api_key.clone().unwrap_or_default();
-                if api_key.trim().is_empty() {
-                    return None;
-                }
-                let base_url = config
-                    .embedding_base_url
-                    .clone()
-                    .unwrap_or_else(|| "https://api.openai.com/v1".to_string());
-                Some(Arc::new(OpenAIEmbeddingProvider {
-                    client,
-                    base_url,
-                    api_key,
-                    model,
-                    dim,
-                }))
-            }
-            "ollama" => {
-                let base_url = config
-                    .embedding_base_url
-                    .clone()
-                    .unwrap_or_else(|| "http://127.0.0.1:11434".to_string());
-                Some(Arc::new(OllamaEmbeddingProvider {
-                    client,
-                    base_url,
-                    model,
-                    dim,
-                }))
+    /// The Generative Language API takes the key in an `x-goog-api-key`
+    /// header. Not the `?key=` query form: reqwest errors print the request
+    /// URL, so a connect failure would log the key.
+    async fn post<T: Serialize + ?Sized>(
+        &self,
+        method: &str,
+        body: &T,
+    ) -> Result<reqwest::Response> {
+        let url = format!(
+            "{}/{}:{}",
+            self.base_url.trim_end_matches('/'),
+            self.model_path(),
+            method
+        );
+        let response = self
+            .client
+            .post(url)
+            .header("x-goog-api-key", &self.api_key)
+            .json(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("embedding request failed: {}", body));
+        }
+        Ok(response)
+    }
+
+    async fn request(&self, text: &str) -> Result<Vec<f32>> {
+        let response = self
+            .post("embedContent", &self.content_request(text))
+            .await?;
+        let body: GeminiEmbedContentResponse = response.json().await?;
+        Ok(body.embedding.values)
+    }
+
+    async fn request_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let request = GeminiBatchEmbedRequest {
+            requests: texts.iter().map(|t| self.content_request(t)).collect(),
+        };
+        let response = self.post("batchEmbedContents", &request).await?;
+        let body: GeminiBatchEmbedResponse = response.json().await?;
+        if body.embeddings.len() != texts.len() {
+            return Err(anyhow!(
+                "embedding batch returned {} vectors for {} inputs",
+                body.embeddings.len(),
+                texts.len()
+            ));
+        }
+        Ok(body.embeddings.into_iter().map(|e| e.values).collect())
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for GeminiEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        traced_request("gemini", &self.model, self.request(text)).await
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let span = info_span!(
+            "embedding_request",
+            provider = "gemini",
+            model = %self.model,
+            batch = texts.len(),
+            duration_ms = field::Empty,
+            ok = field::Empty,
+        );
+        let started = Instant::now();
+        let result = self.request_batch(texts).instrument(span.clone()).await;
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        span.record("ok", result.is_ok());
+        result
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+
+    fn batches_natively(&self) -> bool {
+        true
+    }
+}
+
+/// Key under which a text's vector is looked up in a precomputed file:
+/// lowercase SHA-256 hex of the exact text embedded, prefixes included.
+pub fn content_hash(text: &str) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+/// Serves vectors computed offline from a JSON file mapping
+/// [`content_hash`] to vector, and never touches the network. Text without
+/// an entry (typically recall queries) is an error, so recall for it falls
+/// back to keywords unless an `embedding_fallbacks` provider can embed it.
+#[cfg(feature = "sqlite-vec")]
+pub struct PrecomputedEmbeddingProvider {
+    model: String,
+    dim: usize,
+    vectors: std::collections::HashMap<String, Vec<f32>>,
+}
+
+#[cfg(feature = "sqlite-vec")]
+impl PrecomputedEmbeddingProvider {
+    /// Load and validate the file: it must hold at least one vector and all
+    /// vectors must have the same length.
+    pub fn load(path: &std::path::Path, model: String) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read {}: {e}", path.display()))?;
+        let vectors: std::collections::HashMap<String, Vec<f32>> = serde_json::from_str(&raw)
+            .map_err(|e| anyhow!("invalid precomputed embeddings in {}: {e}", path.display()))?;
+        let dim = vectors
+            .values()
+            .next()
+            .map(Vec::len)
+            .filter(|&dim| dim > 0)
+            .ok_or_else(|| anyhow!("no vectors in {}", path.display()))?;
+        if let Some((hash, v)) = vectors.iter().find(|(_, v)| v.len() != dim) {
+            return Err(anyhow!(
+                "vector for {hash} has {} dims, expected {dim}",
+                v.len()
+            ));
+        }
+        Ok(Self {
+            model,
+            dim,
+            vectors: vectors
+                .into_iter()
+                .map(|(hash, v)| (hash.to_ascii_lowercase(), v))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite-vec")]
+#[async_trait]
+impl EmbeddingProvider for PrecomputedEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let hash = content_hash(text);
+        self.vectors
+            .get(&hash)
+            .cloned()
+            .ok_or_else(|| anyhow!("no precomputed embedding for content {hash}"))
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+}
+
+/// Consecutive failures after which the breaker opens.
+#[cfg(feature = "sqlite-vec")]
+const BREAKER_FAILURE_THRESHOLD: u32 = 3;
+/// How long an open breaker short-circuits calls before probing again.
+#[cfg(feature = "sqlite-vec")]
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Wraps a provider so that an outage fails fast instead of making every
+/// store/recall wait on a timing-out API. Callers already fall back to
+/// keyword recall on error, so an open breaker just degrades to that path.
+pub struct CircuitBreakerProvider {
+    inner: Arc<dyn EmbeddingProvider>,
+    threshold: u32,
+    cooldown: Duration,
+    failures: AtomicU32,
+    open_until: Mutex<Option<Instant>>,
+}
+
+impl CircuitBreakerProvider {
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            inner,
+            threshold: threshold.max(1),
+            cooldown,
+            failures: AtomicU32::new(0),
+            open_until: Mutex::new(None),
+        }
+    }
+
+    fn check_open(&self) -> Result<()> {
+        let open_until = self.open_until.lock().unwrap_or_else(|e| e.into_inner());
+        match *open_until {
+            Some(until) if Instant::now() < until => {
+                Err(anyhow!("embedding provider unavailable (circuit open)"))
             }
-            _ => None,
+            _ => Ok(()),
+        }
+    }
+
+    fn record<T>(&self, result: &Result<T>) {
+        let mut open_until = self.open_until.lock().unwrap_or_else(|e| e.into_inner());
+        if result.is_ok() {
+            self.failures.store(0, Ordering::SeqCst);
+            *open_until = None;
+            return;
+        }
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= self.threshold {
+            if open_until.is_none() {
+                warn!(
+                    "embedding provider failed {failures} times in a row; pausing calls for {}s",
+                    self.cooldown.as_secs()
+                );
+            }
+            *open_until = Some(Instant::now() + self.cooldown);
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::{Config, WorkingDirIsolation};
+#[async_trait]
+impl EmbeddingProvider for CircuitBreakerProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.check_open()?;
+        let result = self.inner.embed(text).await;
+        self.record(&result);
+        result
+    }
 
-    fn base_config() -> Config {
-        let mut cfg = Config::test_defaults();
-        cfg.working_dir_isolation = WorkingDirIsolation::Chat;
-        cfg
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.check_open()?;
+        let result = self.inner.embed_batch(texts).await;
+        self.record(&result);
+        result
     }
 
-    #[test]
-    fn test_create_provider_without_config_returns_none() {
-        let cfg = base_config();
-        assert!(create_provider(&cfg).is_none());
+    async fn embed_batch_partial(&self, texts: &[String]) -> Vec<Result<Vec<f32>>> {
+        if let Err(e) = self.check_open() {
+            return texts.iter().map(|_| Err(anyhow!("{e}"))).collect();
+        }
+        let results = self.inner.embed_batch_partial(texts).await;
+        // A few rejected items are not an outage; only an all-failed batch
+        // counts against the breaker.
+        match results.iter().find(|r| r.is_ok()) {
+            Some(_) => self.record(&Ok(())),
+            None if !results.is_empty() => self.record::<()>(&Err(anyhow!("batch failed"))),
+            None => {}
+        }
+        results
     }
 
-    #[cfg(feature = "sqlite-vec")]
-    #[test]
-    fn test_create_provider_openai_when_configured() {
-        let mut cfg = base_config();
-        cfg.embedding_provider = Some("openai".into());
-        cfg.embedding_api_key = Some("sk-test".into());
-        cfg.embedding_model = Some("text-embedding-3-small".into());
-        cfg.embedding_dim = Some(1536);
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
 
-        let provider = create_provider(&cfg);
-        assert!(provider.is_some());
-        assert_eq!(
-            provider.as_ref().map(|p| p.model()),
-            Some("text-embedding-3-small")
-        );
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn batches_natively(&self) -> bool {
+        self.inner.batches_natively()
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.check_open().is_err()
+    }
+}
+
+struct RateBucket {
+    capacity: f64,
+    per_sec: f64,
+    available: f64,
+}
+
+impl RateBucket {
+    /// A bucket holding `capacity` units that refills fully every `window`;
+    /// `None` when the limit is disabled (0).
+    fn new(capacity: u32, window: Duration) -> Option<Self> {
+        (capacity > 0).then(|| Self {
+            capacity: capacity as f64,
+            per_sec: capacity as f64 / window.as_secs_f64().max(f64::EPSILON),
+            available: capacity as f64,
+        })
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.available = (self.available + elapsed.as_secs_f64() * self.per_sec).min(self.capacity);
+    }
+
+    /// Time until `cost` units are available. A cost above capacity is
+    /// clamped so one oversized call waits for a full bucket instead of
+    /// blocking forever.
+    fn wait_for(&self, cost: f64) -> Duration {
+        let missing = cost.min(self.capacity) - self.available;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_sec)
+        }
+    }
+
+    fn take(&mut self, cost: f64) {
+        self.available -= cost.min(self.capacity);
+    }
+}
+
+struct RateLimitState {
+    requests: Option<RateBucket>,
+    tokens: Option<RateBucket>,
+    refilled_at: Instant,
+}
+
+/// Paces calls to stay under a provider's requests- and tokens-per-window
+/// quotas. Calls wait for budget rather than fail, so it sits inside the
+/// circuit breaker: a paced call is slow, not a failure. Tokens are estimated
+/// at four bytes each.
+pub struct RateLimitedEmbeddingProvider {
+    inner: Arc<dyn EmbeddingProvider>,
+    state: Mutex<RateLimitState>,
+}
+
+impl RateLimitedEmbeddingProvider {
+    pub fn new(
+        inner: Arc<dyn EmbeddingProvider>,
+        requests_per_window: u32,
+        tokens_per_window: u32,
+        window: Duration,
+    ) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(RateLimitState {
+                requests: RateBucket::new(requests_per_window, window),
+                tokens: RateBucket::new(tokens_per_window, window),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    async fn acquire(&self, texts: &[&str]) {
+        let tokens: f64 = texts.iter().map(|t| (t.len() / 4).max(1) as f64).sum();
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.refilled_at);
+                state.refilled_at = now;
+                let RateLimitState {
+                    requests,
+                    tokens: token_bucket,
+                    ..
+                } = &mut *state;
+                let mut wait = Duration::ZERO;
+                if let Some(bucket) = requests.as_mut() {
+                    bucket.refill(elapsed);
+                    wait = wait.max(bucket.wait_for(1.0));
+                }
+                if let Some(bucket) = token_bucket.as_mut() {
+                    bucket.refill(elapsed);
+                    wait = wait.max(bucket.wait_for(tokens));
+                }
+                if wait.is_zero() {
+                    if let Some(bucket) = requests.as_mut() {
+                        bucket.take(1.0);
+                    }
+                    if let Some(bucket) = token_bucket.as_mut() {
+                        bucket.take(tokens);
+                    }
+                    return;
+                }
+                wait
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RateLimitedEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.acquire(&[text]).await;
+        self.inner.embed(text).await
+    }
+
+    /// A native batch is one request; otherwise the inner provider makes one
+    /// request per text, so each is paced on its own.
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if !self.inner.batches_natively() {
+            let mut out = Vec::with_capacity(texts.len());
+            for text in texts {
+                out.push(self.embed(text).await?);
+            }
+            return Ok(out);
+        }
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        self.acquire(&refs).await;
+        self.inner.embed_batch(texts).await
+    }
+
+    async fn embed_batch_partial(&self, texts: &[String]) -> Vec<Result<Vec<f32>>> {
+        if !self.inner.batches_natively() {
+            let mut out = Vec::with_capacity(texts.len());
+            for text in texts {
+                out.push(self.embed(text).await);
+            }
+            return out;
+        }
+        let refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        self.acquire(&refs).await;
+        self.inner.embed_batch_partial(texts).await
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn batches_natively(&self) -> bool {
+        self.inner.batches_natively()
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.inner.is_degraded()
+    }
+}
+
+/// Adds the configured instruction prefixes (e5, bge, nomic, ...) on top of
+/// another provider; plain `embed` calls pass through unchanged.
+pub struct PrefixedEmbeddingProvider {
+    inner: Arc<dyn EmbeddingProvider>,
+    query_prefix: String,
+    document_prefix: String,
+}
+
+impl PrefixedEmbeddingProvider {
+    pub fn new(
+        inner: Arc<dyn EmbeddingProvider>,
+        query_prefix: impl Into<String>,
+        document_prefix: impl Into<String>,
+    ) -> Self {
+        Self {
+            inner,
+            query_prefix: query_prefix.into(),
+            document_prefix: document_prefix.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for PrefixedEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.inner.embed(text).await
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.inner.embed_batch(texts).await
+    }
+
+    async fn embed_batch_partial(&self, texts: &[String]) -> Vec<Result<Vec<f32>>> {
+        self.inner.embed_batch_partial(texts).await
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn batches_natively(&self) -> bool {
+        self.inner.batches_natively()
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.inner.is_degraded()
+    }
+
+    fn query_prefix(&self) -> &str {
+        &self.query_prefix
+    }
+
+    fn document_prefix(&self) -> &str {
+        &self.document_prefix
+    }
+}
+
+/// How `EnsembleEmbeddingProvider` combines member vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnsembleMode {
+    /// Append member vectors; the dimension is the sum of member dimensions.
+    Concat,
+    /// Element-wise mean of L2-normalized member vectors; members must share
+    /// a dimension.
+    Average,
+}
+
+impl EnsembleMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EnsembleMode::Concat => "concat",
+            EnsembleMode::Average => "average",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "concat" => Some(EnsembleMode::Concat),
+            "average" | "mean" => Some(EnsembleMode::Average),
+            _ => None,
+        }
+    }
+}
+
+/// Embeds with several providers concurrently and combines the results, for
+/// multi-model retrieval. Any member failure fails the whole call. Members
+/// that learn their size from their first response leave the combined
+/// dimension at 0 until they have answered.
+pub struct EnsembleEmbeddingProvider {
+    members: Vec<Arc<dyn EmbeddingProvider>>,
+    mode: EnsembleMode,
+    model: String,
+}
+
+impl EnsembleEmbeddingProvider {
+    pub fn new(members: Vec<Arc<dyn EmbeddingProvider>>, mode: EnsembleMode) -> Result<Self> {
+        if members.is_empty() {
+            return Err(anyhow!("ensemble needs at least one member provider"));
+        }
+        if mode == EnsembleMode::Average {
+            let mut known = members.iter().map(|m| m.dimension()).filter(|d| *d > 0);
+            if let Some(dim) = known.next() {
+                if known.any(|d| d != dim) {
+                    return Err(anyhow!("average ensemble members must share a dimension"));
+                }
+            }
+        }
+        let model = members
+            .iter()
+            .map(|m| m.model())
+            .collect::<Vec<_>>()
+            .join("+");
+        Ok(Self {
+            members,
+            mode,
+            model,
+        })
+    }
+
+    fn combine(&self, vectors: Vec<Vec<f32>>) -> Result<Vec<f32>> {
+        for (member, v) in self.members.iter().zip(&vectors) {
+            let expected = member.dimension();
+            if v.is_empty() || (expected > 0 && v.len() != expected) {
+                return Err(anyhow!(
+                    "ensemble member {} returned {} dims, expected {expected}",
+                    member.model(),
+                    v.len(),
+                ));
+            }
+        }
+        match self.mode {
+            EnsembleMode::Concat => Ok(vectors.concat()),
+            EnsembleMode::Average => {
+                let dim = vectors[0].len();
+                if vectors.iter().any(|v| v.len() != dim) {
+                    return Err(anyhow!("average ensemble members returned different sizes"));
+                }
+                let mut out = vec![0.0f32; dim];
+                for v in &vectors {
+                    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+                    if norm == 0.0 {
+                        continue;
+                    }
+                    for (o, x) in out.iter_mut().zip(v) {
+                        *o += x / norm;
+                    }
+                }
+                let n = vectors.len() as f32;
+                out.iter_mut().for_each(|o| *o /= n);
+                Ok(out)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for EnsembleEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let vectors =
+            futures_util::future::try_join_all(self.members.iter().map(|m| m.embed(text))).await?;
+        self.combine(vectors)
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        let dims: Vec<usize> = self.members.iter().map(|m| m.dimension()).collect();
+        if dims.contains(&0) {
+            return 0;
+        }
+        match self.mode {
+            EnsembleMode::Concat => dims.iter().sum(),
+            EnsembleMode::Average => dims[0],
+        }
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.members.iter().any(|m| m.is_degraded())
+    }
+}
+
+/// Tries providers in order until one succeeds, so an outage of the primary
+/// does not drop recall to keyword-only. All providers must embed into the
+/// same vector space, e.g. one model served by OpenAI and by an Azure
+/// deployment: two different models with equal dimensions still produce
+/// vectors that cannot be compared.
+pub struct FallbackEmbeddingProvider {
+    providers: Vec<Arc<dyn EmbeddingProvider>>,
+    /// Size every answer must have: the primary's once it reports one,
+    /// otherwise that of the first vector any provider returned.
+    dim: OnceLock<usize>,
+}
+
+impl FallbackEmbeddingProvider {
+    /// Every fallback must run the primary's model, so their vectors share
+    /// one space and the stored `embedding_model` stays accurate. Providers
+    /// that already know their size must agree on it; those still at 0 are
+    /// checked against the first vector instead.
+    pub fn new(providers: Vec<Arc<dyn EmbeddingProvider>>) -> Result<Self> {
+        let primary = providers
+            .first()
+            .ok_or_else(|| anyhow!("fallback needs at least one provider"))?;
+        for fallback in &providers[1..] {
+            Self::check_compatible(primary.as_ref(), fallback.as_ref())?;
+        }
+        let mut known = providers.iter().filter(|p| p.dimension() > 0);
+        if let Some(first) = known.next() {
+            let dim = first.dimension();
+            if let Some(other) = known.find(|p| p.dimension() != dim) {
+                return Err(anyhow!(
+                    "fallback provider {} has {} dims, {} has {dim}",
+                    other.model(),
+                    other.dimension(),
+                    first.model()
+                ));
+            }
+        }
+        let dim = OnceLock::new();
+        if primary.dimension() > 0 {
+            let _ = dim.set(primary.dimension());
+        }
+        Ok(Self { providers, dim })
+    }
+
+    /// Whether `fallback` may stand in for `primary`: same model, and equal
+    /// sizes where both are known.
+    pub fn check_compatible(
+        primary: &dyn EmbeddingProvider,
+        fallback: &dyn EmbeddingProvider,
+    ) -> Result<()> {
+        if fallback.model() != primary.model() {
+            return Err(anyhow!(
+                "fallback runs model {}, primary runs {}",
+                fallback.model(),
+                primary.model()
+            ));
+        }
+        if primary.dimension() > 0
+            && fallback.dimension() > 0
+            && fallback.dimension() != primary.dimension()
+        {
+            return Err(anyhow!(
+                "fallback has {} dims, primary has {}",
+                fallback.dimension(),
+                primary.dimension()
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_dimension(&self, provider: &dyn EmbeddingProvider, v: &[f32]) -> Result<()> {
+        let expected = *self
+            .dim
+            .get_or_init(|| match self.providers[0].dimension() {
+                0 => v.len(),
+                dim => dim,
+            });
+        if v.len() == expected {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} returned {} dims, expected {expected}",
+                provider.model(),
+                v.len()
+            ))
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for FallbackEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut last_err = None;
+        for provider in &self.providers {
+            let err = match provider.embed(text).await {
+                Ok(v) => match self.check_dimension(provider.as_ref(), &v) {
+                    Ok(()) => return Ok(v),
+                    Err(e) => e,
+                },
+                Err(e) => e,
+            };
+            warn!(
+                "embedding provider {} failed, trying next: {err}",
+                provider.model()
+            );
+            last_err = Some(err);
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("no embedding providers")))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut last_err = None;
+        for provider in &self.providers {
+            let err = match provider.embed_batch(texts).await {
+                Ok(vectors) if vectors.len() != texts.len() => anyhow!(
+                    "{} returned {} vectors for {} texts",
+                    provider.model(),
+                    vectors.len(),
+                    texts.len()
+                ),
+                Ok(vectors) => match vectors
+                    .iter()
+                    .try_for_each(|v| self.check_dimension(provider.as_ref(), v))
+                {
+                    Ok(()) => return Ok(vectors),
+                    Err(e) => e,
+                },
+                Err(e) => e,
+            };
+            warn!(
+                "embedding provider {} failed, trying next: {err}",
+                provider.model()
+            );
+            last_err = Some(err);
+        }
+        Err(last_err.unwrap_or_else(|| anyhow!("no embedding providers")))
+    }
+
+    /// Items a provider rejects are retried, as a smaller batch, on the next.
+    async fn embed_batch_partial(&self, texts: &[String]) -> Vec<Result<Vec<f32>>> {
+        let mut out: Vec<Result<Vec<f32>>> = texts
+            .iter()
+            .map(|_| Err(anyhow!("no embedding providers")))
+            .collect();
+        for provider in &self.providers {
+            let pending: Vec<usize> = (0..texts.len()).filter(|&i| out[i].is_err()).collect();
+            if pending.is_empty() {
+                break;
+            }
+            let batch: Vec<String> = pending.iter().map(|&i| texts[i].clone()).collect();
+            let results = provider.embed_batch_partial(&batch).await;
+            for (&i, result) in pending.iter().zip(results) {
+                out[i] = result.and_then(|v| {
+                    self.check_dimension(provider.as_ref(), &v)?;
+                    Ok(v)
+                });
+            }
+            if out.iter().any(|r| r.is_err()) {
+                warn!(
+                    "embedding provider {} failed some items, trying next",
+                    provider.model()
+                );
+            }
+        }
+        out
+    }
+
+    fn model(&self) -> &str {
+        self.providers[0].model()
+    }
+
+    fn dimension(&self) -> usize {
+        self.dim
+            .get()
+            .copied()
+            .unwrap_or_else(|| self.providers[0].dimension())
+    }
+
+    fn batches_natively(&self) -> bool {
+        self.providers.iter().all(|p| p.batches_natively())
+    }
+
+    /// Degraded while the primary is short-circuited, even if a fallback is
+    /// still answering.
+    fn is_degraded(&self) -> bool {
+        self.providers[0].is_degraded()
+    }
+}
+
+enum EmbeddingJob {
+    Embed { memory_id: i64, content: String },
+    Flush(oneshot::Sender<()>),
+}
+
+/// Background worker that embeds memories after their rows are written, so
+/// callers don't wait on the provider. Jobs run one at a time in FIFO order.
+pub struct EmbeddingQueue {
+    tx: mpsc::UnboundedSender<EmbeddingJob>,
+    depth: Arc<AtomicUsize>,
+}
+
+impl EmbeddingQueue {
+    pub fn spawn(db: Arc<Database>, provider: Arc<dyn EmbeddingProvider>) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let depth = Arc::new(AtomicUsize::new(0));
+        let worker_depth = depth.clone();
+        tokio::spawn(async move {
+            while let Some(job) = rx.recv().await {
+                match job {
+                    EmbeddingJob::Embed { memory_id, content } => {
+                        if let Err(e) =
+                            store_embedding(&db, provider.as_ref(), memory_id, &content).await
+                        {
+                            // The row keeps a NULL embedding_model, so the
+                            // reflector's backfill retries it later.
+                            warn!("queued embedding for memory {memory_id} failed: {e}");
+                        }
+                        worker_depth.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    EmbeddingJob::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        Self { tx, depth }
+    }
+
+    pub fn enqueue(&self, memory_id: i64, content: String) {
+        self.depth.fetch_add(1, Ordering::SeqCst);
+        if self
+            .tx
+            .send(EmbeddingJob::Embed { memory_id, content })
+            .is_err()
+        {
+            self.depth.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Number of memories waiting to be embedded, including the one in flight.
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+
+    /// Wait until every job enqueued before this call has finished.
+    pub async fn flush(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(EmbeddingJob::Flush(done_tx)).is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+}
+
+async fn store_embedding(
+    db: &Arc<Database>,
+    provider: &dyn EmbeddingProvider,
+    memory_id: i64,
+    content: &str,
+) -> Result<()> {
+    let embedding = provider.embed_document(content).await?;
+    let model = provider.model().to_string();
+    let content = content.to_string();
+    call_blocking(db.clone(), move |db| {
+        db.store_memory_embedding_if_current(memory_id, &content, &embedding, &model)
+    })
+    .await?;
+    Ok(())
+}
+
+/// Small LRU of recent KNN recall results keyed by chat and normalized query,
+/// so retries and multi-step turns skip the embedding call and vector scan.
+/// Each entry records a fingerprint of the candidate memories it was computed
+/// against; any store, update, or archive changes that fingerprint and the
+/// entry is treated as a miss.
+pub struct RecallCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<std::collections::HashMap<(i64, String), RecallCacheEntry>>,
+}
+
+struct RecallCacheEntry {
+    fingerprint: u64,
+    hits: Vec<(i64, f32)>,
+    inserted_at: Instant,
+    last_used: Instant,
+}
+
+impl RecallCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            entries: Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Option<Self> {
+        (config.memory_recall_cache_size > 0).then(|| {
+            Self::new(
+                config.memory_recall_cache_size,
+                Duration::from_secs(config.memory_recall_cache_ttl_secs),
+            )
+        })
+    }
+
+    fn key(chat_id: i64, query: &str) -> (i64, String) {
+        let normalized = query
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        (chat_id, normalized)
+    }
+
+    pub fn get(&self, chat_id: i64, query: &str, fingerprint: u64) -> Option<Vec<(i64, f32)>> {
+        let key = Self::key(chat_id, query);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.get_mut(&key)?;
+        if entry.fingerprint != fingerprint || entry.inserted_at.elapsed() > self.ttl {
+            entries.remove(&key);
+            return None;
+        }
+        entry.last_used = Instant::now();
+        Some(entry.hits.clone())
+    }
+
+    pub fn insert(&self, chat_id: i64, query: &str, fingerprint: u64, hits: Vec<(i64, f32)>) {
+        let key = Self::key(chat_id, query);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if !entries.contains_key(&key) && entries.len() >= self.capacity {
+            if let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone())
+            {
+                entries.remove(&oldest);
+            }
+        }
+        let now = Instant::now();
+        entries.insert(
+            key,
+            RecallCacheEntry {
+                fingerprint,
+                hits,
+                inserted_at: now,
+                last_used: now,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub fn create_provider(config: &Config) -> Option<Arc<dyn EmbeddingProvider>> {
+    #[cfg(not(feature = "sqlite-vec"))]
+    {
+        let _ = config;
+        None
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    {
+        let primary = create_ensemble_provider(config, create_base_provider(config)?);
+        let provider = if config.embedding_fallbacks.is_empty() {
+            primary
+        } else {
+            let mut providers = vec![primary];
+            for fallback in &config.embedding_fallbacks {
+                let fallback_config = endpoint_config(config, fallback);
+                match create_base_provider(&fallback_config) {
+                    Some(p) => match FallbackEmbeddingProvider::check_compatible(
+                        providers[0].as_ref(),
+                        p.as_ref(),
+                    ) {
+                        Ok(()) => providers.push(p),
+                        Err(e) => {
+                            warn!("Skipping embedding fallback {}: {e}", fallback.provider)
+                        }
+                    },
+                    None => warn!(
+                        "Skipping embedding fallback {}: unknown provider or missing api key/base url",
+                        fallback.provider
+                    ),
+                }
+            }
+            if providers.len() == 1 {
+                providers.swap_remove(0)
+            } else {
+                let primary = providers[0].clone();
+                match FallbackEmbeddingProvider::new(providers) {
+                    Ok(fallback) => Arc::new(fallback),
+                    Err(e) => {
+                        warn!("Ignoring embedding fallbacks: {e}");
+                        primary
+                    }
+                }
+            }
+        };
+        let query_prefix = config.embedding_query_prefix.clone().unwrap_or_default();
+        let document_prefix = config.embedding_document_prefix.clone().unwrap_or_default();
+        if query_prefix.is_empty() && document_prefix.is_empty() {
+            return Some(provider);
+        }
+        Some(Arc::new(PrefixedEmbeddingProvider::new(
+            provider,
+            query_prefix,
+            document_prefix,
+        )))
+    }
+}
+
+/// `config` with the primary endpoint swapped for `endpoint`.
+#[cfg(feature = "sqlite-vec")]
+fn endpoint_config(config: &Config, endpoint: &crate::config::EmbeddingFallback) -> Config {
+    let mut endpoint_config = config.clone();
+    endpoint_config.embedding_provider = Some(endpoint.provider.clone());
+    endpoint_config.embedding_api_key = endpoint.api_key.clone();
+    endpoint_config.embedding_base_url = endpoint.base_url.clone();
+    endpoint_config.embedding_endpoint_path = endpoint.endpoint_path.clone();
+    endpoint_config.embedding_model = endpoint.model.clone();
+    endpoint_config
+}
+
+/// Combine `primary` with the configured `embedding_ensemble` members, or
+/// return it unchanged when there are none.
+#[cfg(feature = "sqlite-vec")]
+fn create_ensemble_provider(
+    config: &Config,
+    primary: Arc<dyn EmbeddingProvider>,
+) -> Arc<dyn EmbeddingProvider> {
+    if config.embedding_ensemble.is_empty() {
+        return primary;
+    }
+    let mut members = vec![primary.clone()];
+    for member in &config.embedding_ensemble {
+        match create_base_provider(&endpoint_config(config, member)) {
+            Some(p) => members.push(p),
+            None => warn!(
+                "Skipping embedding ensemble member {}: unknown provider or missing api key/base url",
+                member.provider
+            ),
+        }
+    }
+    if members.len() == 1 {
+        return primary;
+    }
+    match EnsembleEmbeddingProvider::new(members, config.embedding_ensemble_mode()) {
+        Ok(ensemble) => Arc::new(ensemble),
+        Err(e) => {
+            warn!("Ignoring embedding ensemble: {e}");
+            primary
+        }
+    }
+}
+
+/// Build one configured provider behind its own circuit breaker, without
+/// fallbacks or instruction prefixes.
+#[cfg(feature = "sqlite-vec")]
+fn create_base_provider(config: &Config) -> Option<Arc<dyn EmbeddingProvider>> {
+    let provider = config
+        .embedding_provider
+        .as_deref()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+    if provider.is_empty() {
+        return None;
+    }
+    // Local lookups: no rate limits to respect, and a missing entry is not
+    // an outage, so it must not trip the circuit breaker.
+    if provider == "precomputed" {
+        let path = config.embedding_precomputed_path.as_deref()?.trim();
+        let model = config
+            .embedding_model
+            .clone()
+            .unwrap_or_else(|| "precomputed".to_string());
+        return match PrecomputedEmbeddingProvider::load(std::path::Path::new(path), model) {
+            Ok(p) => Some(Arc::new(p)),
+            Err(e) => {
+                warn!("Precomputed embeddings unavailable: {e}");
+                None
+            }
+        };
+    }
+
+    let model = config
+        .embedding_model
+        .clone()
+        .unwrap_or_else(|| match provider.as_str() {
+            "openai" => "text-embedding-3-small".to_string(),
+            "ollama" => "nomic-embed-text".to_string(),
+            "gemini" => "text-embedding-004".to_string(),
+            _ => "text-embedding-3-small".to_string(),
+        });
+    let dim = config
+        .embedding_dim
+        .or_else(|| infer_default_dim(&provider, &model));
+    let client = reqwest::Client::new();
+    // Only the text-embedding-3 family accepts `dimensions`; older or
+    // third-party models reject it, so there the setting just records
+    // the size the model already returns.
+    let openai_dimensions = config
+        .embedding_dim
+        .filter(|_| model.starts_with("text-embedding-3"));
+
+    let inner: Arc<dyn EmbeddingProvider> = match provider.as_str() {
+        "openai" => {
+            let api_key = config.embedding_api_key.clone().unwrap_or_default();
+            if api_key.trim().is_empty() {
+                return None;
+            }
+            let base_url = config
+                .embedding_base_url
+                .clone()
+                .unwrap_or_else(|| "https://api.openai.com/v1".to_string());
+            Arc::new(OpenAIEmbeddingProvider {
+                client,
+                base_url,
+                api_key,
+                model,
+                dim: dim.map(OnceLock::from).unwrap_or_default(),
+                auth: AuthMode::Bearer,
+                endpoint_path: config.embedding_endpoint_path.clone(),
+                dimensions: openai_dimensions,
+            })
+        }
+        "azure" => {
+            let api_key = config.embedding_api_key.clone().unwrap_or_default();
+            // Azure has no shared endpoint; the resource URL is required.
+            let base_url = config.embedding_base_url.clone().unwrap_or_default();
+            if api_key.trim().is_empty() || base_url.trim().is_empty() {
+                return None;
+            }
+            Arc::new(OpenAIEmbeddingProvider {
+                client,
+                base_url,
+                api_key,
+                model,
+                dim: dim.map(OnceLock::from).unwrap_or_default(),
+                auth: AuthMode::ApiKeyHeader,
+                endpoint_path: Some(
+                    config
+                        .embedding_endpoint_path
+                        .clone()
+                        .unwrap_or_else(|| AZURE_EMBEDDINGS_PATH.to_string()),
+                ),
+                dimensions: openai_dimensions,
+            })
+        }
+        "ollama" => {
+            let base_url = config
+                .embedding_base_url
+                .clone()
+                .unwrap_or_else(|| "http://127.0.0.1:11434".to_string());
+            Arc::new(OllamaEmbeddingProvider {
+                client,
+                base_url,
+                model,
+                dim: dim.unwrap_or_default(),
+            })
+        }
+        "gemini" => {
+            let api_key = config.embedding_api_key.clone().unwrap_or_default();
+            if api_key.trim().is_empty() {
+                return None;
+            }
+            let base_url = config
+                .embedding_base_url
+                .clone()
+                .unwrap_or_else(|| "https://generativelanguage.googleapis.com/v1beta".to_string());
+            Arc::new(GeminiEmbeddingProvider {
+                client,
+                base_url,
+                api_key,
+                model,
+                dim: dim.unwrap_or_default(),
+                output_dim: config.embedding_dim,
+            })
+        }
+        _ => return None,
+    };
+    let inner: Arc<dyn EmbeddingProvider> =
+        if config.embedding_requests_per_minute > 0 || config.embedding_tokens_per_minute > 0 {
+            Arc::new(RateLimitedEmbeddingProvider::new(
+                inner,
+                config.embedding_requests_per_minute,
+                config.embedding_tokens_per_minute,
+                Duration::from_secs(60),
+            ))
+        } else {
+            inner
+        };
+    Some(Arc::new(CircuitBreakerProvider::new(
+        inner,
+        BREAKER_FAILURE_THRESHOLD,
+        BREAKER_COOLDOWN,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, WorkingDirIsolation};
+
+    fn base_config() -> Config {
+        let mut cfg = Config::test_defaults();
+        cfg.working_dir_isolation = WorkingDirIsolation::Chat;
+        cfg
+    }
+
+    #[test]
+    fn test_create_provider_without_config_returns_none() {
+        let cfg = base_config();
+        assert!(create_provider(&cfg).is_none());
+    }
+
+    struct CountingProvider;
+
+    #[async_trait]
+    impl EmbeddingProvider for CountingProvider {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok(vec![text.len() as f32])
+        }
+
+        fn model(&self) -> &str {
+            "counting"
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_embed_batch_preserves_order() {
+        let texts = vec!["a".to_string(), "abc".to_string(), "ab".to_string()];
+        let out = CountingProvider.embed_batch(&texts).await.unwrap();
+        assert_eq!(out, vec![vec![1.0], vec![3.0], vec![2.0]]);
+    }
+
+    /// Model name shared by the test providers so they can back each other up.
+    const TEST_MODEL: &str = "test-model";
+
+    struct FixedProvider(Vec<f32>);
+
+    #[async_trait]
+    impl EmbeddingProvider for FixedProvider {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            Ok(self.0.clone())
+        }
+
+        fn model(&self) -> &str {
+            TEST_MODEL
+        }
+
+        fn dimension(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_concat_and_average() {
+        let a: Arc<dyn EmbeddingProvider> = Arc::new(FixedProvider(vec![3.0, 4.0]));
+        let b: Arc<dyn EmbeddingProvider> = Arc::new(FixedProvider(vec![0.0, 2.0]));
+
+        let concat =
+            EnsembleEmbeddingProvider::new(vec![a.clone(), b.clone()], EnsembleMode::Concat)
+                .unwrap();
+        assert_eq!(concat.dimension(), 4);
+        assert_eq!(concat.model(), format!("{TEST_MODEL}+{TEST_MODEL}"));
+        assert_eq!(concat.embed("x").await.unwrap(), vec![3.0, 4.0, 0.0, 2.0]);
+
+        let average =
+            EnsembleEmbeddingProvider::new(vec![a.clone(), b], EnsembleMode::Average).unwrap();
+        assert_eq!(average.dimension(), 2);
+        let v = average.embed("x").await.unwrap();
+        assert!(
+            (v[0] - 0.3).abs() < 1e-6 && (v[1] - 0.9).abs() < 1e-6,
+            "{v:?}"
+        );
+
+        let c: Arc<dyn EmbeddingProvider> = Arc::new(FixedProvider(vec![1.0]));
+        assert!(EnsembleEmbeddingProvider::new(vec![a, c], EnsembleMode::Average).is_err());
+        assert!(EnsembleEmbeddingProvider::new(Vec::new(), EnsembleMode::Concat).is_err());
+    }
+
+    /// Reports no size until its first response, like the HTTP providers.
+    #[derive(Default)]
+    struct LearnsSize(OnceLock<usize>);
+
+    #[async_trait]
+    impl EmbeddingProvider for LearnsSize {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            let _ = self.0.set(3);
+            Ok(vec![1.0, 0.0, 0.0])
+        }
+
+        fn model(&self) -> &str {
+            "learns"
+        }
+
+        fn dimension(&self) -> usize {
+            self.0.get().copied().unwrap_or(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_dimension_waits_for_unsized_members() {
+        let ensemble = EnsembleEmbeddingProvider::new(
+            vec![
+                Arc::new(FixedProvider(vec![1.0])),
+                Arc::new(LearnsSize::default()),
+            ],
+            EnsembleMode::Concat,
+        )
+        .unwrap();
+        assert_eq!(ensemble.dimension(), 0);
+        assert_eq!(ensemble.embed("x").await.unwrap().len(), 4);
+        assert_eq!(ensemble.dimension(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_provider_prefixes_queries_and_documents() {
+        let provider = PrefixedEmbeddingProvider::new(Arc::new(CountingProvider), "query: ", "p: ");
+        assert_eq!(provider.embed("ab").await.unwrap(), vec![2.0]);
+        assert_eq!(provider.embed_query("ab").await.unwrap(), vec![9.0]);
+        assert_eq!(provider.embed_document("ab").await.unwrap(), vec![5.0]);
+        let docs = provider
+            .embed_documents(&["a".to_string(), "abc".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(docs, vec![vec![4.0], vec![6.0]]);
+        assert_eq!(CountingProvider.embed_query("ab").await.unwrap(), vec![2.0]);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_create_provider_ollama_without_api_key() {
+        let mut cfg = base_config();
+        cfg.embedding_provider = Some("ollama".into());
+
+        let provider = create_provider(&cfg).expect("ollama needs no api key");
+        assert_eq!(provider.model(), "nomic-embed-text");
+        assert_eq!(provider.dimension(), 1024);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_create_provider_builds_ensemble_from_config() {
+        let mut cfg = base_config();
+        cfg.embedding_provider = Some("ollama".into());
+        cfg.embedding_ensemble = vec![crate::config::EmbeddingFallback {
+            provider: "ollama".into(),
+            api_key: None,
+            base_url: None,
+            endpoint_path: None,
+            model: Some("mxbai-embed-large".into()),
+        }];
+
+        let provider = create_provider(&cfg).unwrap();
+        assert_eq!(provider.model(), "nomic-embed-text+mxbai-embed-large");
+        assert_eq!(provider.dimension(), 2048);
+
+        cfg.embedding_ensemble_mode = "average".into();
+        let provider = create_provider(&cfg).unwrap();
+        assert_eq!(provider.dimension(), 1024);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_create_provider_openai_when_configured() {
+        let mut cfg = base_config();
+        cfg.embedding_provider = Some("openai".into());
+        cfg.embedding_api_key = Some("sk-test".into());
+        cfg.embedding_model = Some("text-embedding-3-small".into());
+        cfg.embedding_dim = Some(1536);
+
+        let provider = create_provider(&cfg);
+        assert!(provider.is_some());
+        assert_eq!(
+            provider.as_ref().map(|p| p.model()),
+            Some("text-embedding-3-small")
+        );
+    }
+
+    #[test]
+    fn test_gemini_request_body_shape() {
+        let provider = GeminiEmbeddingProvider {
+            client: reqwest::Client::new(),
+            base_url: "http://localhost".into(),
+            api_key: "k".into(),
+            model: "text-embedding-004".into(),
+            dim: 256,
+            output_dim: Some(256),
+        };
+        let body = serde_json::to_value(provider.content_request("hello")).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "model": "models/text-embedding-004",
+                "content": {"parts": [{"text": "hello"}]},
+                "outputDimensionality": 256
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gemini_sends_key_in_header_not_url() {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(2)))
+                .unwrap();
+            let mut buf = [0u8; 8192];
+            let n = stream.read(&mut buf).unwrap_or(0);
+            let body = r#"{"embedding":{"values":[0.5,0.5]}}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+        let provider = GeminiEmbeddingProvider {
+            client: reqwest::Client::new(),
+            base_url: format!("http://{addr}"),
+            api_key: "secret-key".into(),
+            model: "text-embedding-004".into(),
+            dim: 2,
+            output_dim: None,
+        };
+        assert_eq!(provider.embed("hi").await.unwrap(), vec![0.5, 0.5]);
+        let request = server.join().unwrap();
+        let request_line = request.lines().next().unwrap();
+        assert!(!request_line.contains("secret-key"), "{request_line}");
+        assert!(request
+            .to_ascii_lowercase()
+            .contains("x-goog-api-key: secret-key"));
+    }
+
+    #[tokio::test]
+    async fn test_ollama_batch_caps_requests_in_flight() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (server_active, server_peak) = (active.clone(), peak.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let (active, peak) = (server_active.clone(), server_peak.clone());
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    while !request.ends_with(b"}") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    let body = r#"{"embedding":[0.5,0.5]}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        let provider = OllamaEmbeddingProvider {
+            client: reqwest::Client::new(),
+            base_url: format!("http://{addr}"),
+            model: "nomic-embed-text".into(),
+            dim: 2,
+        };
+        let texts: Vec<String> = (0..12).map(|i| format!("text {i}")).collect();
+        assert_eq!(provider.embed_batch(&texts).await.unwrap().len(), 12);
+        assert!(provider
+            .embed_batch_partial(&texts)
+            .await
+            .iter()
+            .all(|r| r.is_ok()));
+        let peak = peak.load(Ordering::SeqCst);
+        assert!(peak > 1 && peak <= OLLAMA_BATCH_CONCURRENCY, "peak {peak}");
+    }
+
+    #[test]
+    fn test_openai_request_body_sends_dimensions_when_set() {
+        let mut provider = OpenAIEmbeddingProvider {
+            client: reqwest::Client::new(),
+            base_url: "http://localhost".into(),
+            api_key: "k".into(),
+            model: "text-embedding-3-large".into(),
+            dim: OnceLock::from(512),
+            auth: AuthMode::Bearer,
+            endpoint_path: None,
+            dimensions: Some(512),
+        };
+        assert_eq!(
+            serde_json::to_value(provider.request_body("hello")).unwrap(),
+            serde_json::json!({
+                "model": "text-embedding-3-large",
+                "input": "hello",
+                "dimensions": 512
+            })
+        );
+
+        provider.dimensions = None;
+        assert_eq!(
+            serde_json::to_value(provider.request_body("hello")).unwrap(),
+            serde_json::json!({"model": "text-embedding-3-large", "input": "hello"})
+        );
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_create_provider_azure_requires_endpoint() {
+        let mut cfg = base_config();
+        cfg.embedding_provider = Some("azure".into());
+        cfg.embedding_api_key = Some("azure-key".into());
+        cfg.embedding_model = Some("my-embeddings".into());
+        assert!(create_provider(&cfg).is_none());
+
+        cfg.embedding_base_url = Some("https://res.openai.azure.com".into());
+        let provider = create_provider(&cfg).expect("azure provider");
+        assert_eq!(provider.model(), "my-embeddings");
+        // A deployment name says nothing about size; it is learned on first use.
+        assert_eq!(provider.dimension(), 0);
+
+        cfg.embedding_dim = Some(1536);
+        assert_eq!(create_provider(&cfg).unwrap().dimension(), 1536);
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[tokio::test]
+    async fn test_precomputed_provider_serves_vectors_by_content_hash() {
+        let path =
+            std::env::temp_dir().join(format!("mc_precomputed_{}.json", uuid::Uuid::new_v4()));
+        let file = serde_json::json!({ content_hash("User lives in Tokyo"): [0.5, 0.5, 0.0] });
+        std::fs::write(&path, file.to_string()).unwrap();
+
+        let mut cfg = base_config();
+        cfg.embedding_provider = Some("precomputed".into());
+        assert!(create_provider(&cfg).is_none());
+        cfg.embedding_precomputed_path = Some(path.display().to_string());
+        let provider = create_provider(&cfg).expect("precomputed provider");
+        assert_eq!(provider.dimension(), 3);
+        assert_eq!(provider.model(), "precomputed");
+        assert_eq!(
+            provider.embed("User lives in Tokyo").await.unwrap(),
+            vec![0.5, 0.5, 0.0]
+        );
+        assert!(provider.embed("unknown text").await.is_err());
+
+        std::fs::write(&path, r#"{"a": [1.0, 0.0], "b": [1.0]}"#).unwrap();
+        assert!(PrecomputedEmbeddingProvider::load(&path, "m".into()).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_openai_dimension_detected_from_first_response() {
+        let provider = OpenAIEmbeddingProvider {
+            client: reqwest::Client::new(),
+            base_url: "http://localhost".into(),
+            api_key: "k".into(),
+            model: "gateway-embed".into(),
+            dim: OnceLock::new(),
+            auth: AuthMode::Bearer,
+            endpoint_path: None,
+            dimensions: None,
+        };
+        assert_eq!(provider.dimension(), 0);
+        provider.check_dimension(&[0.1, 0.2, 0.3]).unwrap();
+        assert_eq!(provider.dimension(), 3);
+        assert!(provider.check_dimension(&[0.1, 0.2]).is_err());
+        assert!(provider.check_dimension(&[]).is_err());
+        assert_eq!(provider.dimension(), 3);
+    }
+
+    #[test]
+    fn test_openai_embeddings_url_template() {
+        let mut provider = OpenAIEmbeddingProvider {
+            client: reqwest::Client::new(),
+            base_url: "https://gw.example.com/".into(),
+            api_key: "k".into(),
+            model: "embed-small".into(),
+            dim: OnceLock::from(8),
+            auth: AuthMode::Bearer,
+            endpoint_path: None,
+            dimensions: None,
+        };
+        assert_eq!(
+            provider.embeddings_url(),
+            "https://gw.example.com/embeddings"
+        );
+
+        provider.endpoint_path =
+            Some("/openai/deployments/{model}/embeddings?api-version=2024-02-01".into());
+        assert_eq!(
+            provider.embeddings_url(),
+            "https://gw.example.com/openai/deployments/embed-small/embeddings?api-version=2024-02-01"
+        );
+
+        provider.endpoint_path = Some("https://other.example.com/v2/{model}/embed".into());
+        assert_eq!(
+            provider.embeddings_url(),
+            "https://other.example.com/v2/embed-small/embed"
+        );
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[test]
+    fn test_create_provider_gemini_defaults() {
+        let mut cfg = base_config();
+        cfg.embedding_provider = Some("gemini".into());
+        assert!(
+            create_provider(&cfg).is_none(),
+            "gemini requires an api key"
+        );
+
+        cfg.embedding_api_key = Some("g-test".into());
+        let provider = create_provider(&cfg).expect("gemini provider");
+        assert_eq!(provider.model(), "text-embedding-004");
+        assert_eq!(provider.dimension(), 768);
+
+        cfg.embedding_dim = Some(256);
+        let provider = create_provider(&cfg).expect("gemini provider");
+        assert_eq!(provider.dimension(), 256);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_queue_flush_drains_jobs() {
+        let db = Arc::new(Database::new(microclaw_storage::db::IN_MEMORY_DATA_DIR).unwrap());
+        #[cfg(feature = "sqlite-vec")]
+        db.prepare_vector_index(1).unwrap();
+        let id = db
+            .insert_memory(Some(1), "queued memory", "KNOWLEDGE")
+            .unwrap();
+
+        let queue = EmbeddingQueue::spawn(db.clone(), Arc::new(CountingProvider));
+        queue.enqueue(id, "queued memory".into());
+        queue.flush().await;
+
+        assert_eq!(queue.depth(), 0);
+        let mem = db.get_memory_by_id(id).unwrap().unwrap();
+        assert_eq!(mem.embedding_model.as_deref(), Some("counting"));
+    }
+
+    struct FlakyProvider {
+        calls: AtomicU32,
+        fail: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for FlakyProvider {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.fail.load(Ordering::SeqCst) {
+                Err(anyhow!("provider down"))
+            } else {
+                Ok(vec![1.0])
+            }
+        }
+
+        fn model(&self) -> &str {
+            TEST_MODEL
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    struct RejectsBadProvider;
+
+    #[async_trait]
+    impl EmbeddingProvider for RejectsBadProvider {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            if text.contains("bad") {
+                Err(anyhow!("rejected input"))
+            } else {
+                Ok(vec![text.len() as f32])
+            }
+        }
+
+        fn model(&self) -> &str {
+            TEST_MODEL
+        }
+
+        fn dimension(&self) -> usize {
+            1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embed_batch_partial_keeps_successful_items() {
+        let texts = vec!["one".to_string(), "bad".to_string(), "three".to_string()];
+        assert!(RejectsBadProvider.embed_batch(&texts).await.is_err());
+
+        let results = RejectsBadProvider.embed_batch_partial(&texts).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &vec![3.0]);
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), &vec![5.0]);
+    }
+
+    #[tokio::test]
+    async fn test_wrappers_forward_partial_batches() {
+        let texts = vec!["one".to_string(), "bad".to_string(), "three".to_string()];
+        let picky: Arc<dyn EmbeddingProvider> = Arc::new(RejectsBadProvider);
+        let breaker = CircuitBreakerProvider::new(picky.clone(), 1, Duration::from_secs(60));
+        let results = breaker.embed_batch_partial(&texts).await;
+        assert!(results[0].is_ok() && results[1].is_err() && results[2].is_ok());
+        // One rejected item is not an outage.
+        assert!(!breaker.is_degraded());
+
+        let limited =
+            RateLimitedEmbeddingProvider::new(picky.clone(), 0, 0, Duration::from_secs(1));
+        let results = limited.embed_batch_partial(&texts).await;
+        assert!(results[0].is_ok() && results[1].is_err() && results[2].is_ok());
+
+        let fallback =
+            FallbackEmbeddingProvider::new(vec![picky, Arc::new(FixedProvider(vec![9.0]))])
+                .unwrap();
+        let results = fallback.embed_batch_partial(&texts).await;
+        assert_eq!(results[0].as_ref().unwrap(), &vec![3.0]);
+        assert_eq!(results[1].as_ref().unwrap(), &vec![9.0]);
+        assert_eq!(results[2].as_ref().unwrap(), &vec![5.0]);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_provider_tries_next_on_failure() {
+        let primary = Arc::new(FlakyProvider {
+            calls: AtomicU32::new(0),
+            fail: std::sync::atomic::AtomicBool::new(true),
+        });
+        let fallback = FallbackEmbeddingProvider::new(vec![
+            primary.clone(),
+            Arc::new(FixedProvider(vec![2.0])),
+        ])
+        .unwrap();
+        assert_eq!(fallback.model(), TEST_MODEL);
+        assert_eq!(fallback.dimension(), 1);
+        assert_eq!(fallback.embed("a").await.unwrap(), vec![2.0]);
+
+        primary.fail.store(false, Ordering::SeqCst);
+        assert_eq!(fallback.embed("a").await.unwrap(), vec![1.0]);
+
+        assert!(FallbackEmbeddingProvider::new(vec![
+            primary,
+            Arc::new(FixedProvider(vec![1.0, 2.0])),
+        ])
+        .is_err());
+        assert!(FallbackEmbeddingProvider::new(Vec::new()).is_err());
+        // Same size, different model: the vectors would not be comparable.
+        assert!(FallbackEmbeddingProvider::new(vec![
+            Arc::new(FixedProvider(vec![1.0])),
+            Arc::new(CountingProvider),
+        ])
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_batch_checks_vector_size() {
+        let texts = vec!["a".to_string(), "b".to_string()];
+        let mut fallback = FallbackEmbeddingProvider {
+            providers: vec![Arc::new(FixedProvider(vec![1.0]))],
+            dim: OnceLock::from(2),
+        };
+        assert!(fallback.embed_batch(&texts).await.is_err());
+
+        fallback.providers[0] = Arc::new(FixedProvider(vec![1.0, 2.0]));
+        assert_eq!(fallback.embed_batch(&texts).await.unwrap().len(), 2);
+    }
+
+    /// Sized only by its responses, and never answers.
+    struct UnsizedDown;
+
+    #[async_trait]
+    impl EmbeddingProvider for UnsizedDown {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            Err(anyhow!("down"))
+        }
+
+        fn model(&self) -> &str {
+            TEST_MODEL
+        }
+
+        fn dimension(&self) -> usize {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_learns_dimension_when_primary_is_unsized() {
+        let fallback = FallbackEmbeddingProvider::new(vec![
+            Arc::new(UnsizedDown),
+            Arc::new(FixedProvider(vec![1.0, 2.0])),
+        ])
+        .unwrap();
+        assert_eq!(fallback.dimension(), 0);
+        assert_eq!(fallback.embed("a").await.unwrap(), vec![1.0, 2.0]);
+        assert_eq!(fallback.dimension(), 2);
+        #[cfg(feature = "sqlite-vec")]
+        assert_eq!(detect_dimension(&fallback).await, Some(2));
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[tokio::test]
+    async fn test_prepare_vector_index_waits_for_a_known_dimension() {
+        let flaky = Arc::new(FlakyProvider {
+            calls: AtomicU32::new(0),
+            fail: std::sync::atomic::AtomicBool::new(true),
+        });
+        let provider =
+            FallbackEmbeddingProvider::new(vec![Arc::new(UnsizedDown), flaky.clone()]).unwrap();
+        let db = Database::new(microclaw_storage::db::IN_MEMORY_DATA_DIR).unwrap();
+        let config = Config::test_defaults();
+        let id = db.insert_memory(Some(1), "fact", "KNOWLEDGE").unwrap();
+
+        assert!(!prepare_vector_index(&db, Some(&provider), &config).await);
+        assert!(db.upsert_memory_vec(id, &[1.0]).is_err());
+
+        flaky.fail.store(false, Ordering::SeqCst);
+        assert!(prepare_vector_index(&db, Some(&provider), &config).await);
+        assert!(db.upsert_memory_vec(id, &[1.0]).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_and_recovers() {
+        let inner = Arc::new(FlakyProvider {
+            calls: AtomicU32::new(0),
+            fail: std::sync::atomic::AtomicBool::new(true),
+        });
+        let breaker = CircuitBreakerProvider::new(inner.clone(), 2, Duration::from_millis(50));
+
+        assert!(breaker.embed("a").await.is_err());
+        assert!(!breaker.is_degraded());
+        assert!(breaker.embed("a").await.is_err());
+        assert!(breaker.is_degraded());
+
+        // Open breaker short-circuits without reaching the provider.
+        assert!(breaker.embed("a").await.is_err());
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+        inner.fail.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(breaker.embed("a").await.unwrap(), vec![1.0]);
+        assert!(!breaker.is_degraded());
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_provider_paces_requests() {
+        let inner = Arc::new(FlakyProvider {
+            calls: AtomicU32::new(0),
+            fail: std::sync::atomic::AtomicBool::new(false),
+        });
+        let limited =
+            RateLimitedEmbeddingProvider::new(inner.clone(), 2, 0, Duration::from_millis(200));
+
+        let started = Instant::now();
+        limited.embed("a").await.unwrap();
+        limited.embed("b").await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        // The third call waits for one request's worth of refill (~100ms).
+        limited.embed("c").await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(80));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_batch_charges_per_text_without_batch_endpoint() {
+        let inner = Arc::new(FlakyProvider {
+            calls: AtomicU32::new(0),
+            fail: std::sync::atomic::AtomicBool::new(false),
+        });
+        let limited =
+            RateLimitedEmbeddingProvider::new(inner.clone(), 2, 0, Duration::from_millis(200));
+
+        // Three texts are three requests to a provider without a batch
+        // endpoint, so the third waits for refill.
+        let started = Instant::now();
+        let texts = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_eq!(limited.embed_batch(&texts).await.unwrap().len(), 3);
+        assert!(started.elapsed() >= Duration::from_millis(80));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_rate_bucket_clamps_oversized_cost() {
+        let mut bucket = RateBucket::new(100, Duration::from_secs(1)).unwrap();
+        assert_eq!(bucket.wait_for(500.0), Duration::ZERO);
+        bucket.take(500.0);
+        assert_eq!(bucket.available, 0.0);
+        assert!(bucket.wait_for(50.0) >= Duration::from_millis(499));
+        assert!(RateBucket::new(0, Duration::from_secs(1)).is_none());
+    }
+
+    #[test]
+    fn test_recall_cache_hits_normalized_query_and_invalidates() {
+        let cache = RecallCache::new(2, Duration::from_secs(60));
+        cache.insert(1, "Deploy  Target", 7, vec![(10, 0.1)]);
+        assert_eq!(cache.get(1, "deploy target", 7), Some(vec![(10, 0.1)]));
+        assert_eq!(cache.get(2, "deploy target", 7), None);
+
+        // A different fingerprint means memories changed since caching.
+        assert_eq!(cache.get(1, "deploy target", 8), None);
+        assert!(cache.is_empty());
+
+        cache.insert(1, "a", 1, vec![]);
+        cache.insert(1, "b", 1, vec![]);
+        assert!(cache.get(1, "a", 1).is_some());
+        cache.insert(1, "c", 1, vec![]);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(1, "b", 1).is_none());
+
+        let expired = RecallCache::new(4, Duration::ZERO);
+        expired.insert(1, "a", 1, vec![]);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(expired.get(1, "a", 1).is_none());
     }
 }
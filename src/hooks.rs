@@ -418,18 +418,31 @@ async fn run_hook_command(
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .env("MICROCLAW_HOOK_EVENT", event.as_str())
-        .env("MICROCLAW_HOOK_NAME", &hook.name);
+        .env("MICROCLAW_HOOK_NAME", &hook.name)
+        .kill_on_drop(true);
 
+    let body = serde_json::to_vec(payload)?;
+    if body.len() > max_input_bytes {
+        return Err(anyhow!("hook input exceeds max bytes"));
+    }
     let mut child = command.spawn()?;
-    if let Some(stdin) = child.stdin.as_mut() {
-        let body = serde_json::to_vec(payload)?;
-        if body.len() > max_input_bytes {
-            return Err(anyhow!("hook input exceeds max bytes"));
+    // The write is inside the timeout: a hook that never reads would
+    // otherwise block a payload larger than the pipe buffer forever.
+    let run = async {
+        if let Some(mut stdin) = child.stdin.take() {
+            match stdin.write_all(&body).await {
+                Ok(()) => {}
+                // The hook exited without reading its input; its output still counts.
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+                Err(e) => return Err(anyhow!("failed to write hook input: {e}")),
+            }
+            // Close the pipe so hooks that read until EOF can proceed.
+            drop(stdin);
         }
-        stdin.write_all(&body).await?;
-    }
+        child.wait_with_output().await.map_err(anyhow::Error::from)
+    };
     let timeout = Duration::from_millis(hook.timeout_ms.clamp(10, 120_000));
-    let output = tokio::time::timeout(timeout, child.wait_with_output())
+    let output = tokio::time::timeout(timeout, run)
         .await
         .map_err(|_| anyhow!("hook timed out after {}ms", hook.timeout_ms))??;
     if !output.status.success() {
@@ -712,4 +725,41 @@ fi
             _ => panic!("expected allow after disable"),
         }
     }
+
+    #[tokio::test]
+    async fn test_hook_that_ignores_large_input_still_runs() {
+        let root = std::env::temp_dir().join(format!("hook_stdin_{}", uuid::Uuid::new_v4()));
+        let hooks_dir = root.join("hooks");
+        let hook_dir = hooks_dir.join("no-read");
+        std::fs::create_dir_all(&hook_dir).unwrap();
+        std::fs::write(
+            hook_dir.join("HOOK.md"),
+            r#"---
+name: no-read
+description: never reads stdin
+events: [BeforeToolCall]
+command: "sleep 0.2; echo '{\"action\":\"block\",\"reason\":\"ignored input\"}'"
+enabled: true
+timeout_ms: 2000
+---
+"#,
+        )
+        .unwrap();
+
+        let manager =
+            HookManager::from_test_paths(hooks_dir, root.join("runtime/hooks_state.json"));
+        // Larger than a pipe buffer, so the write cannot complete up front.
+        let outcome = manager
+            .run(
+                HookEvent::BeforeToolCall,
+                json!({"tool_name":"bash","tool_input":{"cmd":"x".repeat(100_000)}}),
+            )
+            .await
+            .unwrap();
+        match outcome {
+            HookOutcome::Block { reason } => assert_eq!(reason, "ignored input"),
+            _ => panic!("expected block"),
+        }
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
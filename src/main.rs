@@ -120,8 +120,8 @@ async fn reembed_memories() -> anyhow::Result<()> {
     #[cfg(feature = "sqlite-vec")]
     {
         use microclaw::embedding;
-        let runtime_data_dir = config.runtime_data_dir();
-        let db = db::Database::new(&runtime_data_dir)?;
+        let db = db::Database::open_file(&config.database_path())?;
+        db.set_busy_timeout(std::time::Duration::from_millis(config.db_busy_timeout_ms))?;
 
         let provider = embedding::create_provider(&config);
         let provider = match provider {
@@ -132,8 +132,19 @@ async fn reembed_memories() -> anyhow::Result<()> {
             }
         };
 
-        let dim = provider.dimension();
-        db.prepare_vector_index(dim)?;
+        let Some(dim) = embedding::detect_dimension(provider.as_ref()).await else {
+            eprintln!("Could not detect the embedding dimension: the provider did not respond.");
+            std::process::exit(1);
+        };
+        if let Some(change) = db.prepare_vector_index_with_metric(dim, config.vector_metric())? {
+            println!(
+                "Vector index settings changed (dimension {} -> {}, metric {} -> {}); rebuilding the index.",
+                change.previous,
+                change.current,
+                change.previous_metric.as_str(),
+                change.current_metric.as_str()
+            );
+        }
         println!("Embedding provider: {} ({}D)", provider.model(), dim);
 
         let memories = db.get_all_active_memories()?;
@@ -142,16 +153,21 @@ async fn reembed_memories() -> anyhow::Result<()> {
         let mut success = 0usize;
         let mut failed = 0usize;
         for (i, (id, content)) in memories.iter().enumerate() {
-            match provider.embed(content).await {
-                Ok(embedding) => {
-                    if let Err(e) = db.upsert_memory_vec(*id, &embedding) {
-                        eprintln!("  [{}] DB error: {}", id, e);
-                        failed += 1;
-                    } else {
+            match provider.embed_document(content).await {
+                Ok(embedding) => match db.upsert_memory_vec(*id, &embedding) {
+                    Ok(true) => {
                         let _ = db.update_memory_embedding_model(*id, provider.model());
                         success += 1;
                     }
-                }
+                    Ok(false) => {
+                        eprintln!("  [{}] Embed error: empty or zero vector", id);
+                        failed += 1;
+                    }
+                    Err(e) => {
+                        eprintln!("  [{}] DB error: {}", id, e);
+                        failed += 1;
+                    }
+                },
                 Err(e) => {
                     eprintln!("  [{}] Embed error: {}", id, e);
                     failed += 1;
@@ -253,10 +269,14 @@ async fn main() -> anyhow::Result<()> {
         logging::init_console_logging();
     }
 
-    let db = db::Database::new(&runtime_data_dir)?;
+    let db = db::Database::open_file(&config.database_path())?;
+    db.set_busy_timeout(std::time::Duration::from_millis(config.db_busy_timeout_ms))?;
     info!("Database initialized");
 
-    let memory_manager = memory::MemoryManager::new(&runtime_data_dir);
+    let memory_manager = memory::MemoryManager::new(&runtime_data_dir)
+        .with_base_dirs(config.memory_base_dirs.iter().map(String::as_str))
+        .with_file_cache(config.memory_file_cache)
+        .with_context_format(config.memory_context_format());
     info!("Memory manager initialized");
 
     let skill_manager = skills::SkillManager::from_skills_dir(&skills_data_dir);
@@ -1,14 +1,13 @@
+use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::anyhow;
-use tracing::info;
-#[cfg(feature = "sqlite-vec")]
-use tracing::warn;
+use tracing::{info, warn};
 
 use crate::channels::telegram::TelegramChannelConfig;
 use crate::channels::{DiscordAdapter, FeishuAdapter, SlackAdapter, TelegramAdapter};
-use crate::config::Config;
-use crate::embedding::EmbeddingProvider;
+use crate::config::{Config, WorkingDirCleanup};
+use crate::embedding::{EmbeddingProvider, EmbeddingQueue, RecallCache};
 use crate::hooks::HookManager;
 use crate::llm::LlmProvider;
 use crate::memory::MemoryManager;
@@ -27,6 +26,10 @@ pub struct AppState {
     pub hooks: Arc<HookManager>,
     pub llm: Box<dyn LlmProvider>,
     pub embedding: Option<Arc<dyn EmbeddingProvider>>,
+    /// Set when `embedding_async` is enabled and an embedding provider exists.
+    pub embedding_queue: Option<EmbeddingQueue>,
+    /// Set when `memory_recall_cache_size` is non-zero.
+    pub recall_cache: Option<RecallCache>,
     pub tools: ToolRegistry,
 }
 
@@ -41,16 +44,7 @@ pub async fn run(
     let llm = crate::llm::create_provider(&config);
     let embedding = crate::embedding::create_provider(&config);
     #[cfg(feature = "sqlite-vec")]
-    {
-        let dim = embedding
-            .as_ref()
-            .map(|e| e.dimension())
-            .or(config.embedding_dim)
-            .unwrap_or(1536);
-        if let Err(e) = db.prepare_vector_index(dim) {
-            warn!("Failed to initialize sqlite-vec index: {e}");
-        }
-    }
+    crate::embedding::prepare_vector_index(&db, embedding.as_deref(), &config).await;
 
     // Build channel registry from config
     let mut registry = ChannelRegistry::new();
@@ -114,6 +108,16 @@ pub async fn run(
 
     let channel_registry = Arc::new(registry);
 
+    if config.sandbox.working_dir_cleanup == WorkingDirCleanup::OnStartup {
+        match microclaw_tools::runtime::clear_tool_working_dirs(Path::new(&config.working_dir)) {
+            Ok(()) => info!(
+                "Cleared tool working directories under {}",
+                config.working_dir
+            ),
+            Err(e) => warn!("Failed to clear tool working directories: {e}"),
+        }
+    }
+
     let mut tools = ToolRegistry::new(&config, channel_registry.clone(), db.clone());
 
     for (server, tool_info) in mcp_manager.all_tools() {
@@ -121,6 +125,13 @@ pub async fn run(
     }
 
     let hooks = Arc::new(HookManager::from_config(&config).with_db(db.clone()));
+    let embedding_queue = match &embedding {
+        Some(provider) if config.embedding_async => {
+            Some(EmbeddingQueue::spawn(db.clone(), provider.clone()))
+        }
+        _ => None,
+    };
+    let recall_cache = RecallCache::from_config(&config);
 
     let state = Arc::new(AppState {
         config,
@@ -131,11 +142,14 @@ pub async fn run(
         hooks,
         llm,
         embedding,
+        embedding_queue,
+        recall_cache,
         tools,
     });
 
     crate::scheduler::spawn_scheduler(state.clone());
     crate::scheduler::spawn_reflector(state.clone());
+    crate::scheduler::spawn_db_backup(state.clone());
 
     if let Some(ref token) = discord_token {
         let discord_state = state.clone();
@@ -155,7 +155,7 @@ Rules:
 - Extract ONLY concrete facts, preferences, expertise, or notable events
 - IGNORE: greetings, small talk, unanswered questions, transient requests
 - Each memory < 100 characters, specific and concrete
-- Category must be exactly one of: PROFILE (user attributes/preferences), KNOWLEDGE (facts/expertise), EVENT (significant things that happened)
+- Category must be exactly one of: PROFILE (user attributes), PREFERENCE (likes, dislikes, preferred styles/tools), KNOWLEDGE (facts/expertise), EVENT (significant things that happened), TASK (ongoing commitments or to-dos)
 - If a new memory updates or supersedes an existing one, add "supersedes_id": <id> to replace it
 - Output ONLY valid JSON array: [{"content":"...","category":"PROFILE","supersedes_id":null}]
 - If nothing worth remembering: []
@@ -189,13 +189,17 @@ fn should_merge_duplicate(
     incoming_content: &str,
     incoming_category: &str,
 ) -> bool {
+    if existing.pinned {
+        return false;
+    }
     if existing.is_archived {
         return true;
     }
     if existing.content.eq_ignore_ascii_case(incoming_content) {
         return false;
     }
-    if incoming_category == "PROFILE" && existing.category != "PROFILE" {
+    let profile = memory_quality::MemoryCategory::Profile.as_str();
+    if incoming_category == profile && existing.category != profile {
         return true;
     }
     incoming_content.len() > existing.content.len() + 8
@@ -242,33 +246,97 @@ async fn upsert_memory_embedding(
         Some(p) => p,
         None => return Ok(()),
     };
+    use tracing::Instrument;
+
+    let span = tracing::info_span!("memory_embedding_upsert", memory_id);
     let model_name = provider.model().to_string();
-    let embedding = provider.embed(content).await.map_err(|_| ())?;
+    let embedding = provider
+        .embed_document(content)
+        .instrument(span.clone())
+        .await
+        .map_err(|_| ())?;
+    let content = content.to_string();
     call_blocking(state.db.clone(), move |db| {
-        db.upsert_memory_vec(memory_id, &embedding)?;
-        db.update_memory_embedding_model(memory_id, &model_name)?;
+        db.store_memory_embedding_if_current(memory_id, &content, &embedding, &model_name)?;
         Ok(())
     })
+    .instrument(span)
     .await
     .map_err(|_| ())
 }
 
+/// Embed up to `batch_size` active memories that have no embedding yet (e.g.
+/// stored before a provider was configured). Rows are picked by
+/// `embedding_model IS NULL`, so repeated calls resume where the last one
+/// stopped. Items the provider rejected are retried later, behind rows that
+/// have not failed yet, so a few bad rows can't starve the rest. Returns how
+/// many rows were filled; errors only when every item failed.
 #[cfg(feature = "sqlite-vec")]
-async fn backfill_embeddings(state: &Arc<AppState>) {
-    if state.embedding.is_none() {
-        return;
+pub(crate) async fn backfill_embeddings(
+    db: &Arc<microclaw_storage::db::Database>,
+    provider: &dyn crate::embedding::EmbeddingProvider,
+    batch_size: usize,
+) -> anyhow::Result<usize> {
+    let batch_size = batch_size.max(1);
+    let pending = call_blocking(db.clone(), move |db| {
+        db.get_memories_without_embedding(None, batch_size)
+    })
+    .await?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    let texts: Vec<String> = pending
+        .iter()
+        .map(|m| m.retrieval_text().to_string())
+        .collect();
+    let mut results = provider.embed_documents_partial(&texts).await;
+    let failed_ids: Vec<i64> = pending
+        .iter()
+        .zip(&results)
+        .filter(|(_, r)| r.is_err())
+        .map(|(m, _)| m.id)
+        .collect();
+    let failed = failed_ids.len();
+    if failed > 0 {
+        call_blocking(db.clone(), move |db| {
+            for id in &failed_ids {
+                db.record_embedding_failure(*id)?;
+            }
+            Ok(())
+        })
+        .await?;
+    }
+    if failed == results.len() {
+        if let Some(Err(e)) = results.pop() {
+            return Err(e);
+        }
     }
-    let pending = match call_blocking(state.db.clone(), move |db| {
-        db.get_memories_without_embedding(None, 50)
+    if failed > 0 {
+        tracing::warn!(
+            "Embedding backfill: {failed} of {} items failed",
+            results.len()
+        );
+    }
+    let model = provider.model().to_string();
+    call_blocking(db.clone(), move |db| {
+        let mut filled = 0;
+        for (memory, result) in pending.iter().zip(&results) {
+            let Ok(embedding) = result else {
+                continue;
+            };
+            if db.store_memory_embedding_if_current(
+                memory.id,
+                &memory.retrieval_text(),
+                embedding,
+                &model,
+            )? {
+                filled += 1;
+            }
+        }
+        Ok(filled)
     })
     .await
-    {
-        Ok(rows) => rows,
-        Err(_) => return,
-    };
-    for mem in pending {
-        let _ = upsert_memory_embedding(state, mem.id, &mem.content).await;
-    }
+    .map_err(Into::into)
 }
 
 pub fn spawn_reflector(state: Arc<AppState>) {
@@ -289,9 +357,74 @@ pub fn spawn_reflector(state: Arc<AppState>) {
     });
 }
 
+/// Periodically write an online backup of the database into
+/// `database_backup_dir`, keeping the newest `db_backup_keep` files.
+pub fn spawn_db_backup(state: Arc<AppState>) {
+    let hours = state.config.db_backup_interval_hours;
+    if hours == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        info!("Database backups started (interval: {hours}h)");
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(hours * 3600)).await;
+            run_db_backup(&state).await;
+        }
+    });
+}
+
+async fn run_db_backup(state: &Arc<AppState>) {
+    let dir = state.config.database_backup_dir();
+    let dest = dir.join(format!(
+        "{BACKUP_FILE_PREFIX}{}.db",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    let target = dest.clone();
+    if let Err(e) = call_blocking(state.db.clone(), move |db| db.backup_to(&target)).await {
+        error!("Database backup to {} failed: {e}", dest.display());
+        return;
+    }
+    info!("Database backed up to {}", dest.display());
+    if let Err(e) = prune_backups(&dir, state.config.db_backup_keep) {
+        error!("Failed to prune old database backups: {e}");
+    }
+}
+
+const BACKUP_FILE_PREFIX: &str = "microclaw-";
+
+/// Delete all but the newest `keep` backups in `dir`. Names embed a sortable
+/// timestamp, so lexical order is age order. Returns how many were removed.
+fn prune_backups(dir: &std::path::Path, keep: usize) -> std::io::Result<usize> {
+    let mut backups: Vec<std::path::PathBuf> = std::fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(BACKUP_FILE_PREFIX) && n.ends_with(".db"))
+        })
+        .collect();
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep);
+    for path in &backups[..excess] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(excess)
+}
+
 async fn run_reflector(state: &Arc<AppState>) {
     #[cfg(feature = "sqlite-vec")]
-    backfill_embeddings(state).await;
+    if let Some(provider) = &state.embedding {
+        // The startup probe may have failed before the provider's vector
+        // size was known; build the index as soon as it is.
+        crate::embedding::prepare_vector_index(&state.db, Some(provider.as_ref()), &state.config)
+            .await;
+        match backfill_embeddings(&state.db, provider.as_ref(), 50).await {
+            Ok(0) => {}
+            Ok(filled) => info!("Reflector: backfilled {filled} memory embeddings"),
+            Err(e) => tracing::warn!("Reflector: embedding backfill failed: {e}"),
+        }
+    }
 
     let _ = call_blocking(state.db.clone(), move |db| db.archive_stale_memories(30)).await;
 
@@ -520,14 +653,13 @@ async fn reflect_for_chat(state: &Arc<AppState>, chat_id: i64) {
             Some(s) => s,
             None => continue,
         };
-        let category = item
-            .get("category")
-            .and_then(|v| v.as_str())
-            .unwrap_or("KNOWLEDGE")
-            .to_ascii_uppercase();
-        if !matches!(category.as_str(), "PROFILE" | "KNOWLEDGE" | "EVENT") {
-            continue;
-        }
+        let category = match item.get("category").and_then(|v| v.as_str()).map_or(
+            Some(memory_quality::MemoryCategory::Knowledge),
+            memory_quality::MemoryCategory::parse,
+        ) {
+            Some(c) => c.as_str().to_string(),
+            None => continue,
+        };
         let content = match memory_quality::normalize_memory_content(content, 180) {
             Some(c) => c,
             None => continue,
@@ -543,6 +675,10 @@ async fn reflect_for_chat(state: &Arc<AppState>, chat_id: i64) {
         // If the LLM flagged an existing memory to supersede, update it
         let supersedes_id = item.get("supersedes_id").and_then(|v| v.as_i64());
         if let Some(sid) = supersedes_id {
+            if existing.iter().any(|m| m.id == sid && m.pinned) {
+                skipped += 1;
+                continue;
+            }
             if existing.iter().any(|m| m.id == sid) {
                 let content = content.to_string();
                 let category = category.to_string();
@@ -567,7 +703,8 @@ async fn reflect_for_chat(state: &Arc<AppState>, chat_id: i64) {
         let topic_key = memory_quality::memory_topic_key(&content);
         if let Some(prev_id) = topic_latest.get(&topic_key).copied() {
             if let Some(prev) = existing_by_id.get(&prev_id) {
-                if !prev.content.eq_ignore_ascii_case(&content)
+                if !prev.pinned
+                    && !prev.content.eq_ignore_ascii_case(&content)
                     && !jaccard_similar(&prev.content, &content, 0.85)
                 {
                     let new_content = content.to_string();
@@ -602,14 +739,14 @@ async fn reflect_for_chat(state: &Arc<AppState>, chat_id: i64) {
             #[cfg(feature = "sqlite-vec")]
             {
                 if let Some(provider) = &state.embedding {
-                    if let Ok(query_vec) = provider.embed(&content).await {
+                    if let Ok(query_vec) = provider.embed_document(&content).await {
                         let nearest = call_blocking(state.db.clone(), move |db| {
                             db.knn_memories(chat_id, &query_vec, 1)
                         })
                         .await
                         .ok()
                         .and_then(|rows| rows.first().copied());
-                        nearest.and_then(|(id, dist)| if dist < 0.15 { Some(id) } else { None })
+                        nearest.and_then(|(id, sim)| if sim > 0.85 { Some(id) } else { None })
                     } else {
                         seen_contents
                             .iter()
@@ -722,6 +859,22 @@ async fn reflect_for_chat(state: &Arc<AppState>, chat_id: i64) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_prune_backups_keeps_newest() {
+        let dir = std::env::temp_dir().join(format!("mc_backups_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for stamp in ["20260101-000000", "20260102-000000", "20260103-000000"] {
+            std::fs::write(dir.join(format!("microclaw-{stamp}.db")), b"").unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        assert_eq!(prune_backups(&dir, 2).unwrap(), 1);
+        assert!(!dir.join("microclaw-20260101-000000.db").exists());
+        assert!(dir.join("microclaw-20260103-000000.db").exists());
+        assert!(dir.join("notes.txt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_jaccard_similar_identical() {
         assert!(jaccard_similar("hello world", "hello world", 0.5));
@@ -774,4 +927,125 @@ mod tests {
             "Ensure TOOLS.md rules are followed for every tool call"
         ));
     }
+
+    #[cfg(feature = "sqlite-vec")]
+    struct LengthEmbedding;
+
+    #[cfg(feature = "sqlite-vec")]
+    #[async_trait::async_trait]
+    impl crate::embedding::EmbeddingProvider for LengthEmbedding {
+        async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+            Ok(vec![text.len() as f32, 1.0])
+        }
+
+        fn model(&self) -> &str {
+            "length"
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[tokio::test]
+    async fn test_backfill_embeddings_is_resumable() {
+        let db = Arc::new(
+            microclaw_storage::db::Database::new(microclaw_storage::db::IN_MEMORY_DATA_DIR)
+                .unwrap(),
+        );
+        db.prepare_vector_index(2).unwrap();
+        for i in 0..3 {
+            db.insert_memory(Some(1), &format!("memory number {i}"), "KNOWLEDGE")
+                .unwrap();
+        }
+
+        assert_eq!(
+            backfill_embeddings(&db, &LengthEmbedding, 2).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            backfill_embeddings(&db, &LengthEmbedding, 2).await.unwrap(),
+            1
+        );
+        assert_eq!(
+            backfill_embeddings(&db, &LengthEmbedding, 2).await.unwrap(),
+            0
+        );
+        assert!(db
+            .get_memories_without_embedding(None, 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    struct RejectsBadEmbedding;
+
+    #[cfg(feature = "sqlite-vec")]
+    #[async_trait::async_trait]
+    impl crate::embedding::EmbeddingProvider for RejectsBadEmbedding {
+        async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+            if text.contains("bad") {
+                anyhow::bail!("rejected input");
+            }
+            Ok(vec![text.len() as f32, 1.0])
+        }
+
+        fn model(&self) -> &str {
+            "picky"
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[tokio::test]
+    async fn test_backfill_embeddings_stores_partial_batch() {
+        let db = Arc::new(
+            microclaw_storage::db::Database::new(microclaw_storage::db::IN_MEMORY_DATA_DIR)
+                .unwrap(),
+        );
+        db.prepare_vector_index(2).unwrap();
+        for content in ["good memory one", "bad memory", "good memory two"] {
+            db.insert_memory(Some(1), content, "KNOWLEDGE").unwrap();
+        }
+
+        assert_eq!(
+            backfill_embeddings(&db, &RejectsBadEmbedding, 10)
+                .await
+                .unwrap(),
+            2
+        );
+        let pending = db.get_memories_without_embedding(None, 10).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].content, "bad memory");
+        assert!(backfill_embeddings(&db, &RejectsBadEmbedding, 10)
+            .await
+            .is_err());
+    }
+
+    #[cfg(feature = "sqlite-vec")]
+    #[tokio::test]
+    async fn test_backfill_embeddings_moves_past_rejected_rows() {
+        let db = Arc::new(
+            microclaw_storage::db::Database::new(microclaw_storage::db::IN_MEMORY_DATA_DIR)
+                .unwrap(),
+        );
+        db.prepare_vector_index(2).unwrap();
+        db.insert_memory(Some(1), "good memory", "KNOWLEDGE")
+            .unwrap();
+        for content in ["bad memory one", "bad memory two"] {
+            db.insert_memory(Some(1), content, "KNOWLEDGE").unwrap();
+        }
+
+        // Whichever rows the first batch picks, rejected ones sink behind
+        // the untried row, so the second batch reaches it.
+        let _ = backfill_embeddings(&db, &RejectsBadEmbedding, 2).await;
+        let _ = backfill_embeddings(&db, &RejectsBadEmbedding, 2).await;
+        let pending = db.get_memories_without_embedding(None, 10).unwrap();
+        assert_eq!(pending.len(), 2);
+        assert!(pending.iter().all(|m| m.content.starts_with("bad")));
+    }
 }
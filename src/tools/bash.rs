@@ -7,14 +7,170 @@ use tracing::info;
 use crate::config::WorkingDirIsolation;
 use microclaw_core::llm_types::ToolDefinition;
 use microclaw_core::text::floor_char_boundary;
-use microclaw_tools::sandbox::{SandboxExecOptions, SandboxRouter};
+use microclaw_tools::sandbox::{
+    changed_files, clear_dir_contents, dir_size_bytes, snapshot_files, FileSnapshot,
+    SandboxExecOptions, SandboxRouter, WorkingDirCleanup,
+};
+
+use super::{schema_object, Artifact, Tool, ToolResult};
+
+const MAX_STREAM_BYTES: usize = 30000;
+/// Cap on files reported as artifacts from a single command.
+const MAX_ARTIFACTS: usize = 20;
+
+/// Cap a captured stdout/stderr stream at `MAX_STREAM_BYTES` on a char boundary.
+pub(super) fn truncate_output(mut text: String) -> String {
+    if text.len() > MAX_STREAM_BYTES {
+        let cutoff = floor_char_boundary(&text, MAX_STREAM_BYTES);
+        text.truncate(cutoff);
+        text.push_str("\n... (output truncated)");
+    }
+    text
+}
+
+/// Working directory rules shared by the command-running tools: a size
+/// quota, optional cleanup after each run, and reporting the files a run
+/// wrote as artifacts.
+#[derive(Clone, Copy)]
+pub(super) struct WorkingDirPolicy {
+    pub(super) isolation: WorkingDirIsolation,
+    quota_bytes: Option<u64>,
+    cleanup: WorkingDirCleanup,
+}
+
+impl WorkingDirPolicy {
+    pub(super) fn new(isolation: WorkingDirIsolation) -> Self {
+        Self {
+            isolation,
+            quota_bytes: None,
+            cleanup: WorkingDirCleanup::Never,
+        }
+    }
+
+    pub(super) fn with_quota_mb(mut self, quota_mb: Option<u64>) -> Self {
+        self.quota_bytes = quota_mb.map(|mb| mb.saturating_mul(1024 * 1024));
+        self
+    }
 
-use super::{schema_object, Tool, ToolResult};
+    pub(super) fn with_cleanup(mut self, cleanup: WorkingDirCleanup) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    pub(super) async fn cleanup_after_run(&self, working_dir: &std::path::Path) {
+        if self.cleanup != WorkingDirCleanup::AfterEachRun {
+            return;
+        }
+        let dir = working_dir.to_path_buf();
+        match tokio::task::spawn_blocking(move || clear_dir_contents(&dir)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!(
+                "Failed to clean working directory {}: {e}",
+                working_dir.display()
+            ),
+            Err(e) => tracing::warn!("Working directory cleanup task failed: {e}"),
+        }
+    }
+
+    /// Stamp the files under `working_dir` before a run, if artifacts are
+    /// tracked. Only a chat's own directory is watched: a shared one also
+    /// changes under other chats' commands, whose files would be reported as
+    /// ours. A directory wiped after each run has nothing left to report.
+    pub(super) async fn snapshot(&self, working_dir: &std::path::Path) -> Option<FileSnapshot> {
+        if self.isolation != WorkingDirIsolation::Chat
+            || self.cleanup == WorkingDirCleanup::AfterEachRun
+        {
+            return None;
+        }
+        let dir = working_dir.to_path_buf();
+        tokio::task::spawn_blocking(move || snapshot_files(&dir))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Files the command wrote under `working_dir`.
+    async fn collect_artifacts(
+        &self,
+        working_dir: &std::path::Path,
+        before: Option<FileSnapshot>,
+    ) -> Vec<Artifact> {
+        let Some(before) = before else {
+            return Vec::new();
+        };
+        let dir = working_dir.to_path_buf();
+        tokio::task::spawn_blocking(move || changed_files(&dir, &before, MAX_ARTIFACTS))
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(Artifact::from_path)
+            .collect()
+    }
+
+    pub(super) async fn quota_exceeded(&self, working_dir: &std::path::Path) -> Option<String> {
+        let quota = self.quota_bytes?;
+        let dir = working_dir.to_path_buf();
+        let used = tokio::task::spawn_blocking(move || dir_size_bytes(&dir))
+            .await
+            .unwrap_or(0);
+        (used > quota).then(|| {
+            format!(
+                "Working directory quota exceeded: {} uses {} bytes (limit {} bytes). Remove files before running more commands.",
+                working_dir.display(),
+                used,
+                quota
+            )
+        })
+    }
+
+    /// Build the tool result for a finished run: the JSON output body, the
+    /// quota check, and any artifacts written since `before`.
+    pub(super) async fn finish_run(
+        &self,
+        working_dir: &std::path::Path,
+        before: Option<FileSnapshot>,
+        exit_code: i32,
+        stdout: String,
+        stderr: String,
+        duration_ms: u128,
+    ) -> ToolResult {
+        let quota_msg = self.quota_exceeded(working_dir).await;
+        let summary = if let Some(msg) = &quota_msg {
+            format!("Exit code {exit_code}. {msg}")
+        } else if exit_code == 0 {
+            format!("Command completed with exit code {exit_code}")
+        } else {
+            format!("Exit code {exit_code}")
+        };
+        let content = json!({
+            "summary": summary,
+            "stdout": truncate_output(stdout),
+            "stderr": truncate_output(stderr),
+            "exit_code": exit_code,
+            "duration_ms": duration_ms,
+        })
+        .to_string();
+        let artifacts = self.collect_artifacts(working_dir, before).await;
+
+        let tool_result = if quota_msg.is_some() {
+            ToolResult::error(content)
+                .with_status_code(exit_code)
+                .with_error_type("quota_exceeded")
+        } else if exit_code == 0 {
+            ToolResult::success(content).with_status_code(exit_code)
+        } else {
+            ToolResult::error(content)
+                .with_status_code(exit_code)
+                .with_error_type("process_exit")
+        };
+        tool_result.with_artifacts(artifacts)
+    }
+}
 
 pub struct BashTool {
     working_dir: PathBuf,
-    working_dir_isolation: WorkingDirIsolation,
     sandbox_router: Option<Arc<SandboxRouter>>,
+    policy: WorkingDirPolicy,
 }
 
 impl BashTool {
@@ -28,8 +184,8 @@ impl BashTool {
     ) -> Self {
         Self {
             working_dir: PathBuf::from(working_dir),
-            working_dir_isolation,
             sandbox_router: None,
+            policy: WorkingDirPolicy::new(working_dir_isolation),
         }
     }
 
@@ -37,6 +193,19 @@ impl BashTool {
         self.sandbox_router = Some(router);
         self
     }
+
+    /// Reject runs once the resolved working directory grows past `quota_mb`.
+    pub fn with_working_dir_quota_mb(mut self, quota_mb: Option<u64>) -> Self {
+        self.policy = self.policy.with_quota_mb(quota_mb);
+        self
+    }
+
+    /// With `AfterEachRun`, empty the working directory once a command has
+    /// finished so the next run starts clean. Other policies are no-ops here.
+    pub fn with_working_dir_cleanup(mut self, cleanup: WorkingDirCleanup) -> Self {
+        self.policy = self.policy.with_cleanup(cleanup);
+        self
+    }
 }
 
 #[async_trait]
@@ -48,7 +217,7 @@ impl Tool for BashTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "bash".into(),
-            description: "Execute a bash command and return the output. Use for running shell commands, scripts, or system operations.".into(),
+            description: "Execute a bash command and return the output. Use for running shell commands, scripts, or system operations. Returns JSON with `summary`, `stdout`, `stderr`, `exit_code`, and `duration_ms` fields.".into(),
             input_schema: schema_object(
                 json!({
                     "command": {
@@ -76,7 +245,7 @@ impl Tool for BashTool {
             .and_then(|v| v.as_u64())
             .unwrap_or(120);
         let working_dir =
-            super::resolve_tool_working_dir(&self.working_dir, self.working_dir_isolation, &input);
+            super::resolve_tool_working_dir(&self.working_dir, self.policy.isolation, &input);
         if let Err(e) = tokio::fs::create_dir_all(&working_dir).await {
             return ToolResult::error(format!(
                 "Failed to create working directory {}: {e}",
@@ -84,6 +253,10 @@ impl Tool for BashTool {
             ));
         }
 
+        if let Some(msg) = self.policy.quota_exceeded(&working_dir).await {
+            return ToolResult::error(msg).with_error_type("quota_exceeded");
+        }
+
         info!("Executing bash: {}", command);
 
         let session_key = super::auth_context_from_input(&input)
@@ -93,47 +266,26 @@ impl Tool for BashTool {
             timeout: std::time::Duration::from_secs(timeout_secs),
             working_dir: Some(working_dir.clone()),
         };
+        let before = self.policy.snapshot(&working_dir).await;
+        let started = std::time::Instant::now();
         let result = if let Some(router) = &self.sandbox_router {
             router.exec(&session_key, command, &exec_opts).await
         } else {
             microclaw_tools::sandbox::exec_host_command(command, &exec_opts).await
         };
 
-        match result {
+        let tool_result = match result {
             Ok(output) => {
-                let stdout = output.stdout;
-                let stderr = output.stderr;
-                let exit_code = output.exit_code;
-
-                let mut result_text = String::new();
-                if !stdout.is_empty() {
-                    result_text.push_str(stdout.as_str());
-                }
-                if !stderr.is_empty() {
-                    if !result_text.is_empty() {
-                        result_text.push('\n');
-                    }
-                    result_text.push_str("STDERR:\n");
-                    result_text.push_str(stderr.as_str());
-                }
-                if result_text.is_empty() {
-                    result_text = format!("Command completed with exit code {exit_code}");
-                }
-
-                // Truncate very long output
-                if result_text.len() > 30000 {
-                    let cutoff = floor_char_boundary(&result_text, 30000);
-                    result_text.truncate(cutoff);
-                    result_text.push_str("\n... (output truncated)");
-                }
-
-                if exit_code == 0 {
-                    ToolResult::success(result_text).with_status_code(exit_code)
-                } else {
-                    ToolResult::error(format!("Exit code {exit_code}\n{result_text}"))
-                        .with_status_code(exit_code)
-                        .with_error_type("process_exit")
-                }
+                self.policy
+                    .finish_run(
+                        &working_dir,
+                        before,
+                        output.exit_code,
+                        output.stdout,
+                        output.stderr,
+                        started.elapsed().as_millis(),
+                    )
+                    .await
             }
             Err(e) => {
                 let msg = e.to_string();
@@ -145,7 +297,9 @@ impl Tool for BashTool {
                         .with_error_type("spawn_error")
                 }
             }
-        }
+        };
+        self.policy.cleanup_after_run(&working_dir).await;
+        tool_result
     }
 }
 
@@ -199,8 +353,11 @@ mod tests {
         let tool = BashTool::new(".");
         let result = tool.execute(json!({"command": stderr_command()})).await;
         assert!(!result.is_error); // exit code is 0
-        assert!(result.content.contains("STDERR"));
-        assert!(result.content.contains("err"));
+        let body: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert!(body["stderr"].as_str().unwrap().contains("err"));
+        assert_eq!(body["stdout"], "");
+        assert_eq!(body["exit_code"], 0);
+        assert!(body["duration_ms"].is_u64());
     }
 
     #[tokio::test]
@@ -221,6 +378,85 @@ mod tests {
         assert!(result.content.contains("Missing 'command'"));
     }
 
+    #[tokio::test]
+    async fn test_bash_working_dir_quota_blocks_runs() {
+        let root = std::env::temp_dir().join(format!("microclaw_bash_{}", uuid::Uuid::new_v4()));
+        let shared = root.join("shared");
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::write(shared.join("big.bin"), vec![0u8; 2 * 1024 * 1024]).unwrap();
+
+        let tool = BashTool::new(root.to_str().unwrap()).with_working_dir_quota_mb(Some(1));
+        let result = tool.execute(json!({"command": "echo hi"})).await;
+        assert!(result.is_error);
+        assert_eq!(result.error_type.as_deref(), Some("quota_exceeded"));
+        assert!(result.content.contains("quota exceeded"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_bash_cleanup_after_each_run_empties_working_dir() {
+        let root = std::env::temp_dir().join(format!("microclaw_bash_{}", uuid::Uuid::new_v4()));
+        let tool = BashTool::new(root.to_str().unwrap())
+            .with_working_dir_cleanup(WorkingDirCleanup::AfterEachRun);
+        let result = tool.execute(json!({"command": "echo hi > out.txt"})).await;
+        assert!(!result.is_error, "{}", result.content);
+
+        let shared = root.join("shared");
+        assert!(shared.is_dir());
+        assert_eq!(std::fs::read_dir(&shared).unwrap().count(), 0);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_bash_reports_written_files_as_artifacts() {
+        let root = std::env::temp_dir().join(format!("microclaw_bash_{}", uuid::Uuid::new_v4()));
+        let chat_dir = root.join("chat").join("telegram").join("100");
+        std::fs::create_dir_all(&chat_dir).unwrap();
+        std::fs::write(chat_dir.join("existing.txt"), "old").unwrap();
+        let auth =
+            json!({"caller_channel": "telegram", "caller_chat_id": 100, "control_chat_ids": []});
+
+        let tool = BashTool::new_with_isolation(root.to_str().unwrap(), WorkingDirIsolation::Chat);
+        let result = tool
+            .execute(json!({"command": "echo hi > plot.png", "__microclaw_auth": auth}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(result.artifacts.len(), 1);
+        assert_eq!(result.artifacts[0].path, chat_dir.join("plot.png"));
+        assert_eq!(result.artifacts[0].mime, "image/png");
+        assert_eq!(result.artifacts[0].label, "plot.png");
+
+        let result = tool
+            .execute(json!({"command": "echo hi", "__microclaw_auth": auth}))
+            .await;
+        assert!(result.artifacts.is_empty());
+
+        // Other chats write to a shared dir too, so nothing is reported there.
+        let shared = BashTool::new(root.to_str().unwrap());
+        let result = shared
+            .execute(json!({"command": "echo hi > plot.png"}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert!(result.artifacts.is_empty());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_truncate_output_cuts_on_char_boundary() {
+        // One ASCII byte shifts every two-byte char so the cap lands mid-char.
+        let text = format!("a{}", "é".repeat(MAX_STREAM_BYTES));
+        let out = truncate_output(text);
+        let kept = out.strip_suffix("\n... (output truncated)").unwrap();
+        assert_eq!(kept.len(), MAX_STREAM_BYTES - 1);
+        assert!(kept.ends_with('é'));
+
+        let short = "héllo".to_string();
+        assert_eq!(truncate_output(short.clone()), short);
+    }
+
     #[test]
     fn test_bash_tool_name_and_definition() {
         let tool = BashTool::new(".");
@@ -0,0 +1,256 @@
+use async_trait::async_trait;
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tracing::info;
+
+use crate::config::WorkingDirIsolation;
+use microclaw_core::llm_types::ToolDefinition;
+use microclaw_tools::sandbox::{wait_or_kill, WorkingDirCleanup};
+
+use super::bash::WorkingDirPolicy;
+use super::{schema_object, Tool, ToolResult};
+
+/// Runs a single program with an argument list, without a shell. Arguments
+/// are passed through verbatim, so no quoting or globbing applies.
+pub struct CommandTool {
+    working_dir: PathBuf,
+    policy: WorkingDirPolicy,
+}
+
+impl CommandTool {
+    pub fn new(working_dir: &str) -> Self {
+        Self::new_with_isolation(working_dir, WorkingDirIsolation::Shared)
+    }
+
+    pub fn new_with_isolation(
+        working_dir: &str,
+        working_dir_isolation: WorkingDirIsolation,
+    ) -> Self {
+        Self {
+            working_dir: PathBuf::from(working_dir),
+            policy: WorkingDirPolicy::new(working_dir_isolation),
+        }
+    }
+
+    /// Reject runs once the resolved working directory grows past `quota_mb`.
+    pub fn with_working_dir_quota_mb(mut self, quota_mb: Option<u64>) -> Self {
+        self.policy = self.policy.with_quota_mb(quota_mb);
+        self
+    }
+
+    /// With `AfterEachRun`, empty the working directory once a command has
+    /// finished so the next run starts clean. Other policies are no-ops here.
+    pub fn with_working_dir_cleanup(mut self, cleanup: WorkingDirCleanup) -> Self {
+        self.policy = self.policy.with_cleanup(cleanup);
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for CommandTool {
+    fn name(&self) -> &str {
+        "run_command"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "run_command".into(),
+            description: "Run a single program with arguments (no shell: no pipes, redirects, or globbing). Prefer this over bash when you just need one command. Returns JSON with `summary`, `stdout`, `stderr`, `exit_code`, and `duration_ms` fields.".into(),
+            input_schema: schema_object(
+                json!({
+                    "command": {
+                        "type": "string",
+                        "description": "Program to run, e.g. `git` or `ls`"
+                    },
+                    "args": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Arguments passed to the program verbatim"
+                    },
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "Timeout in seconds (default: 120)"
+                    }
+                }),
+                &["command"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let command = match input.get("command").and_then(|v| v.as_str()) {
+            Some(c) if !c.trim().is_empty() => c.trim(),
+            _ => return ToolResult::error("Missing 'command' parameter".into()),
+        };
+        let args: Vec<String> = match input.get("args") {
+            None | Some(serde_json::Value::Null) => Vec::new(),
+            Some(serde_json::Value::Array(items)) => {
+                match items
+                    .iter()
+                    .map(|v| v.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                {
+                    Some(args) => args,
+                    None => return ToolResult::error("'args' must be an array of strings".into()),
+                }
+            }
+            Some(_) => return ToolResult::error("'args' must be an array of strings".into()),
+        };
+        let timeout_secs = input
+            .get("timeout_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(120);
+
+        let working_dir =
+            super::resolve_tool_working_dir(&self.working_dir, self.policy.isolation, &input);
+        if let Err(e) = tokio::fs::create_dir_all(&working_dir).await {
+            return ToolResult::error(format!(
+                "Failed to create working directory {}: {e}",
+                working_dir.display()
+            ));
+        }
+
+        if let Some(msg) = self.policy.quota_exceeded(&working_dir).await {
+            return ToolResult::error(msg).with_error_type("quota_exceeded");
+        }
+
+        info!("Executing command: {} {:?}", command, args);
+
+        let mut cmd = tokio::process::Command::new(command);
+        cmd.args(&args)
+            .current_dir(&working_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        // Lead a new process group so a timeout also kills anything it forks.
+        #[cfg(unix)]
+        cmd.process_group(0);
+        let before = self.policy.snapshot(&working_dir).await;
+        let started = std::time::Instant::now();
+        let child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                return ToolResult::error(format!("Failed to start '{command}': {e}"))
+                    .with_error_type("spawn_error")
+            }
+        };
+        let tool_result =
+            match wait_or_kill(child, std::time::Duration::from_secs(timeout_secs)).await {
+                Ok(Some(output)) => {
+                    self.policy
+                        .finish_run(
+                            &working_dir,
+                            before,
+                            output.status.code().unwrap_or(-1),
+                            String::from_utf8_lossy(&output.stdout).into_owned(),
+                            String::from_utf8_lossy(&output.stderr).into_owned(),
+                            started.elapsed().as_millis(),
+                        )
+                        .await
+                }
+                Err(e) => ToolResult::error(format!("Failed to run '{command}': {e}"))
+                    .with_error_type("spawn_error"),
+                Ok(None) => {
+                    ToolResult::error(format!("Command timed out after {timeout_secs} seconds"))
+                        .with_error_type("timeout")
+                }
+            };
+        self.policy.cleanup_after_run(&working_dir).await;
+        tool_result
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_command_passes_args_verbatim() {
+        let tool = CommandTool::new(".");
+        let result = tool
+            .execute(json!({"command": "echo", "args": ["a b", "$HOME", "*"]}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        let body: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(body["stdout"], "a b $HOME *\n");
+        assert_eq!(body["exit_code"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_command_nonzero_exit_and_missing_program() {
+        let tool = CommandTool::new(".");
+        let result = tool.execute(json!({"command": "false"})).await;
+        assert!(result.is_error);
+        assert_eq!(result.error_type.as_deref(), Some("process_exit"));
+
+        let result = tool
+            .execute(json!({"command": "microclaw-no-such-program"}))
+            .await;
+        assert!(result.is_error);
+        assert_eq!(result.error_type.as_deref(), Some("spawn_error"));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_timeout() {
+        let tool = CommandTool::new(".");
+        let result = tool
+            .execute(json!({"command": "sleep", "args": ["10"], "timeout_secs": 1}))
+            .await;
+        assert!(result.is_error);
+        assert_eq!(result.error_type.as_deref(), Some("timeout"));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_timeout_kills_background_children() {
+        let root = std::env::temp_dir().join(format!("microclaw_cmd_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let tool = CommandTool::new(root.to_str().unwrap());
+        let result = tool
+            .execute(json!({
+                "command": "sh",
+                "args": ["-c", "(sleep 2 && touch finished) & wait"],
+                "timeout_secs": 1
+            }))
+            .await;
+        assert_eq!(result.error_type.as_deref(), Some("timeout"));
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+        assert!(!root.join("shared").join("finished").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_run_command_applies_working_dir_policy() {
+        let root = std::env::temp_dir().join(format!("microclaw_cmd_{}", uuid::Uuid::new_v4()));
+        let chat_dir = root.join("chat").join("telegram").join("100");
+        let auth =
+            json!({"caller_channel": "telegram", "caller_chat_id": 100, "control_chat_ids": []});
+
+        let tool =
+            CommandTool::new_with_isolation(root.to_str().unwrap(), WorkingDirIsolation::Chat)
+                .with_working_dir_quota_mb(Some(1));
+        let result = tool
+            .execute(json!({"command": "touch", "args": ["notes.txt"], "__microclaw_auth": auth}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(result.artifacts.len(), 1);
+        assert_eq!(result.artifacts[0].path, chat_dir.join("notes.txt"));
+
+        std::fs::write(chat_dir.join("big.bin"), vec![0u8; 2 * 1024 * 1024]).unwrap();
+        let result = tool
+            .execute(json!({"command": "true", "__microclaw_auth": auth}))
+            .await;
+        assert_eq!(result.error_type.as_deref(), Some("quota_exceeded"));
+
+        let cleaned = CommandTool::new(root.to_str().unwrap())
+            .with_working_dir_cleanup(WorkingDirCleanup::AfterEachRun);
+        let result = cleaned
+            .execute(json!({"command": "touch", "args": ["out.txt"]}))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(std::fs::read_dir(root.join("shared")).unwrap().count(), 0);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1,6 +1,7 @@
 pub mod activate_skill;
 pub mod bash;
 pub mod browser;
+pub mod command;
 pub mod edit_file;
 pub mod export_chat;
 pub mod glob;
@@ -18,7 +19,9 @@ pub mod web_fetch;
 pub mod web_search;
 pub mod write_file;
 
-use std::sync::{Arc, OnceLock};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use std::{path::PathBuf, time::Instant};
 
 use crate::config::Config;
@@ -27,7 +30,8 @@ use microclaw_core::llm_types::ToolDefinition;
 use microclaw_storage::db::Database;
 pub use microclaw_tools::runtime::{
     auth_context_from_input, authorize_chat_access, resolve_tool_path, resolve_tool_working_dir,
-    schema_object, tool_risk, Tool, ToolAuthContext, ToolResult, ToolRisk,
+    schema_object, tool_risk, validate_tool_input, Artifact, Tool, ToolAuthContext, ToolResult,
+    ToolRisk,
 };
 use microclaw_tools::runtime::{inject_auth_context, require_high_risk_approval};
 use microclaw_tools::sandbox::SandboxRouter;
@@ -35,6 +39,64 @@ use microclaw_tools::sandbox::SandboxRouter;
 pub struct ToolRegistry {
     tools: Vec<Box<dyn Tool>>,
     cached_definitions: OnceLock<Vec<ToolDefinition>>,
+    result_cache: Option<ToolResultCache>,
+    controls: ToolControls,
+}
+
+/// Runtime tool switches and caps, shared between a registry and the
+/// registries of the sub-agents it spawns so both draw on the same limits.
+#[derive(Clone, Default)]
+pub struct ToolControls {
+    /// Per-tool caps on concurrent executions; tools not listed are unlimited.
+    concurrency_limits: Arc<HashMap<String, Arc<tokio::sync::Semaphore>>>,
+    /// Tools switched off at runtime; calls fail until re-enabled.
+    disabled: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ToolControls {
+    /// Cap how many executions of each named tool run at once; extra calls
+    /// wait for a free slot. A limit of 0 is treated as unlimited.
+    pub fn with_concurrency_limits(mut self, limits: &HashMap<String, usize>) -> Self {
+        self.concurrency_limits = Arc::new(
+            limits
+                .iter()
+                .filter(|(_, max)| **max > 0)
+                .map(|(name, max)| (name.clone(), Arc::new(tokio::sync::Semaphore::new(*max))))
+                .collect(),
+        );
+        self
+    }
+}
+
+/// Successful results of opted-in tools, keyed by tool name and the
+/// serialized input (object keys serialize sorted, so equal inputs match).
+struct ToolResultCache {
+    tools: HashSet<String>,
+    ttl: Duration,
+    entries: Mutex<HashMap<(String, String), (Instant, ToolResult)>>,
+}
+
+impl ToolResultCache {
+    fn get(&self, name: &str, input: &serde_json::Value) -> Option<ToolResult> {
+        if !self.tools.contains(name) {
+            return None;
+        }
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (stored_at, result) = entries.get(&(name.to_string(), input.to_string()))?;
+        (stored_at.elapsed() < self.ttl).then(|| result.clone())
+    }
+
+    fn insert(&self, name: &str, input: &serde_json::Value, result: &ToolResult) {
+        if !self.tools.contains(name) || result.is_error {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (stored_at, _)| stored_at.elapsed() < self.ttl);
+        entries.insert(
+            (name.to_string(), input.to_string()),
+            (Instant::now(), result.clone()),
+        );
+    }
 }
 
 impl ToolRegistry {
@@ -48,6 +110,8 @@ impl ToolRegistry {
             );
         }
         let sandbox_router = Arc::new(SandboxRouter::new(config.sandbox.clone(), &working_dir));
+        let controls =
+            ToolControls::default().with_concurrency_limits(&config.tool_concurrency_limits);
         tracing::info!(
             mode = ?sandbox_router.mode(),
             backend = sandbox_router.backend_name(),
@@ -55,10 +119,22 @@ impl ToolRegistry {
         );
         let skills_data_dir = config.skills_data_dir();
         let mut tools: Vec<Box<dyn Tool>> = vec![
-            Box::new(bash::BashTool::new_with_isolation(
-                &config.working_dir,
-                config.working_dir_isolation,
-            )),
+            Box::new(
+                bash::BashTool::new_with_isolation(
+                    &config.working_dir,
+                    config.working_dir_isolation,
+                )
+                .with_working_dir_quota_mb(config.sandbox.working_dir_quota_mb)
+                .with_working_dir_cleanup(config.sandbox.working_dir_cleanup),
+            ),
+            Box::new(
+                command::CommandTool::new_with_isolation(
+                    &config.working_dir,
+                    config.working_dir_isolation,
+                )
+                .with_working_dir_quota_mb(config.sandbox.working_dir_quota_mb)
+                .with_working_dir_cleanup(config.sandbox.working_dir_cleanup),
+            ),
             Box::new(browser::BrowserTool::new(&config.data_dir)),
             Box::new(read_file::ReadFileTool::new_with_isolation(
                 &config.working_dir,
@@ -118,7 +194,9 @@ impl ToolRegistry {
                 db.clone(),
                 &config.data_dir,
             )),
-            Box::new(sub_agent::SubAgentTool::new(config, db.clone())),
+            Box::new(
+                sub_agent::SubAgentTool::new(config, db.clone()).with_controls(controls.clone()),
+            ),
             Box::new(activate_skill::ActivateSkillTool::new(&skills_data_dir)),
             Box::new(sync_skills::SyncSkillsTool::new(&skills_data_dir)),
             Box::new(todo::TodoReadTool::new(&config.data_dir)),
@@ -126,12 +204,18 @@ impl ToolRegistry {
             Box::new(structured_memory::StructuredMemorySearchTool::new(
                 db.clone(),
             )),
+            Box::new(structured_memory::StructuredMemoryStoreTool::new(
+                db.clone(),
+            )),
             Box::new(structured_memory::StructuredMemoryDeleteTool::new(
                 db.clone(),
             )),
             Box::new(structured_memory::StructuredMemoryUpdateTool::new(
                 db.clone(),
             )),
+            Box::new(structured_memory::StructuredMemoryStatsTool::new(
+                db.clone(),
+            )),
         ];
 
         // Add ClawHub tools if enabled
@@ -147,11 +231,18 @@ impl ToolRegistry {
         ToolRegistry {
             tools,
             cached_definitions: OnceLock::new(),
+            result_cache: None,
+            controls,
         }
+        .with_result_cache(
+            &config.tool_result_cache_tools,
+            Duration::from_secs(config.tool_result_cache_ttl_secs),
+        )
     }
 
     /// Create a restricted tool registry for sub-agents (no side-effect or recursive tools).
-    pub fn new_sub_agent(config: &Config, db: Arc<Database>) -> Self {
+    /// Pass the parent's [`ToolControls`] so tools it disabled stay disabled.
+    pub fn new_sub_agent(config: &Config, db: Arc<Database>, controls: ToolControls) -> Self {
         let working_dir = PathBuf::from(&config.working_dir);
         if let Err(e) = std::fs::create_dir_all(&working_dir) {
             tracing::warn!(
@@ -162,10 +253,22 @@ impl ToolRegistry {
         }
         let skills_data_dir = config.skills_data_dir();
         let tools: Vec<Box<dyn Tool>> = vec![
-            Box::new(bash::BashTool::new_with_isolation(
-                &config.working_dir,
-                config.working_dir_isolation,
-            )),
+            Box::new(
+                bash::BashTool::new_with_isolation(
+                    &config.working_dir,
+                    config.working_dir_isolation,
+                )
+                .with_working_dir_quota_mb(config.sandbox.working_dir_quota_mb)
+                .with_working_dir_cleanup(config.sandbox.working_dir_cleanup),
+            ),
+            Box::new(
+                command::CommandTool::new_with_isolation(
+                    &config.working_dir,
+                    config.working_dir_isolation,
+                )
+                .with_working_dir_quota_mb(config.sandbox.working_dir_quota_mb)
+                .with_working_dir_cleanup(config.sandbox.working_dir_cleanup),
+            ),
             Box::new(browser::BrowserTool::new(&config.data_dir)),
             Box::new(read_file::ReadFileTool::new_with_isolation(
                 &config.working_dir,
@@ -196,6 +299,37 @@ impl ToolRegistry {
         ToolRegistry {
             tools,
             cached_definitions: OnceLock::new(),
+            result_cache: None,
+            controls,
+        }
+    }
+
+    /// Reuse successful results of the named tools for identical inputs within
+    /// `ttl`. Only list deterministic tools; an empty list disables caching.
+    pub fn with_result_cache(mut self, tools: &[String], ttl: Duration) -> Self {
+        self.result_cache = (!tools.is_empty() && !ttl.is_zero()).then(|| ToolResultCache {
+            tools: tools.iter().cloned().collect(),
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        });
+        self
+    }
+
+    /// Replace this registry's concurrency caps; see
+    /// [`ToolControls::with_concurrency_limits`].
+    pub fn with_concurrency_limits(mut self, limits: &HashMap<String, usize>) -> Self {
+        self.controls = self.controls.with_concurrency_limits(limits);
+        self
+    }
+
+    /// A registry holding just `tools`, with no result cache or limits.
+    #[cfg(test)]
+    fn from_tools(tools: Vec<Box<dyn Tool>>) -> Self {
+        ToolRegistry {
+            tools,
+            cached_definitions: OnceLock::new(),
+            result_cache: None,
+            controls: ToolControls::default(),
         }
     }
 
@@ -205,14 +339,91 @@ impl ToolRegistry {
         self.tools.push(tool);
     }
 
-    pub fn definitions(&self) -> &[ToolDefinition] {
+    /// Definitions of every registered tool, built once and indexed like
+    /// `tools`.
+    fn all_definitions(&self) -> &[ToolDefinition] {
         self.cached_definitions
             .get_or_init(|| self.tools.iter().map(|t| t.definition()).collect())
     }
 
+    /// Definitions of the enabled tools, in registration order.
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        let all = self.all_definitions();
+        let disabled = self
+            .controls
+            .disabled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        all.iter()
+            .filter(|d| !disabled.contains(&d.name))
+            .cloned()
+            .collect()
+    }
+
+    /// Switch a tool off (or back on) without rebuilding the registry.
+    /// Disabled tools stay registered but every call returns a
+    /// `tool_disabled` error. Returns false if no tool has that name.
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        if !self.tools.iter().any(|t| t.name() == name) {
+            return false;
+        }
+        let mut disabled = self
+            .controls
+            .disabled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if enabled {
+            disabled.remove(name);
+        } else {
+            disabled.insert(name.to_string());
+        }
+        true
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        !self
+            .controls
+            .disabled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(name)
+    }
+
+    /// Names of every registered tool, in registration order.
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.iter().map(|t| t.name()).collect()
+    }
+
     pub async fn execute(&self, name: &str, input: serde_json::Value) -> ToolResult {
-        for tool in &self.tools {
+        for (idx, tool) in self.tools.iter().enumerate() {
             if tool.name() == name {
+                if !self.is_enabled(name) {
+                    return ToolResult::error(format!("Tool '{name}' is currently disabled"))
+                        .with_error_type("tool_disabled");
+                }
+                let schema = &self.all_definitions()[idx].input_schema;
+                if let Err(problems) = validate_tool_input(schema, &input) {
+                    let mut result = ToolResult::error(format!(
+                        "Invalid input for tool '{name}': {}",
+                        problems.join("; ")
+                    ))
+                    .with_error_type("invalid_input");
+                    result.status_code = Some(1);
+                    return result;
+                }
+                if let Some(cached) = self
+                    .result_cache
+                    .as_ref()
+                    .and_then(|cache| cache.get(name, &input))
+                {
+                    return cached;
+                }
+                let cache_input = self.result_cache.as_ref().map(|_| input.clone());
+                // The semaphore is never closed, so acquiring cannot fail.
+                let _permit = match self.controls.concurrency_limits.get(name) {
+                    Some(semaphore) => semaphore.acquire().await.ok(),
+                    None => None,
+                };
                 let started = Instant::now();
                 let mut result = tool.execute(input).await;
                 result.duration_ms = Some(started.elapsed().as_millis());
@@ -223,6 +434,9 @@ impl ToolRegistry {
                 if result.status_code.is_none() {
                     result.status_code = Some(if result.is_error { 1 } else { 0 });
                 }
+                if let (Some(cache), Some(input)) = (&self.result_cache, cache_input) {
+                    cache.insert(name, &input, &result);
+                }
                 return result;
             }
         }
@@ -376,6 +590,168 @@ mod tests {
         }
     }
 
+    const COUNTING_TOOL: &str = "counting";
+
+    #[derive(Default)]
+    struct CountingTool {
+        calls: std::sync::atomic::AtomicUsize,
+        definitions: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Tool for CountingTool {
+        fn name(&self) -> &str {
+            COUNTING_TOOL
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            self.definitions
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            ToolDefinition {
+                name: COUNTING_TOOL.into(),
+                description: "counts calls".into(),
+                input_schema: schema_object(json!({"q": {"type": "string"}}), &[]),
+            }
+        }
+
+        async fn execute(&self, _input: serde_json::Value) -> ToolResult {
+            let n = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            ToolResult::success(format!("call {n}"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_result_cache_reuses_identical_inputs() {
+        let registry = ToolRegistry::from_tools(vec![Box::new(CountingTool::default())])
+            .with_result_cache(&["counting".to_string()], Duration::from_secs(60));
+
+        let first = registry.execute("counting", json!({"q": "a"})).await;
+        let second = registry.execute("counting", json!({"q": "a"})).await;
+        let other = registry.execute("counting", json!({"q": "b"})).await;
+        assert_eq!(first.content, "call 0");
+        assert_eq!(second.content, "call 0");
+        assert_eq!(other.content, "call 1");
+
+        let uncached = ToolRegistry::from_tools(vec![Box::new(CountingTool::default())])
+            .with_result_cache(&["bash".to_string()], Duration::from_secs(60));
+        uncached.execute("counting", json!({"q": "a"})).await;
+        let again = uncached.execute("counting", json!({"q": "a"})).await;
+        assert_eq!(again.content, "call 1");
+    }
+
+    #[tokio::test]
+    async fn test_execute_builds_definitions_once() {
+        let tool = CountingTool::default();
+        let definitions = tool.definitions.clone();
+        let registry = ToolRegistry::from_tools(vec![Box::new(tool)]);
+        for _ in 0..3 {
+            registry.execute(COUNTING_TOOL, json!({"q": "a"})).await;
+        }
+        registry.definitions();
+        assert_eq!(definitions.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    const SLOW_TOOL: &str = "slow";
+
+    struct SlowTool {
+        active: Arc<std::sync::atomic::AtomicUsize>,
+        peak: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Tool for SlowTool {
+        fn name(&self) -> &str {
+            SLOW_TOOL
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: SLOW_TOOL.into(),
+                description: "sleeps briefly".into(),
+                input_schema: schema_object(json!({}), &[]),
+            }
+        }
+
+        async fn execute(&self, _input: serde_json::Value) -> ToolResult {
+            use std::sync::atomic::Ordering;
+            let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            ToolResult::success("done".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_queues_excess_calls() {
+        let peak = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let registry = ToolRegistry::from_tools(vec![Box::new(SlowTool {
+            active: Default::default(),
+            peak: peak.clone(),
+        })])
+        .with_concurrency_limits(&HashMap::from([(SLOW_TOOL.to_string(), 1)]));
+
+        let results =
+            futures_util::future::join_all((0..3).map(|_| registry.execute(SLOW_TOOL, json!({}))))
+                .await;
+        assert!(results.iter().all(|r| !r.is_error));
+        assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_is_shared_through_controls() {
+        let active = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let peak = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let controls = ToolControls::default()
+            .with_concurrency_limits(&HashMap::from([(SLOW_TOOL.to_string(), 1)]));
+        let registry = |controls: ToolControls| ToolRegistry {
+            controls,
+            ..ToolRegistry::from_tools(vec![Box::new(SlowTool {
+                active: active.clone(),
+                peak: peak.clone(),
+            })])
+        };
+        let parent = registry(controls.clone());
+        let sub_agent = registry(controls);
+
+        futures_util::future::join(
+            parent.execute(SLOW_TOOL, json!({})),
+            sub_agent.execute(SLOW_TOOL, json!({})),
+        )
+        .await;
+        assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_names_and_definitions_list_every_tool() {
+        let mut registry = ToolRegistry::from_tools(vec![Box::new(CountingTool::default())]);
+        assert_eq!(registry.definitions().len(), 1);
+        registry.add_tool(Box::new(SlowTool {
+            active: Default::default(),
+            peak: Default::default(),
+        }));
+
+        assert_eq!(registry.names(), vec![COUNTING_TOOL, SLOW_TOOL]);
+        let defined: Vec<String> = registry.definitions().into_iter().map(|d| d.name).collect();
+        assert_eq!(defined, registry.names());
+    }
+
+    #[tokio::test]
+    async fn test_set_enabled_blocks_and_restores_tool() {
+        let registry = ToolRegistry::from_tools(vec![Box::new(CountingTool::default())]);
+        assert!(!registry.set_enabled("missing", false));
+
+        assert!(registry.set_enabled(COUNTING_TOOL, false));
+        let blocked = registry.execute(COUNTING_TOOL, json!({})).await;
+        assert!(blocked.is_error);
+        assert_eq!(blocked.error_type.as_deref(), Some("tool_disabled"));
+        assert!(registry.definitions().is_empty());
+
+        assert!(registry.set_enabled(COUNTING_TOOL, true));
+        let result = registry.execute(COUNTING_TOOL, json!({})).await;
+        assert_eq!(result.content, "call 0");
+    }
+
     #[test]
     fn test_tool_risk_levels() {
         assert_eq!(tool_risk("bash"), ToolRisk::High);
@@ -387,12 +763,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_high_risk_tool_requires_second_approval_on_web() {
-        let registry = ToolRegistry {
-            cached_definitions: OnceLock::new(),
-            tools: vec![Box::new(DummyTool {
-                tool_name: "bash".into(),
-            })],
-        };
+        let registry = ToolRegistry::from_tools(vec![Box::new(DummyTool {
+            tool_name: "bash".into(),
+        })]);
         let auth = ToolAuthContext {
             caller_channel: "web".into(),
             caller_chat_id: 1,
@@ -412,12 +785,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_high_risk_tool_requires_second_approval_on_control_chat() {
-        let registry = ToolRegistry {
-            cached_definitions: OnceLock::new(),
-            tools: vec![Box::new(DummyTool {
-                tool_name: "bash".into(),
-            })],
-        };
+        let registry = ToolRegistry::from_tools(vec![Box::new(DummyTool {
+            tool_name: "bash".into(),
+        })]);
         let auth = ToolAuthContext {
             caller_channel: "telegram".into(),
             caller_chat_id: 123,
@@ -431,12 +801,9 @@ mod tests {
 
     #[tokio::test]
     async fn test_medium_risk_tool_no_second_approval() {
-        let registry = ToolRegistry {
-            cached_definitions: OnceLock::new(),
-            tools: vec![Box::new(DummyTool {
-                tool_name: "write_file".into(),
-            })],
-        };
+        let registry = ToolRegistry::from_tools(vec![Box::new(DummyTool {
+            tool_name: "write_file".into(),
+        })]);
         let auth = ToolAuthContext {
             caller_channel: "web".into(),
             caller_chat_id: 1,
@@ -449,4 +816,40 @@ mod tests {
         assert!(!result.is_error);
         assert_eq!(result.content, "ok");
     }
+
+    #[test]
+    fn test_validate_tool_input_reports_missing_and_mistyped() {
+        let schema = schema_object(
+            json!({
+                "path": {"type": "string"},
+                "limit": {"type": "integer"},
+                "mode": {"type": "string", "enum": ["a", "b"]}
+            }),
+            &["path"],
+        );
+        assert!(validate_tool_input(&schema, &json!({"path": "x", "limit": 3.0})).is_ok());
+        assert!(
+            validate_tool_input(&schema, &json!({"path": "x", "__microclaw_auth": {}})).is_ok()
+        );
+
+        let problems =
+            validate_tool_input(&schema, &json!({"limit": "ten", "mode": "c"})).unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                "missing required field 'path'".to_string(),
+                "field 'limit' must be integer".to_string(),
+                "field 'mode' must be one of: \"a\", \"b\"".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejects_invalid_input_before_dispatch() {
+        let registry = ToolRegistry::from_tools(vec![Box::new(read_file::ReadFileTool::new("."))]);
+        let result = registry.execute("read_file", json!({"path": 42})).await;
+        assert!(result.is_error);
+        assert_eq!(result.error_type.as_deref(), Some("invalid_input"));
+        assert!(result.content.contains("field 'path' must be string"));
+    }
 }
@@ -4,10 +4,16 @@ use std::sync::Arc;
 use tracing::info;
 
 use microclaw_core::llm_types::ToolDefinition;
-use microclaw_storage::db::{call_blocking, Database};
+use microclaw_storage::db::{
+    call_blocking, group_memories_by_category, grouped_search_limit, Database, MemoryMatchMode,
+};
+use microclaw_storage::memory_quality::MemoryCategory;
 
 use super::{auth_context_from_input, authorize_chat_access, schema_object, Tool, ToolResult};
 
+/// Cap on memory titles; they are headings, not a second content field.
+const MAX_TITLE_LEN: usize = 100;
+
 // ── Search ────────────────────────────────────────────────────────────────────
 
 pub struct StructuredMemorySearchTool {
@@ -29,13 +35,18 @@ impl Tool for StructuredMemorySearchTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "structured_memory_search".into(),
-            description: "Search structured memories extracted from past conversations. Returns memories whose content contains the query string.".into(),
+            description: "Search structured memories extracted from past conversations. By default returns memories whose content contains the query string; set `match` to require all words or any word instead.".into(),
             input_schema: schema_object(
                 json!({
                     "query": {
                         "type": "string",
                         "description": "Keyword(s) to search for in memory content"
                     },
+                    "match": {
+                        "type": "string",
+                        "enum": ["phrase", "all", "any"],
+                        "description": "phrase (default): content contains the whole query; all: contains every word; any: contains at least one word"
+                    },
                     "limit": {
                         "type": "integer",
                         "description": "Maximum number of results to return (default 10, max 50)"
@@ -43,6 +54,18 @@ impl Tool for StructuredMemorySearchTool {
                     "include_archived": {
                         "type": "boolean",
                         "description": "Whether to include archived memories in results (default false)"
+                    },
+                    "ids_only": {
+                        "type": "boolean",
+                        "description": "Return only id, category, and scope for each match, without content (default false). Useful when you only need to pick which memories to act on."
+                    },
+                    "since": {
+                        "type": "string",
+                        "description": "Only return memories created at or after this time: an RFC 3339 timestamp or a YYYY-MM-DD date (midnight UTC)"
+                    },
+                    "group_by_category": {
+                        "type": "boolean",
+                        "description": "Return results under one heading per category, with `limit` applying to each category instead of overall (default false)"
                     }
                 }),
                 &["query"],
@@ -64,6 +87,30 @@ impl Tool for StructuredMemorySearchTool {
             .get("include_archived")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
+        let match_mode = input
+            .get("match")
+            .and_then(|v| v.as_str())
+            .and_then(MemoryMatchMode::parse)
+            .unwrap_or_default();
+        let ids_only = input
+            .get("ids_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let since = match input.get("since").and_then(|v| v.as_str()) {
+            Some(raw) => match parse_since(raw) {
+                Some(t) => Some(t),
+                None => {
+                    return ToolResult::error(format!(
+                        "Invalid 'since' value '{raw}'. Use an RFC 3339 timestamp or YYYY-MM-DD"
+                    ))
+                }
+            },
+            None => None,
+        };
+        let grouped = input
+            .get("group_by_category")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
         let chat_id = auth_context_from_input(&input)
             .map(|a| a.caller_chat_id)
@@ -74,7 +121,19 @@ impl Tool for StructuredMemorySearchTool {
         );
 
         match call_blocking(self.db.clone(), move |db| {
-            db.search_memories_with_options(chat_id, &query, limit, include_archived, true)
+            db.search_memories_with_options(
+                chat_id,
+                &query,
+                match_mode,
+                if grouped {
+                    grouped_search_limit(limit)
+                } else {
+                    limit
+                },
+                include_archived,
+                true,
+                since,
+            )
         })
         .await
         {
@@ -82,24 +141,66 @@ impl Tool for StructuredMemorySearchTool {
                 ToolResult::success("No memories found matching that query.".into())
             }
             Ok(memories) => {
-                let lines: Vec<String> = memories
+                let format_line = |m: &microclaw_storage::db::Memory| {
+                    let scope = if m.chat_id.is_none() {
+                        "global"
+                    } else {
+                        "chat"
+                    };
+                    if ids_only {
+                        format!("[id={}] [{}] [{}]", m.id, m.category, scope)
+                    } else if let Some(path) = &m.attachment_path {
+                        format!(
+                            "[id={}] [{}] [{}] {} (attachment: {path})",
+                            m.id,
+                            m.category,
+                            scope,
+                            m.display_text()
+                        )
+                    } else {
+                        format!(
+                            "[id={}] [{}] [{}] {}",
+                            m.id,
+                            m.category,
+                            scope,
+                            m.display_text()
+                        )
+                    }
+                };
+                if !grouped {
+                    let lines: Vec<String> = memories.iter().map(format_line).collect();
+                    return ToolResult::success(lines.join("\n"));
+                }
+                let groups = group_memories_by_category(memories, limit);
+                if groups.is_empty() {
+                    return ToolResult::success("No memories found matching that query.".into());
+                }
+                let sections: Vec<String> = MemoryCategory::ALL
                     .iter()
-                    .map(|m| {
-                        let scope = if m.chat_id.is_none() {
-                            "global"
-                        } else {
-                            "chat"
-                        };
-                        format!("[id={}] [{}] [{}] {}", m.id, m.category, scope, m.content)
+                    .filter_map(|category| {
+                        let group = groups.get(category)?;
+                        let lines: Vec<String> = group.iter().map(format_line).collect();
+                        Some(format!("## {category}\n{}", lines.join("\n")))
                     })
                     .collect();
-                ToolResult::success(lines.join("\n"))
+                ToolResult::success(sections.join("\n\n"))
             }
             Err(e) => ToolResult::error(format!("Search failed: {e}")),
         }
     }
 }
 
+fn parse_since(raw: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let raw = raw.trim();
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(raw) {
+        return Some(t.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+}
+
 // ── Delete ────────────────────────────────────────────────────────────────────
 
 pub struct StructuredMemoryDeleteTool {
@@ -177,6 +278,190 @@ impl Tool for StructuredMemoryDeleteTool {
     }
 }
 
+// ── Store ─────────────────────────────────────────────────────────────────────
+
+pub struct StructuredMemoryStoreTool {
+    db: Arc<Database>,
+}
+
+impl StructuredMemoryStoreTool {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for StructuredMemoryStoreTool {
+    fn name(&self) -> &str {
+        "structured_memory_store"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "structured_memory_store".into(),
+            description: "Store a single fact as a structured memory so it is recalled in later conversations. Search first and use structured_memory_update instead if a memory on the same topic already exists.".into(),
+            input_schema: schema_object(
+                json!({
+                    "content": {
+                        "type": "string",
+                        "description": "The fact to remember (max 300 characters)"
+                    },
+                    "category": {
+                        "type": "string",
+                        "description": format!("Category (default KNOWLEDGE): {}", MemoryCategory::names_joined()),
+                        "enum": MemoryCategory::ALL.iter().map(|c| c.as_str()).collect::<Vec<_>>()
+                    },
+                    "scope": {
+                        "type": "string",
+                        "description": "'chat' (default) for this chat only, or 'global' to share across all chats (control chats only)",
+                        "enum": ["chat", "global"]
+                    },
+                    "chat_id": {
+                        "type": "integer",
+                        "description": "Chat ID for chat scope (defaults to the current chat)"
+                    },
+                    "pinned": {
+                        "type": "boolean",
+                        "description": "Pin the memory so it always surfaces and is never archived or merged (default false)"
+                    },
+                    "attachment_path": {
+                        "type": "string",
+                        "description": "Path of a file this memory refers to (image, PDF, ...). Stored as a reference and shown with the memory; only the text is searched."
+                    },
+                    "search_text": {
+                        "type": "string",
+                        "description": "Normalized form to search and embed instead of `content` (e.g. lowercased, punctuation stripped). `content` is still what recall shows."
+                    },
+                    "title": {
+                        "type": "string",
+                        "description": "Short heading for the memory (max 100 characters). Shown before the content and weighted above it in search."
+                    }
+                }),
+                &["content"],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let content = match input.get("content").and_then(|v| v.as_str()) {
+            Some(c) if !c.trim().is_empty() => c.trim().to_string(),
+            _ => return ToolResult::error("Missing or empty 'content' parameter".into()),
+        };
+        if content.len() > 300 {
+            return ToolResult::error("Content exceeds 300 character limit".into());
+        }
+        let raw_category = input
+            .get("category")
+            .and_then(|v| v.as_str())
+            .unwrap_or(MemoryCategory::Knowledge.as_str());
+        let category = match MemoryCategory::parse(raw_category) {
+            Some(c) => c.as_str().to_string(),
+            None => {
+                return ToolResult::error(format!(
+                    "Invalid category '{raw_category}'. Must be one of: {}",
+                    MemoryCategory::names_joined()
+                ));
+            }
+        };
+
+        let auth = auth_context_from_input(&input);
+        let chat_id = match input
+            .get("scope")
+            .and_then(|v| v.as_str())
+            .unwrap_or("chat")
+        {
+            "global" => {
+                if let Some(auth) = &auth {
+                    if !auth.is_control_chat() {
+                        return ToolResult::error(format!(
+                            "Permission denied: only control chats can store global memories (caller: {})",
+                            auth.caller_chat_id
+                        ));
+                    }
+                }
+                None
+            }
+            "chat" => {
+                let chat_id = match input
+                    .get("chat_id")
+                    .and_then(|v| v.as_i64())
+                    .or(auth.as_ref().map(|a| a.caller_chat_id))
+                {
+                    Some(id) => id,
+                    None => return ToolResult::error("Missing 'chat_id' for chat scope".into()),
+                };
+                if let Err(e) = authorize_chat_access(&input, chat_id) {
+                    return ToolResult::error(e);
+                }
+                Some(chat_id)
+            }
+            other => {
+                return ToolResult::error(format!(
+                    "Invalid scope '{other}'. Must be 'chat' or 'global'"
+                ))
+            }
+        };
+        let pinned = input
+            .get("pinned")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let attachment_path = input
+            .get("attachment_path")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string);
+        let search_text = input
+            .get("search_text")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string);
+        let title = input
+            .get("title")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string);
+        if title
+            .as_ref()
+            .is_some_and(|t| t.chars().count() > MAX_TITLE_LEN)
+        {
+            return ToolResult::error(format!("Title exceeds {MAX_TITLE_LEN} character limit"));
+        }
+
+        info!("structured_memory_store: chat_id={chat_id:?} category={category}");
+
+        match call_blocking(self.db.clone(), move |db| {
+            let id = db.insert_memory_with_metadata(
+                chat_id,
+                &content,
+                &category,
+                "structured_memory_store",
+                0.9,
+            )?;
+            if pinned {
+                db.pin_memory(id)?;
+            }
+            if let Some(path) = &attachment_path {
+                db.set_memory_attachment(id, Some(path))?;
+            }
+            if let Some(text) = &search_text {
+                db.set_memory_search_text(id, Some(text))?;
+            }
+            if let Some(title) = &title {
+                db.set_memory_title(id, Some(title))?;
+            }
+            Ok(id)
+        })
+        .await
+        {
+            Ok(id) => ToolResult::success(format!("Memory id={id} stored.")),
+            Err(e) => ToolResult::error(format!("Store failed: {e}")),
+        }
+    }
+}
+
 // ── Update ────────────────────────────────────────────────────────────────────
 
 pub struct StructuredMemoryUpdateTool {
@@ -198,7 +483,7 @@ impl Tool for StructuredMemoryUpdateTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "structured_memory_update".into(),
-            description: "Update the content or category of an existing structured memory. Use this to correct outdated or wrong memories instead of creating a duplicate.".into(),
+            description: "Update the content, category, title, or pin state of an existing structured memory. Use this to correct outdated or wrong memories instead of creating a duplicate.".into(),
             input_schema: schema_object(
                 json!({
                     "id": {
@@ -211,8 +496,16 @@ impl Tool for StructuredMemoryUpdateTool {
                     },
                     "category": {
                         "type": "string",
-                        "description": "Category: PROFILE, KNOWLEDGE, or EVENT",
-                        "enum": ["PROFILE", "KNOWLEDGE", "EVENT"]
+                        "description": format!("Category: {}", MemoryCategory::names_joined()),
+                        "enum": MemoryCategory::ALL.iter().map(|c| c.as_str()).collect::<Vec<_>>()
+                    },
+                    "pinned": {
+                        "type": "boolean",
+                        "description": "Pin (true) or unpin (false). Pinned memories always surface and are never archived or merged by background maintenance."
+                    },
+                    "title": {
+                        "type": "string",
+                        "description": "New title (max 100 characters); an empty string removes it. Omit to keep the current title."
                     }
                 }),
                 &["id", "content"],
@@ -259,23 +552,44 @@ impl Tool for StructuredMemoryUpdateTool {
             }
         }
 
-        let category = input
+        let raw_category = input
             .get("category")
             .and_then(|v| v.as_str())
-            .unwrap_or(&mem.category)
-            .to_string();
-
-        let valid_categories = ["PROFILE", "KNOWLEDGE", "EVENT"];
-        if !valid_categories.contains(&category.as_str()) {
-            return ToolResult::error(format!(
-                "Invalid category '{category}'. Must be one of: PROFILE, KNOWLEDGE, EVENT"
-            ));
+            .unwrap_or(&mem.category);
+        let category = match MemoryCategory::parse(raw_category) {
+            Some(c) => c.as_str().to_string(),
+            None => {
+                return ToolResult::error(format!(
+                    "Invalid category '{raw_category}'. Must be one of: {}",
+                    MemoryCategory::names_joined()
+                ));
+            }
+        };
+
+        let pinned = input.get("pinned").and_then(|v| v.as_bool());
+        let title = input
+            .get("title")
+            .and_then(|v| v.as_str())
+            .map(|t| t.trim().to_string());
+        if title
+            .as_ref()
+            .is_some_and(|t| t.chars().count() > MAX_TITLE_LEN)
+        {
+            return ToolResult::error(format!("Title exceeds {MAX_TITLE_LEN} character limit"));
         }
 
         info!("structured_memory_update: id={id}");
 
         match call_blocking(self.db.clone(), move |db| {
-            db.update_memory_content(id, &content, &category)
+            let updated = db.update_memory_content(id, &content, &category)?;
+            if let (true, Some(title)) = (updated, &title) {
+                db.set_memory_title(id, Some(title))?;
+            }
+            match pinned {
+                Some(true) if updated => db.pin_memory(id),
+                Some(false) if updated => db.unpin_memory(id),
+                _ => Ok(updated),
+            }
         })
         .await
         {
@@ -286,6 +600,95 @@ impl Tool for StructuredMemoryUpdateTool {
     }
 }
 
+// ── Stats ─────────────────────────────────────────────────────────────────────
+
+pub struct StructuredMemoryStatsTool {
+    db: Arc<Database>,
+}
+
+impl StructuredMemoryStatsTool {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for StructuredMemoryStatsTool {
+    fn name(&self) -> &str {
+        "structured_memory_stats"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "structured_memory_stats".into(),
+            description: "Summarize the structured memories visible to this chat: active count per category, the total, and the most recently updated entries. Use it to decide whether a structured_memory_search is worth running.".into(),
+            input_schema: schema_object(
+                json!({
+                    "recent": {
+                        "type": "integer",
+                        "description": "How many recently updated memories to include (default 5, max 20, 0 for none)"
+                    }
+                }),
+                &[],
+            ),
+        }
+    }
+
+    async fn execute(&self, input: serde_json::Value) -> ToolResult {
+        let recent = input
+            .get("recent")
+            .and_then(|v| v.as_u64())
+            .map(|n| n.min(20) as usize)
+            .unwrap_or(5);
+        let chat_id = auth_context_from_input(&input)
+            .map(|a| a.caller_chat_id)
+            .unwrap_or(0);
+
+        info!("structured_memory_stats: chat_id={chat_id} recent={recent}");
+
+        match call_blocking(self.db.clone(), move |db| {
+            let by_category = db.count_memories_by_category(Some(chat_id))?;
+            let recent = if recent == 0 {
+                Vec::new()
+            } else {
+                db.get_memories_for_context(chat_id, recent)?
+            };
+            Ok((by_category, recent))
+        })
+        .await
+        {
+            Ok((by_category, recent)) => {
+                let total: usize = by_category.iter().map(|(_, n)| n).sum();
+                let by_category: serde_json::Map<String, serde_json::Value> = by_category
+                    .into_iter()
+                    .map(|(category, n)| (category, n.into()))
+                    .collect();
+                let recent: Vec<serde_json::Value> = recent
+                    .iter()
+                    .map(|m| {
+                        json!({
+                            "id": m.id,
+                            "category": m.category,
+                            "scope": if m.chat_id.is_none() { "global" } else { "chat" },
+                            "title": m.title,
+                            "content": m.content,
+                        })
+                    })
+                    .collect();
+                ToolResult::success(
+                    json!({
+                        "total": total,
+                        "by_category": by_category,
+                        "recent": recent,
+                    })
+                    .to_string(),
+                )
+            }
+            Err(e) => ToolResult::error(format!("Stats failed: {e}")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -316,6 +719,221 @@ mod tests {
         assert!(!result.content.contains("coffee"));
     }
 
+    #[tokio::test]
+    async fn test_search_ids_only_omits_content() {
+        let db = test_db();
+        let id = db
+            .insert_memory(Some(100), "User loves Rust programming", "PROFILE")
+            .unwrap();
+        let tool = StructuredMemorySearchTool::new(db);
+        let result = tool
+            .execute(json!({
+                "query": "rust",
+                "ids_only": true,
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error);
+        assert_eq!(result.content, format!("[id={id}] [PROFILE] [chat]"));
+    }
+
+    #[tokio::test]
+    async fn test_stats_counts_categories_and_recent() {
+        let db = test_db();
+        db.insert_memory(Some(100), "User likes tea", "PREFERENCE")
+            .unwrap();
+        db.insert_memory(Some(100), "User is in Berlin", "PROFILE")
+            .unwrap();
+        db.insert_memory(None, "Office closes at six", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(Some(200), "Other chat secret", "KNOWLEDGE")
+            .unwrap();
+        let tool = StructuredMemoryStatsTool::new(db);
+        let result = tool
+            .execute(json!({
+                "recent": 2,
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        let body: serde_json::Value = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(body["total"], 3);
+        assert_eq!(body["by_category"]["KNOWLEDGE"], 1);
+        assert_eq!(body["recent"].as_array().unwrap().len(), 2);
+        assert!(!result.content.contains("Other chat secret"));
+    }
+
+    #[tokio::test]
+    async fn test_store_with_attachment_is_returned_by_search() {
+        let db = test_db();
+        let store = StructuredMemoryStoreTool::new(db.clone());
+        let result = store
+            .execute(json!({
+                "content": "Floor plan for the new office",
+                "attachment_path": "uploads/floor-plan.pdf",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+
+        let search = StructuredMemorySearchTool::new(db);
+        let result = search
+            .execute(json!({
+                "query": "floor plan",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(
+            result
+                .content
+                .ends_with("Floor plan for the new office (attachment: uploads/floor-plan.pdf)"),
+            "{}",
+            result.content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_with_search_text_matches_normalized_form() {
+        let db = test_db();
+        let store = StructuredMemoryStoreTool::new(db.clone());
+        let result = store
+            .execute(json!({
+                "content": "Café «Zürich» — opens @ 7:30!",
+                "search_text": "cafe zurich opens 7 30",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+
+        let search = StructuredMemorySearchTool::new(db);
+        let result = search
+            .execute(json!({
+                "query": "cafe zurich",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(
+            result.content.contains("Café «Zürich» — opens @ 7:30!"),
+            "{}",
+            result.content
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_and_update_title() {
+        let db = test_db();
+        let store = StructuredMemoryStoreTool::new(db.clone());
+        let result = store
+            .execute(json!({
+                "content": "Water twice a week in summer",
+                "title": "Garden routine",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+
+        let search = StructuredMemorySearchTool::new(db.clone());
+        let result = search
+            .execute(json!({
+                "query": "routine",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(
+            result
+                .content
+                .contains("Garden routine: Water twice a week in summer"),
+            "{}",
+            result.content
+        );
+
+        let id = db.search_memories(100, "routine", 1).unwrap()[0].id;
+        let update = StructuredMemoryUpdateTool::new(db.clone());
+        let result = update
+            .execute(json!({
+                "id": id,
+                "content": "Water three times a week in summer",
+                "title": "",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(db.get_memory_by_id(id).unwrap().unwrap().title, None);
+
+        let result = store
+            .execute(json!({
+                "content": "x",
+                "title": "t".repeat(MAX_TITLE_LEN + 1),
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(result.is_error);
+
+        let result = store
+            .execute(json!({
+                "content": "x",
+                "title": "浇".repeat(MAX_TITLE_LEN),
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+    }
+
+    #[tokio::test]
+    async fn test_search_grouped_by_category() {
+        let db = test_db();
+        db.insert_memory(Some(100), "Ship the release notes", "TASK")
+            .unwrap();
+        db.insert_memory(Some(100), "Release train leaves on Fridays", "KNOWLEDGE")
+            .unwrap();
+        db.insert_memory(Some(100), "Draft release blog post", "TASK")
+            .unwrap();
+        let tool = StructuredMemorySearchTool::new(db);
+        let result = tool
+            .execute(json!({
+                "query": "release",
+                "limit": 1,
+                "group_by_category": true,
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        let sections: Vec<&str> = result.content.split("\n\n").collect();
+        assert_eq!(sections.len(), 2, "{}", result.content);
+        assert!(sections[0].starts_with("## KNOWLEDGE\n"));
+        assert!(sections[1].starts_with("## TASK\n"));
+        assert_eq!(sections[1].lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_grouped_skips_unrecognised_categories() {
+        let db = test_db();
+        db.insert_memory(Some(100), "Release checklist lives in the wiki", "MISC")
+            .unwrap();
+        let tool = StructuredMemorySearchTool::new(db);
+        let result = tool
+            .execute(json!({
+                "query": "release",
+                "group_by_category": true,
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert_eq!(result.content, "No memories found matching that query.");
+    }
+
+    #[tokio::test]
+    async fn test_search_rejects_invalid_since() {
+        let db = test_db();
+        let tool = StructuredMemorySearchTool::new(db);
+        let result = tool
+            .execute(json!({"query": "notes", "since": "yesterday"}))
+            .await;
+        assert!(result.is_error);
+        assert!(parse_since("2026-03-01").is_some());
+        assert!(parse_since("2026-03-01T09:00:00+09:00").is_some());
+    }
+
     #[tokio::test]
     async fn test_search_empty_query_errors() {
         let db = test_db();
@@ -324,6 +942,42 @@ mod tests {
         assert!(result.is_error);
     }
 
+    #[tokio::test]
+    async fn test_store_chat_and_global_memory() {
+        let db = test_db();
+        let tool = StructuredMemoryStoreTool::new(db.clone());
+        let result = tool
+            .execute(json!({
+                "content": "User deploys with fly.io",
+                "category": "knowledge",
+                "pinned": true,
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        let memories = db.get_all_memories_for_chat(Some(100)).unwrap();
+        assert_eq!(memories.len(), 1);
+        assert_eq!(memories[0].category, "KNOWLEDGE");
+        assert!(memories[0].pinned);
+
+        let denied = tool
+            .execute(json!({
+                "content": "Team uses Rust",
+                "scope": "global",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(denied.is_error);
+        let allowed = tool
+            .execute(json!({
+                "content": "Team uses Rust",
+                "scope": "global",
+                "__microclaw_auth": {"caller_chat_id": 1, "control_chat_ids": [1]}
+            }))
+            .await;
+        assert!(!allowed.is_error, "{}", allowed.content);
+    }
+
     #[tokio::test]
     async fn test_delete_own_chat_memory() {
         let db = test_db();
@@ -374,6 +1028,56 @@ mod tests {
         assert_eq!(mem.content, "User lives in Osaka");
     }
 
+    #[tokio::test]
+    async fn test_update_memory_category_normalized() {
+        let db = test_db();
+        let id = db
+            .insert_memory(Some(100), "Renew passport before June", "EVENT")
+            .unwrap();
+        let tool = StructuredMemoryUpdateTool::new(db.clone());
+        let result = tool
+            .execute(json!({
+                "id": id,
+                "content": "Renew passport before June",
+                "category": "task",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        let mem = db.get_memory_by_id(id).unwrap().unwrap();
+        assert_eq!(mem.category, "TASK");
+
+        let result = tool
+            .execute(json!({
+                "id": id,
+                "content": "Renew passport before June",
+                "category": "GOSSIP",
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(result.is_error);
+        assert!(result.content.contains("PREFERENCE"));
+    }
+
+    #[tokio::test]
+    async fn test_update_memory_pins() {
+        let db = test_db();
+        let id = db
+            .insert_memory(Some(100), "Always answer in English", "PREFERENCE")
+            .unwrap();
+        let tool = StructuredMemoryUpdateTool::new(db.clone());
+        let result = tool
+            .execute(json!({
+                "id": id,
+                "content": "Always answer in English",
+                "pinned": true,
+                "__microclaw_auth": {"caller_chat_id": 100, "control_chat_ids": []}
+            }))
+            .await;
+        assert!(!result.is_error, "{}", result.content);
+        assert!(db.get_memory_by_id(id).unwrap().unwrap().pinned);
+    }
+
     #[tokio::test]
     async fn test_update_content_too_long() {
         let db = test_db();
@@ -3,7 +3,7 @@ use serde_json::json;
 use std::sync::Arc;
 use tracing::info;
 
-use super::{auth_context_from_input, schema_object, Tool, ToolRegistry, ToolResult};
+use super::{auth_context_from_input, schema_object, Tool, ToolControls, ToolRegistry, ToolResult};
 use crate::config::Config;
 #[cfg(test)]
 use crate::config::WorkingDirIsolation;
@@ -17,6 +17,7 @@ const MAX_SUB_AGENT_ITERATIONS: usize = 10;
 pub struct SubAgentTool {
     config: Config,
     db: Arc<Database>,
+    controls: ToolControls,
 }
 
 impl SubAgentTool {
@@ -24,8 +25,15 @@ impl SubAgentTool {
         SubAgentTool {
             config: config.clone(),
             db,
+            controls: ToolControls::default(),
         }
     }
+
+    /// Share the parent registry's runtime switches with spawned sub-agents.
+    pub fn with_controls(mut self, controls: ToolControls) -> Self {
+        self.controls = controls;
+        self
+    }
 }
 
 #[async_trait]
@@ -66,8 +74,9 @@ impl Tool for SubAgentTool {
         info!("Sub-agent starting task: {}", task);
 
         let llm = crate::llm::create_provider(&self.config);
-        let tools = ToolRegistry::new_sub_agent(&self.config, self.db.clone());
-        let tool_defs = tools.definitions().to_vec();
+        let tools =
+            ToolRegistry::new_sub_agent(&self.config, self.db.clone(), self.controls.clone());
+        let tool_defs = tools.definitions();
 
         let system_prompt = "You are a sub-agent assistant. Complete the given task thoroughly and return a clear, concise result. You have access to tools for file operations, search, and web access. Focus on the task and provide actionable output.".to_string();
 
@@ -263,20 +272,34 @@ mod tests {
     #[test]
     fn test_sub_agent_restricted_registry_tool_count() {
         let config = test_config();
-        let registry = ToolRegistry::new_sub_agent(&config, test_db());
+        let registry = ToolRegistry::new_sub_agent(&config, test_db(), ToolControls::default());
         let defs = registry.definitions();
-        assert_eq!(defs.len(), 12);
+        assert_eq!(defs.len(), 13);
+    }
+
+    #[tokio::test]
+    async fn test_sub_agent_registry_shares_disabled_tools() {
+        let config = test_config();
+        let controls = ToolControls::default();
+        let parent = ToolRegistry::new_sub_agent(&config, test_db(), controls.clone());
+        assert!(parent.set_enabled("bash", false));
+
+        let child = ToolRegistry::new_sub_agent(&config, test_db(), controls);
+        assert!(!child.definitions().iter().any(|d| d.name == "bash"));
+        let result = child.execute("bash", json!({"command": "true"})).await;
+        assert_eq!(result.error_type.as_deref(), Some("tool_disabled"));
     }
 
     #[test]
     fn test_sub_agent_restricted_registry_excluded_tools() {
         let config = test_config();
-        let registry = ToolRegistry::new_sub_agent(&config, test_db());
+        let registry = ToolRegistry::new_sub_agent(&config, test_db(), ToolControls::default());
         let defs = registry.definitions();
         let names: Vec<&str> = defs.iter().map(|d| d.name.as_str()).collect();
 
         // Should include
         assert!(names.contains(&"bash"));
+        assert!(names.contains(&"run_command"));
         assert!(names.contains(&"read_file"));
         assert!(names.contains(&"write_file"));
         assert!(names.contains(&"edit_file"));
@@ -963,6 +963,11 @@ fn redact_config(config: &Config) -> serde_json::Value {
     if cfg.embedding_api_key.is_some() {
         cfg.embedding_api_key = Some("***".into());
     }
+    for fallback in &mut cfg.embedding_fallbacks {
+        if fallback.api_key.is_some() {
+            fallback.api_key = Some("***".into());
+        }
+    }
     if cfg.web_auth_token.is_some() {
         cfg.web_auth_token = Some("***".into());
     }
@@ -991,17 +996,63 @@ async fn index() -> impl IntoResponse {
     }
 }
 
+/// Overall state reported by `/api/health`, so orchestration can tell a
+/// fully working instance from one still serving with reduced recall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum HealthStatus {
+    Healthy,
+    /// Serving, but a dependency is failing, e.g. semantic recall has
+    /// fallen back to keywords while the embedding provider is down.
+    Degraded,
+    /// Requests would fail; answered with 503.
+    Down,
+}
+
 async fn api_health(
     headers: HeaderMap,
     State(state): State<WebState>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, String)> {
     metrics_http_inc(&state).await;
     require_scope(&state, &headers, AuthScope::Read).await?;
-    Ok(Json(json!({
-        "ok": true,
-        "version": env!("CARGO_PKG_VERSION"),
-        "web_enabled": state.app_state.config.web_enabled,
-    })))
+    let mut reasons = Vec::new();
+    // A liveness probe should fail when stores would fail, not just when the
+    // database can still be read.
+    let writable = call_blocking(state.app_state.db.clone(), |db| db.check_writable()).await;
+    if let Err(e) = &writable {
+        reasons.push(format!("database not writable: {e}"));
+    }
+    let embeddings = match &state.app_state.embedding {
+        None => "disabled",
+        Some(p) if p.is_degraded() => {
+            reasons.push("embedding provider failing; recall is keyword-only".to_string());
+            "degraded"
+        }
+        Some(_) => "ok",
+    };
+    let status = if writable.is_err() {
+        HealthStatus::Down
+    } else if reasons.is_empty() {
+        HealthStatus::Healthy
+    } else {
+        HealthStatus::Degraded
+    };
+    let code = if status == HealthStatus::Down {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    Ok((
+        code,
+        Json(json!({
+            "ok": status != HealthStatus::Down,
+            "status": status,
+            "reasons": reasons,
+            "version": env!("CARGO_PKG_VERSION"),
+            "web_enabled": state.app_state.config.web_enabled,
+            "embeddings": embeddings,
+        })),
+    ))
 }
 
 fn map_chat_to_session(registry: &ChannelRegistry, chat: ChatSummary) -> SessionItem {
@@ -1140,8 +1191,11 @@ async fn api_memory_observability(
         Some(resolve_chat_id_for_session_key(&state, &session_key).await?)
     };
 
-    let summary = call_blocking(state.app_state.db.clone(), move |db| {
-        db.get_memory_observability_summary(chat_id_filter)
+    let (summary, by_category) = call_blocking(state.app_state.db.clone(), move |db| {
+        Ok((
+            db.get_memory_observability_summary(chat_id_filter)?,
+            db.count_memories_by_category(chat_id_filter)?,
+        ))
     })
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -1184,6 +1238,10 @@ async fn api_memory_observability(
             "injection_selected_24h": summary.injection_selected_24h,
             "injection_candidates_24h": summary.injection_candidates_24h
         },
+        "by_category": by_category
+            .into_iter()
+            .map(|(category, count)| (category, json!(count)))
+            .collect::<serde_json::Map<_, _>>(),
         "reflector_runs": reflector_runs.iter().map(|r| json!({
             "id": r.id,
             "chat_id": r.chat_id,
@@ -1700,6 +1758,8 @@ mod tests {
             hooks: Arc::new(crate::hooks::HookManager::for_tests()),
             llm,
             embedding: None,
+            embedding_queue: None,
+            recall_cache: None,
             tools: ToolRegistry::new(&cfg, channel_registry, db),
         };
         Arc::new(state)
@@ -1773,6 +1833,27 @@ mod tests {
         assert!(text.contains("event: done"));
     }
 
+    #[tokio::test]
+    async fn test_health_reports_structured_status() {
+        let web_state = test_web_state(Box::new(DummyLlm), None, WebLimits::default());
+        let app = build_router(web_state);
+
+        let req = Request::builder()
+            .method("GET")
+            .uri("/api/health")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(v["status"], "healthy");
+        assert_eq!(v["ok"], true);
+        assert_eq!(v["reasons"], json!([]));
+    }
+
     #[tokio::test]
     async fn test_auth_failure_requires_header() {
         let web_state = test_web_state(
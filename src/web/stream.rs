@@ -102,6 +102,7 @@ pub(super) async fn api_send_stream(
                         status_code,
                         bytes,
                         error_type,
+                        artifacts,
                     } => {
                         run_hub
                             .publish(
@@ -114,7 +115,8 @@ pub(super) async fn api_send_stream(
                                     "duration_ms": duration_ms,
                                     "status_code": status_code,
                                     "bytes": bytes,
-                                    "error_type": error_type
+                                    "error_type": error_type,
+                                    "artifacts": artifacts
                                 })
                                 .to_string(),
                                 run_history_limit,
@@ -13,10 +13,17 @@ fn minimal_config() -> Config {
         llm_base_url: None,
         max_tokens: 8192,
         max_tool_iterations: 25,
+        tool_result_cache_tools: Vec::new(),
+        tool_result_cache_ttl_secs: 60,
+        tool_concurrency_limits: std::collections::HashMap::new(),
         max_history_messages: 50,
         max_document_size_mb: 100,
         memory_token_budget: 1500,
         data_dir: "./microclaw.data".into(),
+        db_path: None,
+        db_backup_interval_hours: 0,
+        db_backup_keep: 7,
+        db_busy_timeout_ms: 5000,
         working_dir: "./tmp".into(),
         working_dir_isolation: WorkingDirIsolation::Chat,
         sandbox: microclaw::config::SandboxConfig::default(),
@@ -44,11 +51,34 @@ fn minimal_config() -> Config {
         embedding_provider: None,
         embedding_api_key: None,
         embedding_base_url: None,
+        embedding_endpoint_path: None,
+        embedding_precomputed_path: None,
         embedding_model: None,
         embedding_dim: None,
+        embedding_distance_metric: "cosine".into(),
+        embedding_query_prefix: None,
+        embedding_document_prefix: None,
+        embedding_fallbacks: Vec::new(),
+        embedding_ensemble: Vec::new(),
+        embedding_ensemble_mode: "concat".into(),
+        embedding_requests_per_minute: 0,
+        embedding_tokens_per_minute: 0,
+        memory_min_similarity: 0.0,
+        memory_knn_candidates: 20,
+        memory_mmr_lambda: 1.0,
+        memory_recall_keyword_only: false,
+        memory_co_recall_tracking: false,
+        memory_recall_stopwords: Vec::new(),
+        embedding_async: false,
+        memory_recall_cache_size: 0,
+        memory_recall_cache_ttl_secs: 30,
         reflector_enabled: true,
         reflector_interval_mins: 15,
         soul_path: None,
+        memory_base_dirs: Vec::new(),
+        memory_file_cache: false,
+        memory_file_max_chars: 0,
+        memory_context_format: "xml".into(),
         clawhub: microclaw::config::ClawHubConfig::default(),
         channels: std::collections::HashMap::new(),
     }